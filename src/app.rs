@@ -1,14 +1,32 @@
-use anyhow::Result;
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use ratatui::{backend::Backend, Terminal};
-use std::collections::{BTreeMap, HashSet};
+use anyhow::{Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{
+    backend::Backend,
+    layout::{Position, Rect},
+    Terminal,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crate::cache::{worktree_fingerprint, DiffCache, DiffKey, UntrackedStats};
 use crate::clipboard;
-use crate::config::Config;
-use crate::git::diff::{parse_diff, FileDiff};
-use crate::git::status::{get_commit_files, get_status};
+use crate::config::{Config, LoadedConfig, TreeConfig};
+use crate::filter::TreeFilter;
+use crate::git::backend::{self, ApplyOptions, GitBackend, ProcessBackend};
+use crate::git::diff::hash_worktree_files;
+use crate::git::diff::{parse_diff, DiffLine, DiffStat, DiffTarget, FileDiff};
+use crate::git::fixup::{self, FixupPlan, StagedHunk};
+use crate::keymap::{Action, KeyChord, KeyContext, Keymap, PendingKeys, Resolution};
+use crate::loader::{DiffRequest, Loaded, Loader};
+use crate::notes::{self, Note, Notes};
+use crate::review::{apply_error, PatchCheck, PatchReview};
+use crate::state::{RepoState, ViewedFiles};
+use crate::theme::{ColorDepth, Theme};
+use crate::ui::diff::StyledDiff;
+use crate::watcher::RepoWatcher;
 
 // ─── Focus ──────────────────────────────────────────────────────────────────
 
@@ -79,6 +97,42 @@ impl DiffTool {
     }
 }
 
+// ─── Tree layout ───────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeLayout {
+    /// One row per directory level
+    #[default]
+    Nested,
+    /// Single-child directory chains collapsed into one row (`src/git`)
+    Compact,
+    /// Files only, with full paths
+    Flat,
+    /// Files with full paths, grouped under their status
+    Grouped,
+}
+
+impl TreeLayout {
+    pub fn name(self) -> &'static str {
+        match self {
+            TreeLayout::Nested => "nested",
+            TreeLayout::Compact => "compact",
+            TreeLayout::Flat => "flat",
+            TreeLayout::Grouped => "grouped",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            TreeLayout::Nested => TreeLayout::Compact,
+            TreeLayout::Compact => TreeLayout::Flat,
+            TreeLayout::Flat => TreeLayout::Grouped,
+            TreeLayout::Grouped => TreeLayout::Nested,
+        }
+    }
+}
+
 // ─── Tree nodes ────────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
@@ -87,9 +141,15 @@ pub struct TreeNode {
     pub name: String,
     pub depth: usize,
     pub is_dir: bool,
+    /// Status group header of the grouped layout; its path is only the label
+    pub is_group: bool,
     pub expanded: bool,
     pub staged: char,
     pub unstaged: char,
+    /// Index of the enclosing directory/group node in `all_nodes`
+    pub parent: Option<usize>,
+    /// Line counts of the file, or the sum over all files below a directory
+    pub stat: DiffStat,
 }
 
 impl TreeNode {
@@ -117,18 +177,34 @@ impl TreeNode {
 
 // ─── TreeSection ──────────────────────────────────────────────────────────
 
+/// (path, staged, unstaged) status of one file listed in a section
+pub type SectionFile = (String, char, char);
+
 pub struct TreeSection {
+    pub pane: TreePane,
+    pub layout: TreeLayout,
+    pub files: Vec<SectionFile>,
+    pub stats: HashMap<String, DiffStat>,
     pub all_nodes: Vec<TreeNode>,
     pub visible: Vec<usize>,
     pub cursor: usize,
+    pub filter: TreeFilter,
+    /// Files whose shown version is marked as viewed
+    pub viewed: HashSet<String>,
 }
 
 impl TreeSection {
-    pub fn new() -> Self {
+    pub fn new(pane: TreePane, layout: TreeLayout) -> Self {
         Self {
+            pane,
+            layout,
+            files: Vec::new(),
+            stats: HashMap::new(),
             all_nodes: Vec::new(),
             visible: Vec::new(),
             cursor: 0,
+            viewed: HashSet::new(),
+            filter: TreeFilter::default(),
         }
     }
 
@@ -138,32 +214,109 @@ impl TreeSection {
             .and_then(|&idx| self.all_nodes.get(idx))
     }
 
-    pub fn rebuild_visible(&mut self) {
-        let expanded: std::collections::HashMap<PathBuf, bool> = self
-            .all_nodes
-            .iter()
-            .filter(|n| n.is_dir)
-            .map(|n| (n.path.clone(), n.expanded))
-            .collect();
+    /// Replace the section's files and line counts and rebuild its nodes
+    pub fn set_files(&mut self, files: Vec<SectionFile>, stats: HashMap<String, DiffStat>) {
+        self.files = files;
+        self.stats = stats;
+        build_section(&mut self.all_nodes, &self.files, self.layout, self.pane);
+        self.aggregate_stats();
+        rebuild_section_visible(self);
+    }
+
+    /// Narrow the section to the files matching `filter`
+    pub fn set_filter(&mut self, filter: TreeFilter) {
+        self.filter = filter;
+        self.aggregate_stats();
+        rebuild_section_visible(self);
+    }
+
+    /// Fill in file line counts and sum those of the files passing the
+    /// filter up into their directories
+    fn aggregate_stats(&mut self) {
+        for node in self.all_nodes.iter_mut().filter(|n| n.is_dir) {
+            node.stat = DiffStat::default();
+        }
+        for i in 0..self.all_nodes.len() {
+            if self.all_nodes[i].is_dir {
+                continue;
+            }
+            let path = self.all_nodes[i].path.to_string_lossy().to_string();
+            let stat = self.stats.get(&path).copied().unwrap_or_default();
+            self.all_nodes[i].stat = stat;
+            if !self.file_matches(&self.all_nodes[i]) {
+                continue;
+            }
+            let ancestors: Vec<usize> = self.ancestors(i).collect();
+            for a in ancestors {
+                self.all_nodes[a].stat.accumulate(&stat);
+            }
+        }
+    }
+
+    /// Summed line counts of the files passing the filter
+    pub fn total_stat(&self) -> DiffStat {
+        let mut total = DiffStat::default();
+        for node in self.all_nodes.iter().filter(|n| !n.is_dir) {
+            if self.file_matches(node) {
+                total.accumulate(&node.stat);
+            }
+        }
+        total
+    }
+
+    /// Rebuild the nodes with another layout, keeping the cursor on the same path
+    pub fn set_layout(&mut self, layout: TreeLayout) {
+        let current = self.current_node().map(|n| (n.is_group, n.path.clone()));
+        self.layout = layout;
+        build_section(&mut self.all_nodes, &self.files, self.layout, self.pane);
+        self.aggregate_stats();
+        rebuild_section_visible(self);
+        if let Some((is_group, path)) = current {
+            if let Some(pos) = self.visible.iter().position(|&idx| {
+                let node = &self.all_nodes[idx];
+                node.is_group == is_group && node.path == path
+            }) {
+                self.cursor = pos;
+            }
+        }
+    }
 
+    pub fn clear(&mut self) {
+        self.files.clear();
+        self.stats.clear();
+        self.viewed.clear();
+        self.all_nodes.clear();
         self.visible.clear();
-        'outer: for (i, node) in self.all_nodes.iter().enumerate() {
-            let mut check = node.path.clone();
-            loop {
-                match check.parent() {
-                    Some(p) if p != Path::new("") => {
-                        if let Some(&exp) = expanded.get(p) {
-                            if !exp {
-                                continue 'outer;
-                            }
-                        }
-                        check = p.to_path_buf();
-                    }
-                    _ => break,
+        self.cursor = 0;
+    }
+
+    /// Whether a file node passes the section's filter
+    fn file_matches(&self, node: &TreeNode) -> bool {
+        self.filter.matches(node, node.status_for(self.pane))
+    }
+
+    fn ancestors(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(self.all_nodes[idx].parent, move |&i| {
+            self.all_nodes[i].parent
+        })
+    }
+
+    pub fn rebuild_visible(&mut self) {
+        // Directories stay visible only while they contain a matching file
+        let mut matching = vec![false; self.all_nodes.len()];
+        for (i, node) in self.all_nodes.iter().enumerate() {
+            if !node.is_dir && self.file_matches(node) {
+                matching[i] = true;
+                for ancestor in self.ancestors(i) {
+                    matching[ancestor] = true;
                 }
             }
-            self.visible.push(i);
         }
+
+        let visible: Vec<usize> = (0..self.all_nodes.len())
+            .filter(|&i| matching[i] && self.ancestors(i).all(|a| self.all_nodes[a].expanded))
+            .collect();
+        self.visible = visible;
     }
 
     pub fn clamp_cursor(&mut self) {
@@ -182,6 +335,28 @@ impl TreeSection {
         self.all_nodes.iter().filter(|n| !n.is_dir).count()
     }
 
+    /// Number of files marked as viewed
+    pub fn viewed_count(&self) -> usize {
+        self.viewed.len()
+    }
+
+    /// Paths of the files passing the filter, in tree order
+    pub fn matching_files(&self) -> Vec<String> {
+        self.all_nodes
+            .iter()
+            .filter(|n| !n.is_dir && self.file_matches(n))
+            .map(|n| n.path.to_string_lossy().to_string())
+            .collect()
+    }
+
+    /// Number of files passing the filter (equals `file_count` when unfiltered)
+    pub fn matching_file_count(&self) -> usize {
+        self.all_nodes
+            .iter()
+            .filter(|n| !n.is_dir && self.file_matches(n))
+            .count()
+    }
+
     /// Expand a directory and move cursor to its first child
     fn expand_and_enter(&mut self) {
         let cursor_vis_idx = self.cursor;
@@ -205,34 +380,34 @@ impl TreeSection {
 
     /// Fold the parent directory of the current node
     fn fold_parent(&mut self) {
-        let current_path = match self.current_node() {
-            Some(n) => n.path.clone(),
+        let parent = match self.visible.get(self.cursor) {
+            Some(&idx) => self.all_nodes[idx].parent,
             None => return,
         };
-        if let Some(parent) = current_path.parent() {
-            if parent == Path::new("") {
-                return;
-            }
-            for (i, node) in self.all_nodes.iter_mut().enumerate() {
-                if node.is_dir && node.path == parent {
-                    node.expanded = false;
-                    self.rebuild_visible();
-                    if let Some(pos) = self.visible.iter().position(|&idx| idx == i) {
-                        self.cursor = pos;
-                    }
-                    self.clamp_cursor();
-                    return;
-                }
+        if let Some(parent) = parent {
+            self.all_nodes[parent].expanded = false;
+            self.rebuild_visible();
+            if let Some(pos) = self.visible.iter().position(|&idx| idx == parent) {
+                self.cursor = pos;
             }
+            self.clamp_cursor();
         }
     }
 
-    /// Collect all file paths under a directory node (for batch stage/unstage)
-    fn files_under_dir(&self, dir_path: &Path) -> Vec<String> {
+    /// Collect all file paths under the directory node at the cursor that
+    /// pass the filter (for batch stage/unstage)
+    fn files_under_current_dir(&self) -> Vec<String> {
+        let dir_idx = match self.visible.get(self.cursor) {
+            Some(&idx) => idx,
+            None => return Vec::new(),
+        };
         self.all_nodes
             .iter()
-            .filter(|n| !n.is_dir && n.path.starts_with(dir_path))
-            .map(|n| n.path.to_string_lossy().to_string())
+            .enumerate()
+            .filter(|(i, n)| {
+                !n.is_dir && self.ancestors(*i).any(|a| a == dir_idx) && self.file_matches(n)
+            })
+            .map(|(_, n)| n.path.to_string_lossy().to_string())
             .collect()
     }
 }
@@ -241,21 +416,210 @@ impl TreeSection {
 
 #[derive(Debug, Clone)]
 pub struct DisplayLineInfo {
+    /// Index into `App::diff_files`
+    pub file_idx: usize,
     pub hunk_idx: Option<usize>,
     pub line_in_hunk: Option<usize>,
     pub is_selectable: bool,
 }
 
+/// One file's part of the diff pane: the only one, or in the continuous
+/// view one of every file of a section
+#[derive(Debug, Clone, Default)]
+pub struct DiffFile {
+    pub path: String,
+    /// The file's own raw diff, which patches of its hunks are built from
+    pub raw: String,
+    /// Where the file starts in the raw and in the displayed lines
+    pub first_raw_line: usize,
+    pub first_display_line: usize,
+    /// Its hunks are `file_diff.hunks[first_hunk..first_hunk + hunk_count]`
+    pub first_hunk: usize,
+    pub hunk_count: usize,
+}
+
+// ─── Mouse state ───────────────────────────────────────────────────────────
+
+const DEFAULT_TREE_WIDTH_PERCENT: u16 = 25;
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+const WHEEL_LINES: usize = 3;
+
+#[derive(Debug, Default)]
+struct MouseState {
+    last_click: Option<(Instant, u16, u16)>,
+    dragging_divider: bool,
+    drag_start_line: Option<usize>,
+}
+
+impl MouseState {
+    /// Record a left click and report whether it completes a double-click
+    fn register_click(&mut self, column: u16, row: u16) -> bool {
+        let now = Instant::now();
+        let double = matches!(
+            self.last_click,
+            Some((at, c, r)) if c == column && r == row && now.duration_since(at) < DOUBLE_CLICK
+        );
+        self.last_click = if double {
+            None
+        } else {
+            Some((now, column, row))
+        };
+        double
+    }
+}
+
 // ─── App ───────────────────────────────────────────────────────────────────
 
+/// Files and line counts of both trees. Built by [`load_status`], which
+/// only needs owned inputs so it can run off the UI thread.
+#[derive(Debug, Default)]
+pub struct StatusSnapshot {
+    unstaged: Vec<SectionFile>,
+    staged: Vec<SectionFile>,
+    unstaged_stats: HashMap<String, DiffStat>,
+    staged_stats: HashMap<String, DiffStat>,
+    /// Old/new blob IDs per changed file, for diff cache keys and viewed
+    /// marks; in commit mode the commit's in `unstaged_blobs`
+    unstaged_blobs: HashMap<String, (String, String)>,
+    staged_blobs: HashMap<String, (String, String)>,
+}
+
+/// Collect the tree contents: the files of `revision` in commit mode,
+/// otherwise `git status` split into unstaged and staged changes.
+pub fn load_status(
+    git: &dyn GitBackend,
+    repo_root: &Path,
+    revision: Option<&str>,
+    tree: &TreeConfig,
+    untracked_stats: &UntrackedStats,
+) -> Result<StatusSnapshot> {
+    let ignored = |path: &str| is_ignored_path(&tree.ignore, path);
+
+    if let Some(rev) = revision {
+        let target = DiffTarget::Commit(rev.to_string());
+        let unstaged = git
+            .commit_files(rev)?
+            .into_iter()
+            .filter(|f| !ignored(&f.path))
+            .map(|f| (f.path, f.staged, f.unstaged))
+            .collect();
+        let unstaged_stats = if tree.diffstat {
+            git.numstat(&target).unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+        return Ok(StatusSnapshot {
+            unstaged,
+            unstaged_stats,
+            unstaged_blobs: git.blob_ids(&target).unwrap_or_default(),
+            ..Default::default()
+        });
+    }
+
+    let files = git.status()?;
+
+    // Split files into unstaged and staged
+    let mut unstaged: Vec<SectionFile> = Vec::new();
+    let mut staged: Vec<SectionFile> = Vec::new();
+
+    for file in files.iter().filter(|f| !ignored(&f.path)) {
+        // Unstaged: Y column ≠ ' ' (includes '?' for untracked)
+        if file.unstaged != ' ' {
+            unstaged.push((file.path.clone(), file.staged, file.unstaged));
+        }
+        // Staged: X column ≠ ' ' AND X column ≠ '?'
+        if file.staged != ' ' && file.staged != '?' {
+            staged.push((file.path.clone(), file.staged, file.unstaged));
+        }
+    }
+
+    let (worktree, index) = (
+        DiffTarget::Worktree { staged: false },
+        DiffTarget::Worktree { staged: true },
+    );
+    let (unstaged_stats, staged_stats) = if tree.diffstat {
+        let mut unstaged_stats = git.numstat(&worktree).unwrap_or_default();
+        let untracked: Vec<&str> = unstaged
+            .iter()
+            .filter(|(_, staged, unstaged)| *staged == '?' && *unstaged == '?')
+            .map(|(path, _, _)| path.as_str())
+            .collect();
+        unstaged_stats.extend(untracked_stats.stats(&untracked, repo_root));
+        let staged_stats = git.numstat(&index).unwrap_or_default();
+        (unstaged_stats, staged_stats)
+    } else {
+        (HashMap::new(), HashMap::new())
+    };
+
+    Ok(StatusSnapshot {
+        unstaged,
+        staged,
+        unstaged_stats,
+        staged_stats,
+        unstaged_blobs: git.blob_ids(&worktree).unwrap_or_default(),
+        staged_blobs: git.blob_ids(&index).unwrap_or_default(),
+    })
+}
+
+/// Paths matched by `tree.ignore` are hidden from both trees
+fn is_ignored_path(patterns: &[String], path: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| crate::filter::path_matches(pattern, path))
+}
+
+/// A patch ready to copy or write
+struct PatchExport {
+    text: String,
+    file_name: String,
+    /// What the patch covers, for the status message
+    what: String,
+}
+
+/// Part of the viewed commit waiting for confirmation before it is
+/// applied to the worktree
+pub struct PendingApply {
+    patch: String,
+    /// What the patch covers, for the prompt and status message
+    pub what: String,
+    pub reverse: bool,
+}
+
+/// `src/app.rs` → `src-app.rs{suffix}.patch`
+fn patch_file_name(path: &str, suffix: &str) -> String {
+    format!(
+        "{}{}.patch",
+        path.trim_end_matches('/').replace('/', "-"),
+        suffix
+    )
+}
+
+/// `dir/name`, or `dir/name-2.patch` and so on if that exists already
+fn unused_path(dir: &Path, name: &str) -> PathBuf {
+    let stem = name.strip_suffix(".patch").unwrap_or(name);
+    std::iter::once(dir.join(name))
+        .chain((2..).map(|n| dir.join(format!("{}-{}.patch", stem, n))))
+        .find(|path| !path.exists())
+        .expect("some numbered name is free")
+}
+
 pub struct App {
     pub should_quit: bool,
     pub focus: Focus,
-    #[allow(dead_code)]
     pub config: Config,
     pub tool: DiffTool,
     pub repo_root: PathBuf,
+    pub git_dir: PathBuf,
+    /// Where written patches go: the directory diffview was started in
+    pub export_dir: PathBuf,
+    pub git: Arc<dyn GitBackend>,
+    pub repo_state: RepoState,
+    pub viewed: ViewedFiles,
     pub commit_revision: Option<String>,
+    /// Commit mode applies the commit reversed (revert) instead of as is
+    pub revert: bool,
+    /// The patch under review (`--patch`), shown instead of the repository's changes
+    pub patch: Option<PatchReview>,
 
     // Tree sections
     pub unstaged: TreeSection,
@@ -265,29 +629,205 @@ pub struct App {
     pub diff_origin: Option<TreePane>,
     pub display_diff: String,
     pub raw_diff: String,
+    /// `display_diff` / `raw_diff` styled for rendering, built once per load
+    pub display_text: StyledDiff,
+    pub raw_text: StyledDiff,
     pub file_diff: FileDiff,
     pub diff_scroll: usize,
     pub diff_cursor: usize,
     pub hunk_cursor: usize,
     pub current_file: Option<String>,
     pub line_infos: Vec<DisplayLineInfo>,
+    /// The files in the diff pane; `current_file` is `None` in the
+    /// continuous view, which shows every file of `diff_origin`
+    pub diff_files: Vec<DiffFile>,
+    pub continuous: bool,
     pub diff_pane_height: usize,
     pub diff_pane_width: u16,
 
+    // Mouse / layout
+    pub tree_width_percent: u16,
+    pub selection_anchor: Option<usize>,
+    mouse: MouseState,
+
+    // Appearance
+    pub theme: Theme,
+
+    // Auto-refresh, started by `run`
+    watcher: Option<RepoWatcher>,
+
+    // Background diff/status loading
+    loader: Loader,
+    diff_cache: DiffCache,
+    untracked_stats: Arc<UntrackedStats>,
+    unstaged_blobs: HashMap<String, (String, String)>,
+    staged_blobs: HashMap<String, (String, String)>,
+
+    // Key bindings
+    pub keymap: Keymap,
+    pending_keys: PendingKeys,
+
+    // Tree filter
+    pub filter_query: String,
+    pub filter_input: Option<String>,
+
+    // Diff search
+    pub search_query: String,
+    pub search_input: Option<String>,
+
+    // Review notes
+    pub notes: Notes,
+    /// The raw diff line each note is shown on, by note index
+    note_lines: HashMap<usize, usize>,
+    pub note_input: Option<String>,
+    /// The line `note_input` comments on
+    note_target: Option<Note>,
+    /// Cursor of the notes popup while it is open
+    pub notes_list: Option<usize>,
+
+    /// Fixup commits shown for confirmation
+    pub fixup_plan: Option<FixupPlan>,
+    /// Cherry-pick or revert shown for confirmation
+    pub pending_apply: Option<PendingApply>,
+
     // Status bar
     pub status_message: Option<String>,
     pub error_message: Option<String>,
 }
 
+/// The config for the repository at `repo_root`; failing to load it is
+/// reported as a problem and the defaults are used
+fn load_config(repo_root: &Path) -> LoadedConfig {
+    Config::load(Some(repo_root)).unwrap_or_else(|err| LoadedConfig {
+        problems: vec![format!("{:#}", err)],
+        ..Default::default()
+    })
+}
+
 impl App {
     pub fn new(tool_override: Option<String>, revision_override: Option<String>) -> Result<Self> {
         let repo_root = crate::git::get_repo_root()?;
+        let config = load_config(&repo_root);
+        Self::open(repo_root, config, tool_override, revision_override)
+    }
+
+    /// Review the patch in `patch_file` against the current repository
+    pub fn new_patch(tool_override: Option<String>, patch_file: &Path) -> Result<Self> {
+        let text = std::fs::read(patch_file)
+            .with_context(|| format!("Failed to read {}", patch_file.display()))?;
+        let name = patch_file.file_name().map_or_else(
+            || patch_file.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        );
+        let repo_root = crate::git::get_repo_root()?;
+        let config = load_config(&repo_root);
+        Self::open_patch(
+            repo_root,
+            config,
+            tool_override,
+            &name,
+            &String::from_utf8_lossy(&text),
+        )
+    }
+
+    /// Browse a diff read from stdin. Outside a repository the diff is
+    /// still shown; only saving the tree layout is unavailable.
+    pub fn new_pager(tool_override: Option<String>, text: &str) -> Result<Self> {
+        let repo_root = match crate::git::get_repo_root() {
+            Ok(root) => root,
+            Err(_) => std::env::current_dir()?,
+        };
+        let config = load_config(&repo_root);
+        Self::open_pager(repo_root, config, tool_override, text)
+    }
+
+    /// Show the files of `text` read-only, from `repo_root` or any directory
+    pub fn open_pager(
+        repo_root: PathBuf,
+        config: LoadedConfig,
+        tool_override: Option<String>,
+        text: &str,
+    ) -> Result<Self> {
+        let review = PatchReview::read_only("stdin", text);
+        if review.files.is_empty() {
+            anyhow::bail!("stdin: no file diffs found");
+        }
+        Self::open_with(repo_root, config, tool_override, None, Some(review))
+    }
+
+    /// Show the files of a patch instead of the repository's changes; they
+    /// are checked against the worktree and applied only on request
+    pub fn open_patch(
+        repo_root: PathBuf,
+        config: LoadedConfig,
+        tool_override: Option<String>,
+        name: &str,
+        text: &str,
+    ) -> Result<Self> {
+        let review = PatchReview::new(name, text);
+        if review.files.is_empty() {
+            anyhow::bail!("{}: no file diffs found", name);
+        }
+        Self::open_with(repo_root, config, tool_override, None, Some(review))
+    }
+
+    /// Open the repository at `repo_root` rather than the one containing
+    /// the current directory
+    pub fn open(
+        repo_root: PathBuf,
+        config: LoadedConfig,
+        tool_override: Option<String>,
+        revision_override: Option<String>,
+    ) -> Result<Self> {
+        Self::open_with(repo_root, config, tool_override, revision_override, None)
+    }
+
+    fn open_with(
+        repo_root: PathBuf,
+        loaded: LoadedConfig,
+        tool_override: Option<String>,
+        revision_override: Option<String>,
+        patch: Option<PatchReview>,
+    ) -> Result<Self> {
+        let git_dir = match crate::git::get_git_dir(&repo_root) {
+            Ok(dir) => dir,
+            Err(_) if patch.as_ref().is_some_and(|p| p.read_only) => repo_root.join(".git"),
+            Err(err) => return Err(err),
+        };
+        let mut state_problems = Vec::new();
+        let repo_state = RepoState::load(&git_dir).unwrap_or_else(|err| {
+            state_problems.push(format!("{:#}", err));
+            RepoState::default()
+        });
+        let notes = Notes::load(&git_dir).unwrap_or_else(|err| {
+            state_problems.push(format!("{:#}", err));
+            Notes::default()
+        });
+        let viewed = ViewedFiles::load(&git_dir).unwrap_or_else(|err| {
+            state_problems.push(format!("{:#}", err));
+            ViewedFiles::default()
+        });
+        let layout = repo_state.tree_layout;
+
+        let config = loaded.config;
+
+        let (git, backend_problem): (Arc<dyn GitBackend>, _) =
+            match backend::open(&config.git.backend, &repo_root) {
+                Ok(git) => (git, None),
+                Err(err) => (
+                    Arc::new(ProcessBackend::new(&repo_root)),
+                    Some(format!("Git backend: {:#}", err)),
+                ),
+            };
+
         let commit_revision = match revision_override {
-            Some(rev) => Some(crate::git::resolve_commit(&rev, &repo_root)?),
+            Some(rev) => Some(git.rev_parse(&format!("{}^{{commit}}", rev))?),
             None => None,
         };
 
-        let config = Config::load().unwrap_or_default();
+        let (keymap, key_problems) = Keymap::new(&config.keys);
+        let depth = ColorDepth::detect(config.theme.color_depth.as_deref());
+        let (theme, theme_problems) = Theme::from_config(&config.theme, depth);
 
         let tool = if let Some(t) = tool_override {
             DiffTool::from_str(&t)
@@ -295,29 +835,64 @@ impl App {
             DiffTool::from_str(&config.diff.tool)
         };
 
+        let continuous = config.diff.continuous;
         let mut app = App {
             should_quit: false,
             focus: Focus::Unstaged,
             config,
             tool,
+            export_dir: std::env::current_dir().unwrap_or_else(|_| repo_root.clone()),
             repo_root,
+            git_dir,
+            git,
+            repo_state,
+            viewed,
             commit_revision,
-            unstaged: TreeSection::new(),
-            staged: TreeSection::new(),
+            revert: false,
+            patch,
+            unstaged: TreeSection::new(TreePane::Unstaged, layout),
+            staged: TreeSection::new(TreePane::Staged, layout),
             diff_origin: None,
             display_diff: String::new(),
             raw_diff: String::new(),
+            display_text: StyledDiff::default(),
+            raw_text: StyledDiff::default(),
             file_diff: FileDiff::default(),
             diff_scroll: 0,
             diff_cursor: 0,
             hunk_cursor: 0,
             current_file: None,
             line_infos: Vec::new(),
+            diff_files: Vec::new(),
+            continuous,
             diff_pane_height: 20,
             diff_pane_width: {
                 let w = crossterm::terminal::size().map(|(w, _)| w).unwrap_or(120);
                 ((w * 3) / 4).saturating_sub(2)
             },
+            tree_width_percent: DEFAULT_TREE_WIDTH_PERCENT,
+            selection_anchor: None,
+            mouse: MouseState::default(),
+            theme,
+            watcher: None,
+            loader: Loader::new(),
+            diff_cache: DiffCache::default(),
+            untracked_stats: Arc::default(),
+            unstaged_blobs: HashMap::new(),
+            staged_blobs: HashMap::new(),
+            keymap,
+            pending_keys: PendingKeys::default(),
+            filter_query: String::new(),
+            filter_input: None,
+            search_query: String::new(),
+            search_input: None,
+            notes,
+            note_lines: HashMap::new(),
+            note_input: None,
+            note_target: None,
+            notes_list: None,
+            fixup_plan: None,
+            pending_apply: None,
             status_message: None,
             error_message: None,
         };
@@ -325,13 +900,28 @@ impl App {
         app.refresh_trees()?;
 
         // Auto-focus: if unstaged is empty but staged has items, start in staged
-        if !app.is_commit_mode() && app.unstaged.is_empty() && !app.staged.is_empty() {
+        if !app.has_single_tree() && app.unstaged.is_empty() && !app.staged.is_empty() {
             app.focus = Focus::Staged;
         }
 
         // Auto-load diff for the first file in the focused section
         app.auto_load_first_diff();
 
+        let mut problems: Vec<String> = backend_problem.into_iter().collect();
+        if !loaded.problems.is_empty() {
+            problems.push(format!("Config: {}", loaded.problems.join("; ")));
+        }
+        if !key_problems.is_empty() {
+            problems.push(format!("Key bindings: {}", key_problems.join("; ")));
+        }
+        if !theme_problems.is_empty() {
+            problems.push(format!("Theme: {}", theme_problems.join("; ")));
+        }
+        problems.extend(state_problems);
+        if !problems.is_empty() {
+            app.error_message = Some(problems.join(" | "));
+        }
+
         Ok(app)
     }
 
@@ -345,7 +935,7 @@ impl App {
         if let Some(node) = section.current_node() {
             if !node.is_dir && (self.is_commit_mode() || !node.is_untracked()) {
                 let path = node.path.to_string_lossy().to_string();
-                let _ = self.load_diff(&path, pane);
+                self.request_diff(&path, pane, false);
             }
         }
     }
@@ -354,6 +944,16 @@ impl App {
         self.commit_revision.is_some()
     }
 
+    /// A diff from stdin, shown read-only
+    pub fn is_pager(&self) -> bool {
+        self.patch.as_ref().is_some_and(|p| p.read_only)
+    }
+
+    /// Commit mode and patch review list their files in one tree
+    pub fn has_single_tree(&self) -> bool {
+        self.is_commit_mode() || self.patch.is_some()
+    }
+
     pub fn commit_label(&self) -> Option<String> {
         self.commit_revision
             .as_ref()
@@ -361,7 +961,11 @@ impl App {
     }
 
     pub fn tree_title(&self, pane: TreePane) -> &'static str {
-        if self.is_commit_mode() {
+        if self.is_pager() {
+            "Diff"
+        } else if self.patch.is_some() {
+            "Patch"
+        } else if self.is_commit_mode() {
             "Files"
         } else {
             pane.label()
@@ -369,8 +973,20 @@ impl App {
     }
 
     pub fn diff_origin_label(&self, pane: TreePane) -> String {
+        if let Some(review) = &self.patch {
+            let check = self.shown_file().and_then(|path| review.checks.get(path));
+            return match check {
+                Some(PatchCheck::Applied) => format!("{}: already applied", review.name),
+                Some(PatchCheck::Fails(reason)) => format!("{}: {}", review.name, reason),
+                _ => review.name.clone(),
+            };
+        }
         if let Some(label) = self.commit_label() {
-            label
+            if self.revert {
+                format!("{}, reverting", label)
+            } else {
+                label
+            }
         } else {
             pane.label().to_lowercase()
         }
@@ -386,7 +1002,7 @@ impl App {
     }
 
     pub fn is_tree_focused(&self, pane: TreePane) -> bool {
-        if self.is_commit_mode() && pane == TreePane::Staged {
+        if self.has_single_tree() && pane == TreePane::Staged {
             return false;
         }
         match pane {
@@ -403,7 +1019,7 @@ impl App {
     }
 
     fn focused_pane(&self) -> Option<TreePane> {
-        if self.is_commit_mode() {
+        if self.has_single_tree() {
             return match self.focus {
                 Focus::Unstaged => Some(TreePane::Unstaged),
                 _ => None,
@@ -420,168 +1036,885 @@ impl App {
     // ─── Tree building ───────────────────────────────────────────────────
 
     pub fn refresh_trees(&mut self) -> Result<()> {
-        if let Some(rev) = self.commit_revision.as_deref() {
-            let files = get_commit_files(rev, &self.repo_root)?;
-            let commit_files: Vec<(String, char, char)> = files
-                .into_iter()
-                .map(|f| (f.path, f.staged, f.unstaged))
-                .collect();
+        // Index writes up to here are diffview's own and covered by this load
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.index_seen();
+        }
+        let snapshot = self.status_snapshot()?;
+        self.apply_status(snapshot);
+        Ok(())
+    }
 
-            build_section(&mut self.unstaged.all_nodes, &commit_files);
-            rebuild_section_visible(&mut self.unstaged);
+    /// The tree contents: the repository's status, or the reviewed patch's
+    /// files after checking them again
+    fn status_snapshot(&mut self) -> Result<StatusSnapshot> {
+        // A watcher load started earlier could finish later with older status
+        self.loader.cancel_status();
+        if let Some(review) = &mut self.patch {
+            if !review.read_only {
+                review.check(self.git.as_ref());
+            }
+            let (unstaged, unstaged_stats) = review.tree_files(self.config.tree.diffstat);
+            return Ok(StatusSnapshot {
+                unstaged,
+                unstaged_stats,
+                ..Default::default()
+            });
+        }
+        load_status(
+            self.git.as_ref(),
+            &self.repo_root,
+            self.commit_revision.as_deref(),
+            &self.config.tree,
+            &self.untracked_stats,
+        )
+    }
 
-            self.staged.all_nodes.clear();
-            self.staged.visible.clear();
-            self.staged.cursor = 0;
+    fn apply_status(&mut self, snapshot: StatusSnapshot) {
+        self.unstaged_blobs = snapshot.unstaged_blobs;
+        self.staged_blobs = snapshot.staged_blobs;
+        self.prune_diff_cache();
+        self.unstaged
+            .set_files(snapshot.unstaged, snapshot.unstaged_stats);
+        if self.has_single_tree() {
+            self.staged.clear();
             if self.focus == Focus::Staged {
                 self.focus = Focus::Unstaged;
             }
-            return Ok(());
+        } else {
+            self.staged
+                .set_files(snapshot.staged, snapshot.staged_stats);
         }
+        self.update_viewed();
+    }
 
-        let files = get_status(&self.repo_root)?;
+    // ─── Viewed marks ────────────────────────────────────────────────────
 
-        // Split files into unstaged and staged
-        let mut unstaged_files: Vec<(String, char, char)> = Vec::new();
-        let mut staged_files: Vec<(String, char, char)> = Vec::new();
+    /// The blob ID of each file's version shown in `pane`: the new blob of
+    /// the commit or the index, or the worktree file hashed. A deleted file
+    /// has its old blob.
+    fn shown_blobs(&self, pane: TreePane, paths: &[String]) -> Result<HashMap<String, String>> {
+        let known = if pane.is_staged() {
+            &self.staged_blobs
+        } else {
+            &self.unstaged_blobs
+        };
+        let is_null = |id: &str| id.bytes().all(|b| b == b'0');
+        let mut blobs = HashMap::new();
+        let mut worktree = Vec::new();
+        for path in paths {
+            let (old, new) = known.get(path).cloned().unwrap_or_default();
+            if !is_null(&new) {
+                blobs.insert(path.clone(), new);
+            } else if !pane.is_staged()
+                && !self.is_commit_mode()
+                && self.repo_root.join(path).is_file()
+            {
+                worktree.push(path.clone());
+            } else if !is_null(&old) {
+                blobs.insert(path.clone(), old);
+            }
+        }
+        blobs.extend(hash_worktree_files(&worktree, &self.repo_root)?);
+        Ok(blobs)
+    }
 
-        for file in &files {
-            // Unstaged: Y column ≠ ' ' (includes '?' for untracked)
-            if file.unstaged != ' ' {
-                unstaged_files.push((file.path.clone(), file.staged, file.unstaged));
+    /// Find the tree files whose shown version was marked as viewed. Marks
+    /// of other versions of the files in the trees are dropped, so they do
+    /// not pile up as a file is edited.
+    fn update_viewed(&mut self) {
+        if self.patch.is_some() {
+            return;
+        }
+        let mut shown: HashMap<String, HashSet<String>> = HashMap::new();
+        for pane in [TreePane::Unstaged, TreePane::Staged] {
+            let paths: Vec<String> = self
+                .tree(pane)
+                .files
+                .iter()
+                .map(|(path, ..)| path.clone())
+                .filter(|path| self.viewed.has_path(path))
+                .collect();
+            let blobs = match self.shown_blobs(pane, &paths) {
+                Ok(blobs) => blobs,
+                Err(e) => {
+                    self.error_message = Some(format!("Viewed files: {:#}", e));
+                    return;
+                }
+            };
+            for path in paths {
+                shown.entry(path).or_default();
             }
-            // Staged: X column ≠ ' ' AND X column ≠ '?'
-            if file.staged != ' ' && file.staged != '?' {
-                staged_files.push((file.path.clone(), file.staged, file.unstaged));
+            let mut viewed = HashSet::new();
+            for (path, blob) in blobs {
+                if self.viewed.contains(&path, &blob) {
+                    viewed.insert(path.clone());
+                }
+                shown.entry(path).or_default().insert(blob);
             }
+            self.tree_mut(pane).viewed = viewed;
         }
+        // Commit mode shows other versions than the worktree's
+        if !self.is_commit_mode() && self.viewed.prune(&shown) {
+            if let Err(e) = self.viewed.save(&self.git_dir) {
+                self.error_message = Some(format!("Failed to save viewed files: {}", e));
+            }
+        }
+    }
 
-        build_section(&mut self.unstaged.all_nodes, &unstaged_files);
-        rebuild_section_visible(&mut self.unstaged);
-
-        build_section(&mut self.staged.all_nodes, &staged_files);
-        rebuild_section_visible(&mut self.staged);
+    /// x key: mark the selected file, every file below the selected
+    /// directory, or the file in the diff as viewed, or unmark them
+    fn toggle_viewed(&mut self) {
+        if self.patch.is_some() {
+            self.error_message = Some("Viewed marks are kept for repositories only".to_string());
+            return;
+        }
+        let target = match self.focused_pane() {
+            Some(pane) => self.tree(pane).current_node().map(|node| {
+                let path = node.path.to_string_lossy().to_string();
+                let paths = if node.is_dir {
+                    self.tree(pane).files_under_current_dir()
+                } else {
+                    vec![path.clone()]
+                };
+                (pane, path, paths)
+            }),
+            None => self
+                .diff_origin
+                .zip(self.shown_file())
+                .map(|(pane, path)| (pane, path.to_string(), vec![path.to_string()])),
+        };
+        let Some((pane, label, paths)) = target else {
+            return;
+        };
+        let blobs = match self.shown_blobs(pane, &paths) {
+            Ok(blobs) => blobs,
+            Err(e) => {
+                self.error_message = Some(format!("Viewed files: {:#}", e));
+                return;
+            }
+        };
+        if blobs.is_empty() {
+            self.error_message = Some(format!("{} has no version to mark", label));
+            return;
+        }
 
-        Ok(())
+        let viewed = !paths
+            .iter()
+            .all(|path| self.tree(pane).viewed.contains(path));
+        for (path, blob) in &blobs {
+            self.viewed.set(path, blob, viewed);
+        }
+        self.update_viewed();
+        if let Err(e) = self.viewed.save(&self.git_dir) {
+            self.error_message = Some(format!("Failed to save viewed files: {}", e));
+            return;
+        }
+        let section = self.tree(pane);
+        self.status_message = Some(format!(
+            "{} {} ({}/{} viewed)",
+            if viewed { "Viewed" } else { "Not viewed" },
+            label,
+            section.viewed_count(),
+            section.file_count()
+        ));
     }
 
     // ─── Diff loading ────────────────────────────────────────────────────
 
-    pub fn load_diff(&mut self, path: &str, pane: TreePane) -> Result<()> {
-        let (raw, display) = if let Some(rev) = self.commit_revision.as_deref() {
-            let raw = crate::git::diff::get_raw_commit_diff(rev, path, &self.repo_root)
-                .unwrap_or_default();
-            let display = crate::git::diff::get_display_commit_diff(
-                rev,
-                path,
-                self.tool.name(),
-                self.diff_pane_width,
-                &self.repo_root,
-            )
-            .unwrap_or_else(|_| raw.clone());
-            (raw, display)
-        } else {
-            let raw = crate::git::diff::get_raw_diff(path, pane.is_staged(), &self.repo_root)
-                .unwrap_or_default();
-            let display = crate::git::diff::get_display_diff(
-                path,
-                pane.is_staged(),
-                self.tool.name(),
-                self.diff_pane_width,
-                &self.repo_root,
-            )
-            .unwrap_or_else(|_| raw.clone());
-            (raw, display)
-        };
-
-        self.raw_diff = raw.clone();
-        self.display_diff = display;
-        self.file_diff = parse_diff(&raw);
-        self.current_file = Some(path.to_string());
-        self.diff_origin = Some(pane);
-        self.diff_scroll = 0;
-        self.diff_cursor = 0;
-        self.hunk_cursor = 0;
-        self.build_line_infos();
+    /// Run the diff commands synchronously, for callers that act on the
+    /// result right away (e.g. after staging lines)
+    fn fetch_diff(&mut self, path: &str, pane: TreePane) -> (String, String) {
+        self.loader.cancel_diff();
+        if let Some(raw) = self.patch_diff(path) {
+            return (raw.clone(), raw);
+        }
+        let key = self.diff_key(path, pane);
+        if let Some(hit) = key.as_ref().and_then(|k| self.diff_cache.get(k)) {
+            return hit;
+        }
 
-        Ok(())
+        let (raw, display) = self.run_diff(path, pane);
+        if let Some(key) = key {
+            self.diff_cache.insert(key, raw.clone(), display.clone());
+        }
+        (raw, display)
     }
 
-    fn clear_diff(&mut self) {
-        self.display_diff.clear();
-        self.raw_diff.clear();
-        self.file_diff = FileDiff::default();
-        self.current_file = None;
-        self.diff_origin = None;
-        self.diff_scroll = 0;
-        self.diff_cursor = 0;
-        self.hunk_cursor = 0;
-        self.line_infos.clear();
-    }
+    fn run_diff(&self, path: &str, pane: TreePane) -> (String, String) {
+        let target = self.diff_target(pane);
+        let raw = self.git.diff_target(&target, path).unwrap_or_default();
+        if self.tool == DiffTool::Raw {
+            return (raw.clone(), raw);
+        }
 
-    fn set_untracked_diff_message(&mut self, path: String, pane: TreePane) {
-        self.display_diff = "(untracked file – press Enter to stage it)".to_string();
-        self.raw_diff = String::new();
-        self.file_diff = FileDiff::default();
-        self.current_file = Some(path);
-        self.diff_origin = Some(pane);
-        self.diff_scroll = 0;
-        self.diff_cursor = 0;
-        self.hunk_cursor = 0;
-        self.line_infos.clear();
+        let display = crate::git::diff::get_display_diff(
+            &target,
+            path,
+            &raw,
+            self.tool.name(),
+            self.diff_pane_width,
+            &self.repo_root,
+        );
+        (raw, display)
     }
 
-    fn build_line_infos(&mut self) {
-        let mut infos: Vec<DisplayLineInfo> = Vec::new();
-        let mut hunk_idx: Option<usize> = None;
-        let mut line_in_hunk: usize = 0;
-        let mut current_hunk_counter = 0usize;
+    fn diff_target(&self, pane: TreePane) -> DiffTarget {
+        match &self.commit_revision {
+            Some(rev) => DiffTarget::Commit(rev.clone()),
+            None => DiffTarget::Worktree {
+                staged: pane.is_staged(),
+            },
+        }
+    }
 
-        for line in self.raw_diff.lines() {
-            if line.starts_with("@@") {
-                hunk_idx = Some(current_hunk_counter);
-                current_hunk_counter += 1;
-                line_in_hunk = 0;
-                infos.push(DisplayLineInfo {
-                    hunk_idx,
-                    line_in_hunk: None,
-                    is_selectable: false,
-                });
-            } else if hunk_idx.is_some() {
-                let is_sel = line.starts_with('+') || line.starts_with('-');
-                infos.push(DisplayLineInfo {
-                    hunk_idx,
-                    line_in_hunk: Some(line_in_hunk),
-                    is_selectable: is_sel,
-                });
-                line_in_hunk += 1;
-            } else {
-                infos.push(DisplayLineInfo {
-                    hunk_idx: None,
-                    line_in_hunk: None,
-                    is_selectable: false,
-                });
+    /// Cache key for a file diff, `None` when the file's versions are unknown
+    fn diff_key(&self, path: &str, pane: TreePane) -> Option<DiffKey> {
+        let target = self.diff_target(pane);
+        let (base, head) = match &target {
+            DiffTarget::Commit(rev) => (String::new(), rev.clone()),
+            DiffTarget::Worktree { staged: true } => self.staged_blobs.get(path)?.clone(),
+            DiffTarget::Worktree { staged: false } => {
+                let (index_blob, _) = self.unstaged_blobs.get(path)?;
+                let worktree = worktree_fingerprint(&self.repo_root.join(path))
+                    .unwrap_or_else(|| "deleted".to_string());
+                (index_blob.clone(), worktree)
             }
-        }
+        };
+        Some(DiffKey {
+            path: path.to_string(),
+            target,
+            base,
+            head,
+            tool: self.tool.name().to_string(),
+            width: if self.tool == DiffTool::Delta {
+                self.diff_pane_width
+            } else {
+                0
+            },
+        })
+    }
 
-        self.line_infos = infos;
+    /// Forget cached diffs of files whose versions changed
+    fn prune_diff_cache(&mut self) {
+        let (unstaged, staged) = (&self.unstaged_blobs, &self.staged_blobs);
+        self.diff_cache.retain(|key| match &key.target {
+            DiffTarget::Commit(_) => true,
+            DiffTarget::Worktree { staged: true } => staged
+                .get(&key.path)
+                .is_some_and(|(base, head)| *base == key.base && *head == key.head),
+            DiffTarget::Worktree { staged: false } => unstaged
+                .get(&key.path)
+                .is_some_and(|(base, _)| *base == key.base),
+        });
+    }
+
+    /// Load a diff in the background. A new file shows a loading indicator
+    /// until the result arrives; a reload (`keep_position`) keeps the old
+    /// content on screen meanwhile. Cached diffs are shown immediately.
+    fn request_diff(&mut self, path: &str, pane: TreePane, keep_position: bool) {
+        if self.continuous {
+            self.show_all_files(pane, Some(path), keep_position);
+            return;
+        }
+        if let Some(raw) = self.patch_diff(path) {
+            self.loader.cancel_diff();
+            self.set_diff_content(path, pane, raw.clone(), raw, keep_position);
+            return;
+        }
+        let key = self.diff_key(path, pane);
+        if let Some((raw, display)) = key.as_ref().and_then(|k| self.diff_cache.get(k)) {
+            self.loader.cancel_diff();
+            self.set_diff_content(path, pane, raw, display, keep_position);
+            return;
+        }
+
+        let request = self.diff_request(path, pane, key, keep_position);
+        if !self.loader.load_diff(request) {
+            let (raw, display) = self.fetch_diff(path, pane);
+            self.set_diff_content(path, pane, raw, display, keep_position);
+            return;
+        }
+
+        if !keep_position {
+            if self.current_file.as_deref() != Some(path) || self.diff_origin != Some(pane) {
+                self.selection_anchor = None;
+            }
+            self.set_diff_text(String::new(), String::new());
+            self.file_diff = FileDiff::default();
+            self.line_infos.clear();
+            self.diff_files.clear();
+            self.current_file = Some(path.to_string());
+            self.diff_origin = Some(pane);
+            self.diff_scroll = 0;
+            self.diff_cursor = 0;
+            self.hunk_cursor = 0;
+        }
+    }
+
+    fn diff_request(
+        &self,
+        path: &str,
+        pane: TreePane,
+        key: Option<DiffKey>,
+        keep_position: bool,
+    ) -> DiffRequest {
+        DiffRequest {
+            key,
+            path: path.to_string(),
+            pane,
+            target: self.diff_target(pane),
+            tool: self.tool.name().to_string(),
+            width: self.diff_pane_width,
+            repo_root: self.repo_root.clone(),
+            git: self.git.clone(),
+            keep_position,
+        }
+    }
+
+    /// A file's diff from the reviewed patch, shown as is
+    fn patch_diff(&self, path: &str) -> Option<String> {
+        Some(self.patch.as_ref()?.file(path)?.raw.clone())
+    }
+
+    /// `raw` parsed; a reviewed patch's files were parsed when it was read
+    fn parsed_diff(&self, path: &str, raw: &str) -> FileDiff {
+        match self.patch.as_ref().and_then(|review| review.file(path)) {
+            Some(file) => file.diff.clone(),
+            None => parse_diff(raw),
+        }
+    }
+
+    /// Whether the diff pane is waiting for a background load
+    pub fn diff_loading_for(&self) -> Option<Duration> {
+        self.loader.diff_loading_for()
+    }
+
+    fn set_diff_content(
+        &mut self,
+        path: &str,
+        pane: TreePane,
+        raw: String,
+        display: String,
+        keep_position: bool,
+    ) {
+        let prev_scroll = self.diff_scroll;
+        let prev_cursor = self.diff_cursor;
+
+        self.file_diff = self.parsed_diff(path, &raw);
+        self.diff_files = vec![DiffFile {
+            path: path.to_string(),
+            raw: raw.clone(),
+            hunk_count: self.file_diff.hunks.len(),
+            ..DiffFile::default()
+        }];
+        self.set_diff_text(raw, display);
+        if self.current_file.as_deref() != Some(path) || self.diff_origin != Some(pane) {
+            self.selection_anchor = None;
+        }
+        self.current_file = Some(path.to_string());
+        self.diff_origin = Some(pane);
+        self.diff_scroll = 0;
+        self.diff_cursor = 0;
+        self.hunk_cursor = 0;
+        self.build_line_infos();
+
+        if keep_position {
+            let line_count = self.raw_text.len();
+            self.diff_scroll = prev_scroll.min(line_count.saturating_sub(1));
+            self.diff_cursor = prev_cursor.min(line_count.saturating_sub(1));
+        }
+    }
+
+    /// Apply loads that finished in the background
+    fn process_loaded(&mut self) {
+        for loaded in self.loader.poll() {
+            match loaded {
+                Loaded::Diff { request, result } => match result {
+                    Ok((raw, display)) => {
+                        if let Some(key) = request.key {
+                            self.diff_cache.insert(key, raw.clone(), display.clone());
+                        }
+                        self.set_diff_content(
+                            &request.path,
+                            request.pane,
+                            raw,
+                            display,
+                            request.keep_position,
+                        )
+                    }
+                    Err(err) => {
+                        self.error_message = Some(format!("Failed to load diff: {:#}", err))
+                    }
+                },
+                Loaded::Diffs { results, scroll_to } => self.show_loaded_files(results, scroll_to),
+                Loaded::Status(result) => match result {
+                    Ok(snapshot) => self.apply_refresh(snapshot),
+                    Err(err) => {
+                        self.error_message = Some(format!("Auto-refresh failed: {:#}", err))
+                    }
+                },
+            }
+        }
+    }
+
+    /// Store the diff text and style it for rendering
+    fn set_diff_text(&mut self, raw: String, display: String) {
+        (self.raw_text, self.display_text) = self.style_diff(&raw, &display);
+        self.raw_diff = raw;
+        self.display_diff = display;
+    }
+
+    /// Raw and displayed lines, styled
+    fn style_diff(&self, raw: &str, display: &str) -> (StyledDiff, StyledDiff) {
+        let raw_text = StyledDiff::new(raw, false, &self.theme);
+        let display_text = if self.tool == DiffTool::Raw && raw == display {
+            raw_text.clone()
+        } else {
+            StyledDiff::new(display, self.tool != DiffTool::Raw, &self.theme)
+        };
+        (raw_text, display_text)
+    }
+
+    /// The styled lines on screen: raw lines while selecting, else the tool's output
+    pub fn diff_text(&self) -> &StyledDiff {
+        if self.focus == Focus::InlineSelect {
+            &self.raw_text
+        } else {
+            &self.display_text
+        }
+    }
+
+    fn clear_diff(&mut self) {
+        self.loader.cancel_diff();
+        self.selection_anchor = None;
+        self.set_diff_text(String::new(), String::new());
+        self.file_diff = FileDiff::default();
+        self.current_file = None;
+        self.diff_origin = None;
+        self.diff_scroll = 0;
+        self.diff_cursor = 0;
+        self.hunk_cursor = 0;
+        self.line_infos.clear();
+        self.diff_files.clear();
+        self.note_lines.clear();
+    }
+
+    fn set_untracked_diff_message(&mut self, path: String, pane: TreePane) {
+        self.loader.cancel_diff();
+        self.set_diff_text(
+            String::new(),
+            "(untracked file – press Enter to stage it)".to_string(),
+        );
+        self.file_diff = FileDiff::default();
+        self.current_file = Some(path);
+        self.diff_origin = Some(pane);
+        self.diff_scroll = 0;
+        self.diff_cursor = 0;
+        self.hunk_cursor = 0;
+        self.line_infos.clear();
+        self.diff_files.clear();
+        self.note_lines.clear();
+    }
+
+    fn build_line_infos(&mut self) {
+        let mut infos: Vec<DisplayLineInfo> = Vec::new();
+        let mut current_hunk_counter = 0usize;
+
+        for (file_idx, file) in self.diff_files.iter().enumerate() {
+            let mut hunk_idx: Option<usize> = None;
+            let mut line_in_hunk: usize = 0;
+            for line in file.raw.lines() {
+                if line.starts_with("@@") {
+                    hunk_idx = Some(current_hunk_counter);
+                    current_hunk_counter += 1;
+                    line_in_hunk = 0;
+                    infos.push(DisplayLineInfo {
+                        file_idx,
+                        hunk_idx,
+                        line_in_hunk: None,
+                        is_selectable: false,
+                    });
+                } else if hunk_idx.is_some() {
+                    let is_sel = line.starts_with('+') || line.starts_with('-');
+                    infos.push(DisplayLineInfo {
+                        file_idx,
+                        hunk_idx,
+                        line_in_hunk: Some(line_in_hunk),
+                        is_selectable: is_sel,
+                    });
+                    line_in_hunk += 1;
+                } else {
+                    infos.push(DisplayLineInfo {
+                        file_idx,
+                        hunk_idx: None,
+                        line_in_hunk: None,
+                        is_selectable: false,
+                    });
+                }
+            }
+        }
+
+        self.line_infos = infos;
+        self.locate_notes();
     }
 
     /// Reload diff for the current file with the current origin
     fn reload_current_diff(&mut self) -> Result<()> {
+        if self.continuous {
+            if let Some(pane) = self.diff_origin {
+                self.load_all_files(pane, true, HashMap::new());
+            }
+            return Ok(());
+        }
         if let (Some(path), Some(pane)) = (self.current_file.clone(), self.diff_origin) {
-            let prev_scroll = self.diff_scroll;
-            let prev_cursor = self.diff_cursor;
-            self.load_diff(&path, pane)?;
-            let line_count = self.raw_diff.lines().count();
-            self.diff_scroll = prev_scroll.min(line_count.saturating_sub(1));
-            self.diff_cursor = prev_cursor.min(line_count.saturating_sub(1));
+            let (raw, display) = self.fetch_diff(&path, pane);
+            self.set_diff_content(&path, pane, raw, display, true);
         }
         Ok(())
     }
 
+    /// Reload the shown diff in the background, keeping the position
+    fn request_reload_current_diff(&mut self) {
+        if self.continuous {
+            if let Some(pane) = self.diff_origin {
+                self.show_all_files(pane, None, true);
+            }
+            return;
+        }
+        if let (Some(path), Some(pane)) = (self.current_file.clone(), self.diff_origin) {
+            self.request_diff(&path, pane, true);
+        }
+    }
+
+    // ─── Continuous view ────────────────────────────────────────────────
+
+    /// Show every file of `pane` in one diff, scrolled to `path`. A reload
+    /// (`keep_position`) fetches the diffs again and stays where it was;
+    /// otherwise diffs already shown are only scrolled. Diffs missing from
+    /// the cache are loaded in the background; a reload keeps the old
+    /// content on screen meanwhile.
+    fn show_all_files(&mut self, pane: TreePane, path: Option<&str>, keep_position: bool) {
+        let shown = self.current_file.is_none()
+            && self.diff_origin == Some(pane)
+            && !self.diff_files.is_empty();
+        if keep_position || !shown {
+            if self.request_all_files(pane, path, keep_position) {
+                return;
+            }
+            self.load_all_files(pane, keep_position, HashMap::new());
+        }
+        if !keep_position {
+            self.scroll_to_path(path);
+        }
+    }
+
+    fn scroll_to_path(&mut self, path: Option<&str>) {
+        if let Some(idx) = path.and_then(|p| self.diff_files.iter().position(|f| f.path == p)) {
+            self.scroll_to_file(idx);
+        }
+    }
+
+    /// Files of `pane` in the continuous view: those passing the filter,
+    /// in tree order
+    fn continuous_paths(&self, pane: TreePane) -> Vec<String> {
+        self.tree(pane)
+            .matching_files()
+            .into_iter()
+            .filter(|path| !self.has_untracked_file_in_pane(pane, path))
+            .collect()
+    }
+
+    /// Start loading the diffs of `pane` that are not cached. False when
+    /// all are at hand or there is no runtime.
+    fn request_all_files(
+        &mut self,
+        pane: TreePane,
+        path: Option<&str>,
+        keep_position: bool,
+    ) -> bool {
+        let requests: Vec<DiffRequest> = self
+            .continuous_paths(pane)
+            .into_iter()
+            .filter(|path| self.patch_diff(path).is_none())
+            .filter_map(|path| {
+                let key = self.diff_key(&path, pane);
+                let cached = key.as_ref().is_some_and(|k| self.diff_cache.contains(k));
+                (!cached).then(|| self.diff_request(&path, pane, key, keep_position))
+            })
+            .collect();
+        if requests.is_empty() || !self.loader.load_diffs(requests, path.map(String::from)) {
+            return false;
+        }
+
+        if !keep_position {
+            self.selection_anchor = None;
+            self.set_diff_text(String::new(), String::new());
+            self.file_diff = FileDiff::default();
+            self.line_infos.clear();
+            self.diff_files.clear();
+            self.current_file = None;
+            self.diff_origin = Some(pane);
+            self.diff_scroll = 0;
+            self.diff_cursor = 0;
+            self.hunk_cursor = 0;
+        }
+        true
+    }
+
+    /// Show the continuous view once its diffs were loaded in the background
+    fn show_loaded_files(
+        &mut self,
+        results: Vec<(DiffRequest, Result<(String, String)>)>,
+        scroll_to: Option<String>,
+    ) {
+        let Some((pane, keep_position)) = results
+            .first()
+            .map(|(request, _)| (request.pane, request.keep_position))
+        else {
+            return;
+        };
+        if !self.continuous {
+            return;
+        }
+        let mut loaded = HashMap::new();
+        for (request, result) in results {
+            match result {
+                Ok((raw, display)) => {
+                    if let Some(key) = request.key {
+                        self.diff_cache.insert(key, raw.clone(), display.clone());
+                    }
+                    loaded.insert(request.path, (raw, display));
+                }
+                Err(err) => self.error_message = Some(format!("Failed to load diff: {:#}", err)),
+            }
+        }
+        self.load_all_files(pane, keep_position, loaded);
+        if !keep_position {
+            self.scroll_to_path(scroll_to.as_deref());
+        }
+    }
+
+    /// Show the diff of every file of `pane` passing the filter one after
+    /// another, taking them from `loaded` or fetching them
+    fn load_all_files(
+        &mut self,
+        pane: TreePane,
+        keep_position: bool,
+        mut loaded: HashMap<String, (String, String)>,
+    ) {
+        self.loader.cancel_diff();
+        let prev_scroll = self.diff_scroll;
+        let prev_cursor = self.diff_cursor;
+        let paths = self.continuous_paths(pane);
+
+        let mut files: Vec<DiffFile> = Vec::new();
+        let mut file_diff = FileDiff::default();
+        let (mut raw_all, mut display_all) = (String::new(), String::new());
+        let (mut raw_text, mut display_text) = (StyledDiff::default(), StyledDiff::default());
+        for path in paths {
+            let (raw, display) = match loaded.remove(&path) {
+                Some(diff) => diff,
+                None => self.fetch_diff(&path, pane),
+            };
+            if raw.is_empty() {
+                continue;
+            }
+            let hunks = self.parsed_diff(&path, &raw).hunks;
+            let (raw_part, display_part) = self.style_diff(&raw, &display);
+            files.push(DiffFile {
+                path,
+                raw: raw.clone(),
+                first_raw_line: raw_text.len(),
+                first_display_line: display_text.len(),
+                first_hunk: file_diff.hunks.len(),
+                hunk_count: hunks.len(),
+            });
+            file_diff.hunks.extend(hunks);
+            raw_text.append(raw_part);
+            display_text.append(display_part);
+            raw_all.push_str(&raw);
+            display_all.push_str(&display);
+        }
+
+        if self.diff_origin != Some(pane) || self.current_file.is_some() {
+            self.selection_anchor = None;
+        }
+        self.raw_text = raw_text;
+        self.display_text = display_text;
+        self.raw_diff = raw_all;
+        self.display_diff = display_all;
+        self.file_diff = file_diff;
+        self.diff_files = files;
+        self.current_file = None;
+        self.diff_origin = Some(pane);
+        self.build_line_infos();
+
+        let line_count = self.diff_text().len();
+        if keep_position {
+            self.diff_scroll = prev_scroll.min(line_count.saturating_sub(1));
+            self.diff_cursor = prev_cursor.min(self.raw_text.len().saturating_sub(1));
+            self.hunk_cursor = self
+                .hunk_cursor
+                .min(self.file_diff.hunks.len().saturating_sub(1));
+        } else {
+            self.diff_scroll = 0;
+            self.diff_cursor = 0;
+            self.hunk_cursor = 0;
+        }
+    }
+
+    /// Whether the diff pane pins the current file's name above its lines
+    pub fn has_sticky_header(&self) -> bool {
+        self.current_file.is_none() && !self.diff_files.is_empty()
+    }
+
+    /// Whether a diff (possibly still loading) is in the pane
+    pub fn has_diff(&self) -> bool {
+        self.current_file.is_some() || !self.diff_files.is_empty()
+    }
+
+    /// Where `file` starts in the lines on screen
+    fn file_start(&self, file: &DiffFile) -> usize {
+        if self.focus == Focus::InlineSelect {
+            file.first_raw_line
+        } else {
+            file.first_display_line
+        }
+    }
+
+    /// Index into `diff_files` of the file shown at `line`
+    pub fn file_index_at(&self, line: usize) -> usize {
+        self.diff_files
+            .iter()
+            .rposition(|file| self.file_start(file) <= line)
+            .unwrap_or(0)
+    }
+
+    /// The line the pane is on: the cursor in line select, else the top
+    fn position_line(&self) -> usize {
+        if self.focus == Focus::InlineSelect {
+            self.diff_cursor
+        } else {
+            self.diff_scroll
+        }
+    }
+
+    /// The file the diff pane is on: the open one, or in the continuous
+    /// view the one at the cursor or the top of the pane
+    pub fn shown_file(&self) -> Option<&str> {
+        if let Some(path) = &self.current_file {
+            return Some(path);
+        }
+        self.diff_files
+            .get(self.file_index_at(self.position_line()))
+            .map(|file| file.path.as_str())
+    }
+
+    /// The file a hunk of `file_diff` belongs to
+    fn file_of_hunk(&self, hunk_idx: usize) -> Option<&DiffFile> {
+        self.diff_files
+            .iter()
+            .find(|f| (f.first_hunk..f.first_hunk + f.hunk_count).contains(&hunk_idx))
+    }
+
+    fn scroll_to_file(&mut self, idx: usize) {
+        let Some(file) = self.diff_files.get(idx) else {
+            return;
+        };
+        let line = self.file_start(file);
+        if file.hunk_count > 0 {
+            self.hunk_cursor = file.first_hunk;
+        }
+        if self.focus == Focus::InlineSelect {
+            self.diff_cursor = line;
+        }
+        self.diff_scroll = line;
+    }
+
+    /// ]/[ keys: the next or previous file, in the continuous view by
+    /// scrolling and otherwise by opening it from the section
+    fn jump_file(&mut self, forward: bool) {
+        let Some(pane) = self.diff_origin else {
+            return;
+        };
+        if self.current_file.is_none() {
+            let line = self.position_line();
+            let idx = self.file_index_at(line);
+            let target = if forward {
+                idx + 1
+            } else if self
+                .diff_files
+                .get(idx)
+                .is_some_and(|file| self.file_start(file) < line)
+            {
+                idx
+            } else {
+                idx.saturating_sub(1)
+            };
+            if target < self.diff_files.len() {
+                self.scroll_to_file(target);
+            }
+            return;
+        }
+
+        let files: Vec<String> = self
+            .tree(pane)
+            .matching_files()
+            .into_iter()
+            .filter(|path| !self.has_untracked_file_in_pane(pane, path))
+            .collect();
+        let current = self.current_file.as_deref();
+        let Some(idx) = files.iter().position(|f| Some(f.as_str()) == current) else {
+            return;
+        };
+        let target = if forward {
+            files.get(idx + 1)
+        } else {
+            idx.checked_sub(1).and_then(|i| files.get(i))
+        };
+        if let Some(path) = target.cloned() {
+            if self.focus == Focus::InlineSelect {
+                self.focus = Focus::DiffView;
+            }
+            self.request_diff(&path, pane, false);
+        }
+    }
+
+    /// a key: switch between one file and every file of the section
+    fn toggle_continuous(&mut self) {
+        let path = self.shown_file().map(String::from);
+        let pane = self.diff_origin.or_else(|| self.focused_pane());
+        self.continuous = !self.continuous;
+        self.status_message = Some(if self.continuous {
+            "All files in one diff".to_string()
+        } else {
+            "One file at a time".to_string()
+        });
+        if self.focus == Focus::InlineSelect {
+            self.focus = Focus::DiffView;
+        }
+
+        match self.focus {
+            Focus::Unstaged | Focus::Staged => {
+                self.clear_diff();
+                self.tree_load_preview();
+            }
+            _ => {
+                let Some(pane) = pane else {
+                    return;
+                };
+                self.clear_diff();
+                let path = path.or_else(|| self.tree(pane).matching_files().into_iter().next());
+                if let Some(path) = path {
+                    self.request_diff(&path, pane, false);
+                }
+            }
+        }
+    }
+
     fn has_untracked_file_in_pane(&self, pane: TreePane, path: &str) -> bool {
-        if self.is_commit_mode() {
+        if self.has_single_tree() {
             return false;
         }
         self.tree(pane)
@@ -591,15 +1924,31 @@ impl App {
     }
 
     fn refresh_latest_state(&mut self) -> Result<()> {
+        let snapshot = self.status_snapshot()?;
+        self.apply_refresh(snapshot);
+
+        self.status_message = Some(if self.is_pager() {
+            "Diff from stdin cannot be read again".to_string()
+        } else if let Some(review) = &self.patch {
+            format!("Checked {} again", review.name)
+        } else if let Some(label) = self.commit_label() {
+            format!("Refreshed {}", label)
+        } else {
+            "Refreshed latest state".to_string()
+        });
+        Ok(())
+    }
+
+    /// Show a fresh status snapshot and reload the diff, keeping focus and
+    /// position where possible
+    fn apply_refresh(&mut self, snapshot: StatusSnapshot) {
         let prev_focus = self.focus.clone();
-        let prev_scroll = self.diff_scroll;
-        let prev_cursor = self.diff_cursor;
         let current = self.current_file.clone().zip(self.diff_origin);
 
-        self.refresh_trees()?;
+        self.apply_status(snapshot);
 
         // Keep focus unless the current tree became empty.
-        if self.is_commit_mode() {
+        if self.has_single_tree() {
             self.focus = match prev_focus {
                 Focus::DiffView | Focus::InlineSelect => Focus::DiffView,
                 _ => Focus::Unstaged,
@@ -623,63 +1972,68 @@ impl App {
                 self.tree_load_preview();
             }
             Focus::DiffView | Focus::InlineSelect => {
-                if let Some((path, pane)) = current {
+                if let (true, Some(pane)) = (self.continuous, self.diff_origin) {
+                    self.show_all_files(pane, None, true);
+                } else if let Some((path, pane)) = current {
                     if self.has_untracked_file_in_pane(pane, &path) {
                         self.set_untracked_diff_message(path, pane);
                     } else {
-                        self.reload_current_diff()?;
-                        let line_count = self.raw_diff.lines().count();
-                        self.diff_scroll = prev_scroll.min(line_count.saturating_sub(1));
-                        self.diff_cursor = prev_cursor.min(line_count.saturating_sub(1));
+                        self.request_diff(&path, pane, true);
                     }
                 } else {
                     self.clear_diff();
                 }
             }
         }
-
-        self.status_message = Some(if let Some(label) = self.commit_label() {
-            format!("Refreshed {}", label)
-        } else {
-            "Refreshed latest state".to_string()
-        });
-        Ok(())
     }
 
     // ─── Main event loop ─────────────────────────────────────────────────
 
     pub fn run<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        self.start_watcher();
+
         loop {
             let size = terminal.size()?;
-            let maximized_diff = matches!(self.focus, Focus::DiffView | Focus::InlineSelect);
-            let next_diff_width = if maximized_diff {
-                size.width.saturating_sub(2)
-            } else {
-                ((size.width * 3) / 4).saturating_sub(2)
-            };
+            let screen = Rect::new(0, 0, size.width, size.height);
+            let next_diff_width = crate::ui::layout(self, screen).diff.width.saturating_sub(2);
             let diff_width_changed = self.diff_pane_width != next_diff_width;
 
             self.diff_pane_width = next_diff_width;
-            self.diff_pane_height = size.height.saturating_sub(3) as usize;
+            self.diff_pane_height = (size.height.saturating_sub(3) as usize)
+                .saturating_sub(usize::from(self.has_sticky_header()));
 
-            if diff_width_changed && self.tool == DiffTool::Delta && self.current_file.is_some() {
-                let _ = self.reload_current_diff();
+            if diff_width_changed && self.tool == DiffTool::Delta && self.has_diff() {
+                self.request_reload_current_diff();
             }
 
+            self.process_loaded();
+
             terminal.draw(|f| crate::ui::render(f, self))?;
 
-            if crossterm::event::poll(Duration::from_millis(50))? {
+            // Poll faster while background loads are running so results show up promptly
+            let timeout = if self.loader.is_busy() { 10 } else { 50 };
+            if crossterm::event::poll(Duration::from_millis(timeout))? {
                 match crossterm::event::read()? {
                     crossterm::event::Event::Key(key) => self.handle_key(key)?,
-                    crossterm::event::Event::Resize(_, _) => {
-                        if self.tool == DiffTool::Delta && self.current_file.is_some() {
-                            let _ = self.reload_current_diff();
-                        }
+                    crossterm::event::Event::Mouse(event) => self.handle_mouse(event, screen)?,
+                    crossterm::event::Event::Resize(_, _)
+                        if self.tool == DiffTool::Delta && self.has_diff() =>
+                    {
+                        self.request_reload_current_diff();
                     }
                     _ => {}
                 }
             }
 
+            if self.pending_keys.expired(Instant::now()) {
+                self.pending_keys.clear();
+                self.status_message = None;
+            }
+
+            if self.watcher.as_mut().is_some_and(|w| w.poll()) {
+                self.auto_refresh();
+            }
+
             if self.should_quit {
                 break;
             }
@@ -687,63 +2041,467 @@ impl App {
         Ok(())
     }
 
+    /// Watch the worktree and index. Commit mode and patch review show
+    /// fixed content, so there is nothing to watch.
+    fn start_watcher(&mut self) {
+        if !self.config.watch.enabled || self.has_single_tree() {
+            return;
+        }
+        let debounce = Duration::from_millis(self.config.watch.debounce_ms);
+        match RepoWatcher::new(&self.repo_root, &self.git_dir, debounce) {
+            Ok(watcher) => self.watcher = Some(watcher),
+            Err(err) => {
+                self.error_message = Some(format!(
+                    "Auto-refresh disabled: {:#} (set watch.enabled = false to silence)",
+                    err
+                ));
+            }
+        }
+    }
+
+    /// Refresh triggered by the watcher. Status is gathered in the
+    /// background and the status bar is left alone, so messages from the
+    /// user's last action stay visible.
+    fn auto_refresh(&mut self) {
+        let git = self.git.clone();
+        let repo_root = self.repo_root.clone();
+        let revision = self.commit_revision.clone();
+        let tree = self.config.tree.clone();
+        let untracked_stats = self.untracked_stats.clone();
+        let load = move || {
+            load_status(
+                git.as_ref(),
+                &repo_root,
+                revision.as_deref(),
+                &tree,
+                &untracked_stats,
+            )
+        };
+        if !self.loader.load_status(load) {
+            match load_status(
+                self.git.as_ref(),
+                &self.repo_root,
+                self.commit_revision.as_deref(),
+                &self.config.tree,
+                &self.untracked_stats,
+            ) {
+                Ok(snapshot) => self.apply_refresh(snapshot),
+                Err(err) => self.error_message = Some(format!("Auto-refresh failed: {:#}", err)),
+            }
+        }
+    }
+
+    // ─── Mouse handling ──────────────────────────────────────────────────
+
+    fn handle_mouse(&mut self, event: MouseEvent, screen: Rect) -> Result<()> {
+        if self.filter_input.is_some()
+            || self.search_input.is_some()
+            || self.note_input.is_some()
+            || self.notes_list.is_some()
+            || self.fixup_plan.is_some()
+            || self.pending_apply.is_some()
+        {
+            return Ok(());
+        }
+
+        let areas = crate::ui::layout(self, screen);
+        let pos = Position::new(event.column, event.row);
+
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                // The divider is the pair of border columns between tree and diff
+                if areas.unstaged.is_some()
+                    && (event.column == areas.diff.x || event.column + 1 == areas.diff.x)
+                {
+                    self.mouse.dragging_divider = true;
+                    return Ok(());
+                }
+
+                let double = self.mouse.register_click(event.column, event.row);
+                for pane in [TreePane::Unstaged, TreePane::Staged] {
+                    if let Some(area) = areas.tree(pane).filter(|a| a.contains(pos)) {
+                        return self.click_tree(pane, area, event.row, double);
+                    }
+                }
+                if areas.diff.contains(pos) {
+                    self.click_diff(areas.diff, event.row);
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if self.mouse.dragging_divider {
+                    let percent = (event.column as u32 * 100 / screen.width.max(1) as u32) as u16;
+                    self.tree_width_percent = percent.clamp(10, 70);
+                } else if let Some(start) = self.mouse.drag_start_line {
+                    self.drag_diff_selection(areas.diff, event.row, start);
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) => {
+                self.mouse.dragging_divider = false;
+                self.mouse.drag_start_line = None;
+            }
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                let down = matches!(event.kind, MouseEventKind::ScrollDown);
+                for pane in [TreePane::Unstaged, TreePane::Staged] {
+                    if areas.tree(pane).is_some_and(|a| a.contains(pos)) {
+                        self.focus = pane.to_focus();
+                        if down {
+                            self.tree_move_down();
+                        } else {
+                            self.tree_move_up();
+                        }
+                        return Ok(());
+                    }
+                }
+                if areas.diff.contains(pos) {
+                    self.scroll_diff_by_wheel(down);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Click in a tree pane: focus it and select the row; double-click
+    /// stages/unstages (opens the diff in commit mode)
+    fn click_tree(&mut self, pane: TreePane, area: Rect, row: u16, double: bool) -> Result<()> {
+        let offset = crate::ui::tree::scroll_offset(self, pane, area.height as usize);
+        if self.tree(pane).is_empty() {
+            return Ok(());
+        }
+        self.focus = pane.to_focus();
+
+        if row > area.y && row + 1 < area.bottom() {
+            let idx = offset + (row - area.y - 1) as usize;
+            if idx < self.tree(pane).visible.len() {
+                self.tree_mut(pane).cursor = idx;
+                self.tree_load_preview();
+                if double {
+                    self.tree_enter()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Display line under a screen row of the diff pane, if any
+    fn diff_line_at(&self, area: Rect, row: u16) -> Option<usize> {
+        // Below the border and the sticky file header, if any
+        let top = area.y + 1 + u16::from(self.has_sticky_header());
+        if row < top || row + 1 >= area.bottom() {
+            return None;
+        }
+        let line = self.diff_scroll + (row - top) as usize;
+        (line < self.diff_line_count()).then_some(line)
+    }
+
+    fn diff_line_count(&self) -> usize {
+        self.diff_text().len()
+    }
+
+    fn click_diff(&mut self, area: Rect, row: u16) {
+        if !self.has_diff() {
+            return;
+        }
+        let line = self.diff_line_at(area, row);
+
+        match self.focus {
+            Focus::Unstaged | Focus::Staged => {
+                self.focus = Focus::DiffView;
+            }
+            Focus::InlineSelect => {
+                if let Some(line) = line {
+                    self.diff_cursor = line;
+                    self.selection_anchor = None;
+                    self.sync_hunk_cursor();
+                }
+            }
+            Focus::DiffView => {}
+        }
+        self.mouse.drag_start_line = line;
+    }
+
+    /// Drag in the diff pane: select the lines between the press and the pointer
+    fn drag_diff_selection(&mut self, area: Rect, row: u16, start: usize) {
+        if self.is_pager() || !self.tool.supports_line_ops() || self.file_diff.hunks.is_empty() {
+            return;
+        }
+
+        // Dragging past the pane edges scrolls
+        if row <= area.y {
+            self.diff_scroll = self.diff_scroll.saturating_sub(1);
+        } else if row + 1 >= area.bottom() {
+            let max_scroll = self.raw_text.len().saturating_sub(1);
+            self.diff_scroll = (self.diff_scroll + 1).min(max_scroll);
+        }
+        let row = row.clamp(area.y + 1, area.bottom().saturating_sub(2));
+        let line = match self.diff_line_at(area, row) {
+            Some(l) => l,
+            None => return,
+        };
+
+        if self.focus != Focus::InlineSelect {
+            self.focus = Focus::InlineSelect;
+        }
+        self.selection_anchor = Some(start);
+        self.diff_cursor = line;
+        self.sync_hunk_cursor();
+    }
+
+    fn scroll_diff_by_wheel(&mut self, down: bool) {
+        let max_scroll = self.diff_line_count().saturating_sub(1);
+        self.diff_scroll = if down {
+            (self.diff_scroll + WHEEL_LINES).min(max_scroll)
+        } else {
+            self.diff_scroll.saturating_sub(WHEEL_LINES)
+        };
+        if self.focus == Focus::InlineSelect {
+            // Keep the cursor inside the viewport
+            let last = self.diff_scroll + self.diff_pane_height.saturating_sub(1);
+            self.diff_cursor = self
+                .diff_cursor
+                .clamp(self.diff_scroll, last.min(max_scroll));
+            self.sync_hunk_cursor();
+        }
+    }
+
     // ─── Key handling ────────────────────────────────────────────────────
 
-    fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
+    pub(crate) fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
         self.error_message = None;
         self.status_message = None;
 
-        if key.code == KeyCode::Char('r') {
-            self.refresh_latest_state()?;
+        if self.filter_input.is_some() {
+            self.handle_filter_input_key(key);
             return Ok(());
         }
+        if self.search_input.is_some() {
+            self.handle_search_input_key(key);
+            return Ok(());
+        }
+        if self.note_input.is_some() {
+            self.handle_note_input_key(key);
+            return Ok(());
+        }
+        if self.notes_list.is_some() {
+            self.handle_notes_list_key(key);
+            return Ok(());
+        }
+        if self.fixup_plan.is_some() {
+            return self.handle_fixup_key(key);
+        }
+        if self.pending_apply.is_some() {
+            return self.handle_pending_apply_key(key);
+        }
 
-        match self.focus {
-            Focus::Unstaged | Focus::Staged => self.handle_tree_key(key)?,
-            Focus::DiffView => self.handle_diff_key(key)?,
-            Focus::InlineSelect => self.handle_inline_select_key(key)?,
+        let context = self.key_context();
+        let chord = KeyChord::from_event(key);
+        let action = match self
+            .pending_keys
+            .feed(&self.keymap, context, chord, Instant::now())
+        {
+            Resolution::Action(action) => action,
+            Resolution::Pending => {
+                self.status_message = Some(self.pending_keys.label());
+                return Ok(());
+            }
+            Resolution::Unbound => return Ok(()),
+        };
+
+        match action {
+            Action::Quit => {
+                self.should_quit = true;
+                return Ok(());
+            }
+            Action::Refresh => {
+                self.refresh_latest_state()?;
+                return Ok(());
+            }
+            Action::Help => {
+                self.status_message = Some(self.help(context));
+                return Ok(());
+            }
+            Action::CopyPatch | Action::WritePatch => {
+                self.export_patch(action == Action::WritePatch);
+                return Ok(());
+            }
+            Action::ApplyToIndex => {
+                self.apply_from_patch(true)?;
+                return Ok(());
+            }
+            Action::ListNotes => {
+                self.open_notes_list();
+                return Ok(());
+            }
+            Action::ToggleRevert => {
+                self.toggle_revert();
+                return Ok(());
+            }
+            Action::CherryPick => {
+                if self.is_commit_mode() {
+                    self.apply_from_patch(false)?;
+                } else {
+                    self.error_message = Some("Only a commit can be cherry-picked".to_string());
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        match context {
+            KeyContext::Tree => self.handle_tree_action(action)?,
+            KeyContext::Diff => self.handle_diff_action(action)?,
+            KeyContext::Select => self.handle_inline_select_action(action)?,
         }
         Ok(())
     }
 
+    /// The help line for a context, labelled for the current mode
+    fn help(&self, context: KeyContext) -> String {
+        let commit = self.is_commit_mode();
+        let pager = self.is_pager();
+        let patch = self.patch.is_some() && !pager;
+        self.keymap
+            .help(context, |action, description| match action {
+                Action::ToggleStage if pager || commit => Some("open"),
+                Action::ToggleStage if patch => Some("apply"),
+                Action::Apply if pager || commit => None,
+                Action::Apply if context == KeyContext::Diff && !patch => None,
+                Action::ApplyToIndex if !patch && !commit => None,
+                Action::CherryPick | Action::ToggleRevert if !commit => None,
+                Action::CherryPick if self.revert => Some("revert"),
+                Action::ToggleRevert => Some("pick/revert"),
+                _ => Some(description),
+            })
+    }
+
+    pub fn key_context(&self) -> KeyContext {
+        match self.focus {
+            Focus::Unstaged | Focus::Staged => KeyContext::Tree,
+            Focus::DiffView => KeyContext::Diff,
+            Focus::InlineSelect => KeyContext::Select,
+        }
+    }
+
     // ─── Tree key handling ──────────────────────────────────────────────
 
-    fn handle_tree_key(&mut self, key: KeyEvent) -> Result<()> {
-        match key.code {
-            KeyCode::Char('q') => {
-                self.should_quit = true;
-            }
-            KeyCode::Char('j') | KeyCode::Down => {
+    fn handle_tree_action(&mut self, action: Action) -> Result<()> {
+        match action {
+            Action::Down => {
                 self.tree_move_down();
             }
-            KeyCode::Char('k') | KeyCode::Up => {
+            Action::Up => {
                 self.tree_move_up();
             }
-            KeyCode::Char('l') | KeyCode::Right => {
+            Action::Open => {
                 self.tree_action_right()?;
             }
-            KeyCode::Char('h') | KeyCode::Left => {
+            Action::Back => {
                 self.tree_action_left();
             }
-            KeyCode::Enter => {
+            Action::ToggleStage => {
                 self.tree_enter()?;
             }
-            KeyCode::Char('c') => {
+            Action::CopyPath => {
                 self.tree_copy_path_to_clipboard();
             }
-            KeyCode::Char('?') => {
-                let help = if self.is_commit_mode() {
-                    "j/k:move  l:open  h:back  Enter:open  c:copy-path  r:refresh  n/p:hunk  q:quit"
-                } else {
-                    "j/k:move  l:open  h:back  Enter:stage/unstage  c:copy-path  r:refresh  v:line-select  n/p:hunk  q:quit"
-                };
-                self.status_message = Some(help.to_string());
+            Action::Filter => {
+                self.filter_input = Some(self.filter_query.clone());
+            }
+            Action::CycleLayout => {
+                self.cycle_tree_layout();
+            }
+            Action::ToggleContinuous => {
+                self.toggle_continuous();
+            }
+            Action::ToggleViewed => {
+                self.toggle_viewed();
+            }
+            Action::Fixup => {
+                self.plan_fixups();
+            }
+            Action::Cancel if !self.filter_query.is_empty() => {
+                self.apply_filter(String::new());
             }
             _ => {}
         }
         Ok(())
     }
 
+    // ─── Filter bar ─────────────────────────────────────────────────────
+
+    fn handle_filter_input_key(&mut self, key: KeyEvent) {
+        let input = match self.filter_input.as_mut() {
+            Some(i) => i,
+            None => return,
+        };
+        match key.code {
+            KeyCode::Esc => {
+                self.filter_input = None;
+            }
+            KeyCode::Enter => {
+                let query = self.filter_input.take().unwrap_or_default();
+                self.apply_filter(query);
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.clear();
+            }
+            KeyCode::Char(c) => {
+                input.push(c);
+            }
+            _ => {}
+        }
+    }
+
+    /// Narrow both tree sections to the files matching `query`
+    fn apply_filter(&mut self, query: String) {
+        let filter = TreeFilter::parse(&query);
+        self.filter_query = query.trim().to_string();
+
+        for section in [&mut self.unstaged, &mut self.staged] {
+            section.set_filter(filter.clone());
+        }
+
+        if !self.has_single_tree() {
+            match self.focus {
+                Focus::Unstaged if self.unstaged.is_empty() && !self.staged.is_empty() => {
+                    self.focus = Focus::Staged;
+                }
+                Focus::Staged if self.staged.is_empty() && !self.unstaged.is_empty() => {
+                    self.focus = Focus::Unstaged;
+                }
+                _ => {}
+            }
+        }
+
+        self.status_message = Some(if filter.is_active() {
+            format!("Filter: {}", self.filter_query)
+        } else {
+            "Filter cleared".to_string()
+        });
+        self.tree_load_preview();
+    }
+
+    /// t key: switch to the next tree layout and remember it for this repository
+    fn cycle_tree_layout(&mut self) {
+        let layout = self.repo_state.tree_layout.next();
+        self.repo_state.tree_layout = layout;
+        self.unstaged.set_layout(layout);
+        self.staged.set_layout(layout);
+
+        if !self.git_dir.is_dir() {
+            self.status_message = Some(format!("Tree layout: {} (not saved)", layout.name()));
+        } else {
+            match self.repo_state.save(&self.git_dir) {
+                Ok(_) => self.status_message = Some(format!("Tree layout: {}", layout.name())),
+                Err(e) => self.error_message = Some(format!("Failed to save layout: {}", e)),
+            }
+        }
+        self.tree_load_preview();
+    }
+
     fn tree_move_down(&mut self) {
         let pane = match self.focused_pane() {
             Some(p) => p,
@@ -757,7 +2515,7 @@ impl App {
 
         if can_move {
             self.tree_mut(pane).cursor += 1;
-        } else if !self.is_commit_mode() && pane == TreePane::Unstaged && !self.staged.is_empty() {
+        } else if !self.has_single_tree() && pane == TreePane::Unstaged && !self.staged.is_empty() {
             self.focus = Focus::Staged;
             self.staged.cursor = 0;
         }
@@ -778,7 +2536,7 @@ impl App {
 
         if can_move {
             self.tree_mut(pane).cursor -= 1;
-        } else if !self.is_commit_mode() && pane == TreePane::Staged && !self.unstaged.is_empty() {
+        } else if !self.has_single_tree() && pane == TreePane::Staged && !self.unstaged.is_empty() {
             self.focus = Focus::Unstaged;
             self.unstaged.cursor = self.unstaged.visible.len().saturating_sub(1);
         }
@@ -809,10 +2567,13 @@ impl App {
             self.tree_mut(pane).expand_and_enter();
             self.tree_load_preview();
         } else {
-            if is_untracked && !self.is_commit_mode() {
+            if is_untracked && !self.is_commit_mode() && !self.continuous {
                 self.set_untracked_diff_message(path, pane);
-            } else {
-                self.load_diff(&path, pane)?;
+            } else if self.current_file.as_deref() != Some(path.as_str())
+                || self.diff_origin != Some(pane)
+            {
+                // Usually already loaded (or loading) by the preview
+                self.request_diff(&path, pane, false);
             }
             self.focus = Focus::DiffView;
         }
@@ -829,11 +2590,15 @@ impl App {
         self.tree_mut(pane).fold_parent();
     }
 
-    /// Enter key: stage/unstage file or dir
+    /// Enter key: stage/unstage file or dir; opens the diff in commit
+    /// mode and the pager, applies in patch review
     fn tree_enter(&mut self) -> Result<()> {
-        if self.is_commit_mode() {
+        if self.is_pager() || self.is_commit_mode() {
             return self.tree_action_right();
         }
+        if self.patch.is_some() {
+            return self.apply_from_patch(false);
+        }
 
         let pane = match self.focused_pane() {
             Some(p) => p,
@@ -851,13 +2616,13 @@ impl App {
         match pane {
             TreePane::Unstaged => {
                 if is_dir {
-                    let files = self.unstaged.files_under_dir(Path::new(&path));
+                    let files = self.unstaged.files_under_current_dir();
                     for file in &files {
-                        let _ = crate::git::apply::stage_file(file, &self.repo_root);
+                        let _ = self.git.stage_file(file);
                     }
                     self.status_message = Some(format!("Staged directory: {}", path));
                 } else {
-                    match crate::git::apply::stage_file(&path, &self.repo_root) {
+                    match self.git.stage_file(&path) {
                         Ok(_) => self.status_message = Some(format!("Staged: {}", path)),
                         Err(e) => {
                             self.error_message = Some(format!("Error: {}", e));
@@ -868,13 +2633,13 @@ impl App {
             }
             TreePane::Staged => {
                 if is_dir {
-                    let files = self.staged.files_under_dir(Path::new(&path));
+                    let files = self.staged.files_under_current_dir();
                     for file in &files {
-                        let _ = crate::git::apply::unstage_file(file, &self.repo_root);
+                        let _ = self.git.unstage_file(file);
                     }
                     self.status_message = Some(format!("Unstaged directory: {}", path));
                 } else {
-                    match crate::git::apply::unstage_file(&path, &self.repo_root) {
+                    match self.git.unstage_file(&path) {
                         Ok(_) => self.status_message = Some(format!("Unstaged: {}", path)),
                         Err(e) => {
                             self.error_message = Some(format!("Error: {}", e));
@@ -898,6 +2663,10 @@ impl App {
         let path = {
             let section = self.tree(pane);
             match section.current_node() {
+                Some(n) if n.is_group => {
+                    self.error_message = Some(format!("{} is a status group, not a path", n.name));
+                    return;
+                }
                 Some(n) => n.path.to_string_lossy().to_string(),
                 None => return,
             }
@@ -935,188 +2704,1002 @@ impl App {
             return;
         }
 
-        if is_untracked && !self.is_commit_mode() {
-            self.set_untracked_diff_message(path, pane);
+        if is_untracked && !self.is_commit_mode() && !self.continuous {
+            self.set_untracked_diff_message(path, pane);
+        } else {
+            let same_file = self.current_file.as_deref() == Some(path.as_str())
+                && self.diff_origin == Some(pane);
+            self.request_diff(&path, pane, same_file);
+        }
+    }
+
+    fn refresh_after_tree_op(&mut self) -> Result<()> {
+        let prev_focus = self.focus.clone();
+        self.refresh_trees()?;
+
+        match prev_focus {
+            Focus::Unstaged if self.unstaged.is_empty() && !self.staged.is_empty() => {
+                self.focus = Focus::Staged;
+            }
+            Focus::Staged if self.staged.is_empty() && !self.unstaged.is_empty() => {
+                self.focus = Focus::Unstaged;
+            }
+            _ => {}
+        }
+
+        self.tree_load_preview();
+        Ok(())
+    }
+
+    // ─── Patch export ───────────────────────────────────────────────────
+
+    /// Copy or write the patch for the current scope
+    fn export_patch(&mut self, write: bool) {
+        let export = match self.patch_to_export() {
+            Ok(export) => export,
+            Err(e) => {
+                self.error_message = Some(format!("Export: {}", e));
+                return;
+            }
+        };
+
+        if !write {
+            match clipboard::copy_text(&export.text) {
+                Ok(_) => self.status_message = Some(format!("Copied patch: {}", export.what)),
+                Err(e) => self.error_message = Some(format!("Clipboard error: {}", e)),
+            }
+            return;
+        }
+
+        let path = unused_path(&self.export_dir, &export.file_name);
+        match std::fs::write(&path, &export.text) {
+            Ok(_) => {
+                self.status_message = Some(format!("Wrote {} ({})", path.display(), export.what))
+            }
+            Err(e) => self.error_message = Some(format!("Export: {}: {}", path.display(), e)),
+        }
+    }
+
+    /// The patch for what the focus is on: the selected lines, the current
+    /// hunk, or the file or directory in the tree. In commit mode the tree
+    /// exports the whole commit in `format-patch` style.
+    fn patch_to_export(&self) -> Result<PatchExport> {
+        match self.key_context() {
+            KeyContext::Tree => {
+                if let Some(rev) = &self.commit_revision {
+                    let (file_name, text) =
+                        crate::git::diff::get_format_patch(rev, &self.repo_root)?;
+                    let short = &rev[..rev.len().min(7)];
+                    return Ok(PatchExport {
+                        text,
+                        file_name,
+                        what: format!("commit {}", short),
+                    });
+                }
+                let (text, path) = self.tree_patch()?;
+                Ok(PatchExport {
+                    text,
+                    file_name: patch_file_name(&path, ""),
+                    what: path,
+                })
+            }
+            KeyContext::Diff => {
+                let (text, what) = self.hunk_patch()?;
+                let file = self
+                    .file_of_hunk(self.hunk_cursor)
+                    .context("No diff open")?;
+                let suffix = format!("-hunk-{}", self.hunk_cursor - file.first_hunk + 1);
+                Ok(PatchExport {
+                    text,
+                    file_name: patch_file_name(&file.path, &suffix),
+                    what,
+                })
+            }
+            KeyContext::Select => {
+                let (text, what) = self.selected_lines_patch()?;
+                let file = self.shown_file().unwrap_or_default();
+                Ok(PatchExport {
+                    text,
+                    file_name: patch_file_name(file, "-lines"),
+                    what,
+                })
+            }
+        }
+    }
+
+    /// Diffs of the file or the files below the directory at the tree
+    /// cursor, and the node's path
+    fn tree_patch(&self) -> Result<(String, String)> {
+        let pane = self.focused_pane().context("No file selected")?;
+        let section = self.tree(pane);
+        let node = section.current_node().context("No file selected")?;
+        let path = node.path.to_string_lossy().to_string();
+        let files = if node.is_dir {
+            section.files_under_current_dir()
+        } else {
+            vec![path.clone()]
+        };
+        let mut text = String::new();
+        for file in &files {
+            match &self.patch {
+                Some(review) => text.extend(review.file(file).map(|f| f.raw.as_str())),
+                None => text.push_str(&self.git.diff_target(&self.diff_target(pane), file)?),
+            }
+        }
+        if text.is_empty() {
+            anyhow::bail!("No diff for {}", path);
+        }
+        Ok((text, path))
+    }
+
+    /// The current hunk as a standalone patch, and what it is
+    fn hunk_patch(&self) -> Result<(String, String)> {
+        let hunk = self
+            .file_diff
+            .hunks
+            .get(self.hunk_cursor)
+            .context("No hunk here")?;
+        let file = self
+            .file_of_hunk(self.hunk_cursor)
+            .context("No diff open")?;
+        Ok((
+            crate::git::apply::export_patch(&file.raw, &[(hunk, None)]),
+            format!(
+                "hunk {} of {}",
+                self.hunk_cursor - file.first_hunk + 1,
+                file.path
+            ),
+        ))
+    }
+
+    /// The selected lines as a standalone patch, and what they are
+    fn selected_lines_patch(&self) -> Result<(String, String)> {
+        let by_file = self.selected_lines_by_file();
+        let mut text = String::new();
+        let mut count = 0;
+        for (file_idx, by_hunk) in &by_file {
+            let file = &self.diff_files[*file_idx];
+            let parts: Vec<_> = by_hunk
+                .iter()
+                .filter_map(|(h, lines)| Some((self.file_diff.hunks.get(*h)?, Some(lines))))
+                .collect();
+            text.push_str(&crate::git::apply::export_patch(&file.raw, &parts));
+            count += by_hunk.values().map(HashSet::len).sum::<usize>();
+        }
+        if count == 0 {
+            anyhow::bail!("Only +/- lines can be selected");
+        }
+        let noun = if count == 1 { "line" } else { "lines" };
+        let what = match by_file.keys().collect::<Vec<_>>().as_slice() {
+            [file_idx] => format!("{} {} of {}", count, noun, self.diff_files[**file_idx].path),
+            files => format!("{} {} in {} files", count, noun, files.len()),
+        };
+        Ok((text, what))
+    }
+
+    // ─── Patch review and cherry-picking ────────────────────────────────
+
+    /// R key in commit mode: switch between cherry-picking and reverting
+    fn toggle_revert(&mut self) {
+        if !self.is_commit_mode() {
+            self.error_message = Some("Only a commit can be reverted".to_string());
+            return;
+        }
+        self.revert = !self.revert;
+        let (enter, index) = (
+            self.keymap.key(Action::CherryPick),
+            self.keymap.key(Action::ApplyToIndex),
+        );
+        self.status_message = Some(if self.revert {
+            format!(
+                "Revert: {} reverts in the worktree, {} in the index",
+                enter, index
+            )
+        } else {
+            format!(
+                "Cherry-pick: {} applies to the worktree, {} to the index",
+                enter, index
+            )
+        });
+    }
+
+    /// Apply in commit mode: point at the keys that do take the commit
+    fn commit_read_only(&mut self) {
+        self.error_message = Some(format!(
+            "The commit is read-only: {} {}s it into the worktree, {} into the index",
+            self.keymap.key(Action::CherryPick),
+            if self.revert { "revert" } else { "cherry-pick" },
+            self.keymap.key(Action::ApplyToIndex),
+        ));
+    }
+
+    /// Apply part of the reviewed patch or the viewed commit to the
+    /// worktree, or the index when `cached`: the file or directory in the
+    /// tree, the current hunk in the diff, or the selected lines. A commit
+    /// is applied reversed while `revert` is on, and only after
+    /// confirmation when it goes to the worktree.
+    fn apply_from_patch(&mut self, cached: bool) -> Result<()> {
+        if self.patch.is_none() && !self.is_commit_mode() {
+            self.error_message =
+                Some("Only a reviewed patch (--patch) or a commit can be applied".to_string());
+            return Ok(());
+        }
+        if self.is_pager() {
+            self.error_message = Some("Diff from stdin is read-only".to_string());
+            return Ok(());
+        }
+        let part = match self.key_context() {
+            KeyContext::Tree => self.tree_patch(),
+            KeyContext::Diff => self.hunk_patch(),
+            KeyContext::Select => self.selected_lines_patch(),
+        };
+        let (patch, what) = match part {
+            Ok(part) => part,
+            Err(e) => {
+                self.error_message = Some(format!("Apply: {}", e));
+                return Ok(());
+            }
+        };
+
+        let reverse = self.is_commit_mode() && self.revert;
+        if self.is_commit_mode() && !cached {
+            self.pending_apply = Some(PendingApply {
+                patch,
+                what,
+                reverse,
+            });
+            return Ok(());
+        }
+        self.run_apply(&patch, &what, cached, reverse)
+    }
+
+    /// The commit-mode confirmation: y or Enter applies, anything else
+    /// cancels
+    fn handle_pending_apply_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Some(pending) = self.pending_apply.take() else {
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Enter | KeyCode::Char('y') => {
+                self.run_apply(&pending.patch, &pending.what, false, pending.reverse)
+            }
+            _ => {
+                self.status_message = Some("Worktree left untouched".to_string());
+                Ok(())
+            }
+        }
+    }
+
+    fn run_apply(&mut self, patch: &str, what: &str, cached: bool, reverse: bool) -> Result<()> {
+        let options = ApplyOptions {
+            cached,
+            reverse,
+            ..ApplyOptions::default()
+        };
+        match self.git.apply(patch, options) {
+            Ok(()) => {
+                let target = if cached { "index" } else { "worktree" };
+                let done = match (self.is_commit_mode(), reverse) {
+                    (true, true) => "Reverted",
+                    (true, false) => "Cherry-picked",
+                    _ => "Applied",
+                };
+                let preposition = if reverse { "in" } else { "to" };
+                self.status_message =
+                    Some(format!("{} {} {} the {}", done, what, preposition, target));
+                self.selection_anchor = None;
+            }
+            Err(e) => self.error_message = Some(format!("Apply failed: {}", apply_error(&e))),
+        }
+        // The checks change once parts are applied
+        self.refresh_trees()
+    }
+
+    // ─── Diff view key handling ─────────────────────────────────────────
+
+    fn handle_diff_action(&mut self, action: Action) -> Result<()> {
+        let line_count = self.display_text.len();
+        let half_page = (self.diff_pane_height / 2).max(1);
+
+        match action {
+            Action::Down if self.diff_scroll + 1 < line_count => {
+                self.diff_scroll += 1;
+            }
+            Action::Up if self.diff_scroll > 0 => {
+                self.diff_scroll -= 1;
+            }
+            Action::HalfPageDown => {
+                self.diff_scroll = (self.diff_scroll + half_page).min(line_count.saturating_sub(1));
+            }
+            Action::HalfPageUp => {
+                self.diff_scroll = self.diff_scroll.saturating_sub(half_page);
+            }
+            Action::Top => {
+                self.diff_scroll = 0;
+            }
+            Action::Bottom => {
+                self.diff_scroll = line_count.saturating_sub(1);
+            }
+            Action::NextHunk => self.jump_next_hunk(),
+            Action::PrevHunk => self.jump_prev_hunk(),
+            Action::Search => self.search_input = Some(String::new()),
+            Action::SearchNext => self.search(true, false),
+            Action::SearchPrev => self.search(false, false),
+            Action::NextFile => self.jump_file(true),
+            Action::PrevFile => self.jump_file(false),
+            Action::ToggleContinuous => self.toggle_continuous(),
+            Action::Annotate => self.start_note(),
+            Action::ToggleViewed => self.toggle_viewed(),
+            Action::Fixup => self.plan_fixups(),
+            Action::Apply => self.apply_current_hunk()?,
+            Action::Back => {
+                self.focus = self
+                    .diff_origin
+                    .map(|p| p.to_focus())
+                    .unwrap_or(Focus::Unstaged);
+            }
+            Action::LineSelect => {
+                if self.tool.supports_line_ops() {
+                    if self.file_diff.hunks.is_empty() {
+                        self.error_message = Some("No hunks to select lines from".to_string());
+                    } else {
+                        self.focus = Focus::InlineSelect;
+                        self.diff_cursor = self.diff_scroll;
+                        let apply = if self.is_commit_mode() {
+                            Action::CherryPick
+                        } else {
+                            Action::Apply
+                        };
+                        self.status_message = Some(format!(
+                            "Inline select: {}/{} move  {} apply  {}/{} exit",
+                            self.keymap.key(Action::Down),
+                            self.keymap.key(Action::Up),
+                            self.keymap.key(apply),
+                            self.keymap.key(Action::LineSelect),
+                            self.keymap.key(Action::Back),
+                        ));
+                    }
+                } else {
+                    self.error_message =
+                        Some("Line selection unavailable with difftastic".to_string());
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Enter in the diff: in patch review apply the current hunk. The
+    /// worktree diff stages hunks from line-select only.
+    fn apply_current_hunk(&mut self) -> Result<()> {
+        if self.is_commit_mode() {
+            self.commit_read_only();
+        } else if self.patch.is_some() {
+            self.apply_from_patch(false)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the shown hunks of the files at `file_idxs` still match the
+    /// worktree. An unstaged diff may come from the cache, whose worktree
+    /// side is only a fingerprint, so it is checked against a fresh diff
+    /// before staging from it; a stale diff is dropped and reloaded.
+    fn shown_hunks_current(&mut self, file_idxs: &[usize]) -> Result<bool> {
+        if self.diff_origin != Some(TreePane::Unstaged) {
+            return Ok(true);
+        }
+        let target = self.diff_target(TreePane::Unstaged);
+        for &idx in file_idxs {
+            let Some(file) = self.diff_files.get(idx) else {
+                continue;
+            };
+            let fresh = parse_diff(&self.git.diff_target(&target, &file.path)?).hunks;
+            let shown = self
+                .file_diff
+                .hunks
+                .get(file.first_hunk..file.first_hunk + file.hunk_count)
+                .unwrap_or_default();
+            if fresh.as_slice() != shown {
+                let path = file.path.clone();
+                if let Some(key) = self.diff_key(&path, TreePane::Unstaged) {
+                    self.diff_cache.remove(&key);
+                }
+                self.reload_current_diff()?;
+                self.error_message = Some(format!(
+                    "{} changed since its diff was shown; reloaded it",
+                    path
+                ));
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn jump_next_hunk(&mut self) {
+        let count = self.file_diff.hunks.len();
+        if count == 0 {
+            return;
+        }
+        if self.hunk_cursor + 1 < count {
+            self.hunk_cursor += 1;
+        }
+        self.scroll_to_hunk(self.hunk_cursor);
+    }
+
+    fn jump_prev_hunk(&mut self) {
+        if self.file_diff.hunks.is_empty() {
+            return;
+        }
+        if self.hunk_cursor > 0 {
+            self.hunk_cursor -= 1;
+        }
+        self.scroll_to_hunk(self.hunk_cursor);
+    }
+
+    fn scroll_to_hunk(&mut self, hunk_idx: usize) {
+        if let Some(&line_no) = self.diff_text().hunk_starts.get(hunk_idx) {
+            if self.focus == Focus::InlineSelect {
+                self.diff_cursor = line_no;
+            }
+            self.diff_scroll = line_no;
+        }
+    }
+
+    // ─── Diff search ────────────────────────────────────────────────────
+
+    fn handle_search_input_key(&mut self, key: KeyEvent) {
+        let input = match self.search_input.as_mut() {
+            Some(i) => i,
+            None => return,
+        };
+        match key.code {
+            KeyCode::Esc => {
+                self.search_input = None;
+            }
+            KeyCode::Enter => {
+                let query = self.search_input.take().unwrap_or_default();
+                // An empty query repeats the last search, as in `less`
+                let repeat = query.is_empty();
+                if !repeat {
+                    self.search_query = query;
+                }
+                self.search(true, !repeat);
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.clear();
+            }
+            KeyCode::Char(c) => {
+                input.push(c);
+            }
+            _ => {}
+        }
+    }
+
+    /// Scroll to the next (or previous) diff line containing the search
+    /// query, wrapping around; a new search may match the top line itself.
+    /// Matching ignores case unless the query has capitals.
+    fn search(&mut self, forward: bool, include_top: bool) {
+        if self.search_query.is_empty() {
+            self.error_message = Some(format!(
+                "No search yet: press {}",
+                self.keymap.key(Action::Search)
+            ));
+            return;
+        }
+        let ignore_case = !self.search_query.chars().any(char::is_uppercase);
+        let query = if ignore_case {
+            self.search_query.to_lowercase()
+        } else {
+            self.search_query.clone()
+        };
+        let matches: Vec<usize> = self
+            .diff_text()
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                let text: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+                if ignore_case {
+                    text.to_lowercase().contains(&query)
+                } else {
+                    text.contains(&query)
+                }
+            })
+            .map(|(i, _)| i)
+            .collect();
+        if matches.is_empty() {
+            self.error_message = Some(format!("Not found: {}", self.search_query));
+            return;
+        }
+
+        let found = if forward {
+            matches
+                .iter()
+                .position(|&i| i > self.diff_scroll || (include_top && i == self.diff_scroll))
         } else {
-            let _ = self.load_diff(&path, pane);
+            matches.iter().rposition(|&i| i < self.diff_scroll)
+        };
+        let (index, wrapped) = match found {
+            Some(index) => (index, false),
+            None if forward => (0, true),
+            None => (matches.len() - 1, true),
+        };
+        let line = matches[index];
+        self.diff_scroll = line;
+        if let Some(hunk) = self
+            .diff_text()
+            .hunk_starts
+            .iter()
+            .rposition(|&start| start <= line)
+        {
+            self.hunk_cursor = hunk;
         }
+        self.status_message = Some(format!(
+            "/{}: match {}/{}{}",
+            self.search_query,
+            index + 1,
+            matches.len(),
+            if wrapped { " (wrapped)" } else { "" }
+        ));
     }
 
-    fn refresh_after_tree_op(&mut self) -> Result<()> {
-        let prev_focus = self.focus.clone();
-        self.refresh_trees()?;
+    // ─── Review notes ───────────────────────────────────────────────────
 
-        match prev_focus {
-            Focus::Unstaged if self.unstaged.is_empty() && !self.staged.is_empty() => {
-                self.focus = Focus::Staged;
-            }
-            Focus::Staged if self.staged.is_empty() && !self.unstaged.is_empty() => {
-                self.focus = Focus::Unstaged;
+    /// The note a diff line would get: its path, content and line number
+    fn line_anchor(&self, line_idx: usize) -> Option<Note> {
+        let info = self.line_infos.get(line_idx)?;
+        let (hunk_idx, line_in_hunk) = (info.hunk_idx?, info.line_in_hunk?);
+        let hunk = self.file_diff.hunks.get(hunk_idx)?;
+        let (side, line) = *notes::line_numbers(hunk).get(line_in_hunk)?;
+        let content = match hunk.lines.get(line_in_hunk)? {
+            DiffLine::Context(text) | DiffLine::Added(text) | DiffLine::Removed(text) => text,
+        };
+        Some(Note {
+            path: self.diff_files.get(info.file_idx)?.path.clone(),
+            line,
+            side,
+            commit: self.commit_revision.clone(),
+            content: content.clone(),
+            text: String::new(),
+        })
+    }
+
+    /// Find the diff line of every note on a shown file: the one with the
+    /// note's content nearest to its line number
+    fn locate_notes(&mut self) {
+        self.note_lines.clear();
+        if self.notes.is_empty() {
+            return;
+        }
+        let noted: HashSet<&str> = self.notes.notes.iter().map(|n| n.path.as_str()).collect();
+        let numbers: Vec<Vec<(notes::Side, u32)>> = self
+            .file_diff
+            .hunks
+            .iter()
+            .map(notes::line_numbers)
+            .collect();
+        let commit = self.commit_revision.as_ref();
+        // (line index, path, side, line number, content) of lines of noted files
+        let mut anchors = Vec::new();
+        for (idx, info) in self.line_infos.iter().enumerate() {
+            let (Some(hunk_idx), Some(line_in_hunk)) = (info.hunk_idx, info.line_in_hunk) else {
+                continue;
+            };
+            let Some(path) = self.diff_files.get(info.file_idx).map(|f| f.path.as_str()) else {
+                continue;
+            };
+            if !noted.contains(path) {
+                continue;
             }
-            _ => {}
+            let (Some(&(side, line)), Some(diff_line)) = (
+                numbers.get(hunk_idx).and_then(|n| n.get(line_in_hunk)),
+                self.file_diff.hunks[hunk_idx].lines.get(line_in_hunk),
+            ) else {
+                continue;
+            };
+            let content = match diff_line {
+                DiffLine::Context(text) | DiffLine::Added(text) | DiffLine::Removed(text) => text,
+            };
+            anchors.push((idx, path, side, line, content.as_str()));
         }
-
-        self.tree_load_preview();
-        Ok(())
+        let note_lines = self
+            .notes
+            .notes
+            .iter()
+            .enumerate()
+            .filter(|(_, note)| note.commit.as_ref() == commit)
+            .filter_map(|(note_idx, note)| {
+                anchors
+                    .iter()
+                    .filter(|(_, path, side, _, content)| {
+                        *path == note.path && *side == note.side && *content == note.content
+                    })
+                    .min_by_key(|(_, _, _, line, _)| line.abs_diff(note.line))
+                    .map(|(line_idx, ..)| (note_idx, *line_idx))
+            })
+            .collect();
+        self.note_lines = note_lines;
     }
 
-    // ─── Diff view key handling ─────────────────────────────────────────
+    /// The note shown after raw diff line `line`. Notes are drawn only on
+    /// raw lines; delta and difftastic output has other line numbers.
+    pub fn note_on(&self, line: usize) -> Option<&Note> {
+        if self.focus != Focus::InlineSelect && self.tool != DiffTool::Raw {
+            return None;
+        }
+        self.note_lines
+            .iter()
+            .find(|(_, l)| **l == line)
+            .and_then(|(idx, _)| self.notes.notes.get(*idx))
+    }
 
-    fn handle_diff_key(&mut self, key: KeyEvent) -> Result<()> {
-        let line_count = self.display_diff.lines().count();
-        let half_page = (self.diff_pane_height / 2).max(1);
+    /// c key: comment on the cursor line, or in the diff on the current
+    /// hunk's first changed line. An existing note is edited.
+    fn start_note(&mut self) {
+        if !self.git_dir.is_dir() {
+            self.error_message = Some("Notes are kept in a repository".to_string());
+            return;
+        }
+        let line = if self.focus == Focus::InlineSelect {
+            Some(self.diff_cursor)
+        } else {
+            self.raw_text
+                .hunk_starts
+                .get(self.hunk_cursor)
+                .and_then(|&start| {
+                    (start..self.line_infos.len()).find(|&idx| self.line_infos[idx].is_selectable)
+                })
+        };
+        let Some(anchor) = line.and_then(|idx| self.line_anchor(idx)) else {
+            self.error_message = Some("No diff line to comment on".to_string());
+            return;
+        };
+        let existing = self
+            .notes
+            .find(&anchor)
+            .filter(|idx| line.is_some_and(|l| self.note_lines.get(idx) == Some(&l)));
+        let (text, anchor) = match existing {
+            Some(idx) => (
+                self.notes.notes[idx].text.clone(),
+                self.notes.notes[idx].clone(),
+            ),
+            None => (String::new(), anchor),
+        };
+        self.note_input = Some(text);
+        self.note_target = Some(anchor);
+    }
 
+    fn handle_note_input_key(&mut self, key: KeyEvent) {
+        let input = match self.note_input.as_mut() {
+            Some(i) => i,
+            None => return,
+        };
         match key.code {
-            KeyCode::Char('q') => {
-                self.should_quit = true;
-            }
-            KeyCode::Char('j') | KeyCode::Down => {
-                if self.diff_scroll + 1 < line_count {
-                    self.diff_scroll += 1;
-                }
+            KeyCode::Esc => {
+                self.note_input = None;
+                self.note_target = None;
             }
-            KeyCode::Char('k') | KeyCode::Up => {
-                if self.diff_scroll > 0 {
-                    self.diff_scroll -= 1;
+            KeyCode::Enter => {
+                let text = self.note_input.take().unwrap_or_default();
+                if let Some(target) = self.note_target.take() {
+                    self.save_note(Note { text, ..target });
                 }
             }
-            KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                self.diff_scroll = (self.diff_scroll + half_page).min(line_count.saturating_sub(1));
+            KeyCode::Backspace => {
+                input.pop();
             }
             KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                self.diff_scroll = self.diff_scroll.saturating_sub(half_page);
-            }
-            KeyCode::Char('g') => {
-                self.diff_scroll = 0;
+                input.clear();
             }
-            KeyCode::Char('G') => {
-                self.diff_scroll = line_count.saturating_sub(1);
+            KeyCode::Char(c) => {
+                input.push(c);
             }
-            KeyCode::Char('n') => self.jump_next_hunk(),
-            KeyCode::Char('p') => self.jump_prev_hunk(),
-            KeyCode::Char('h') | KeyCode::Left => {
-                self.focus = self
-                    .diff_origin
-                    .map(|p| p.to_focus())
-                    .unwrap_or(Focus::Unstaged);
+            _ => {}
+        }
+    }
+
+    fn save_note(&mut self, note: Note) {
+        let location = note.location();
+        let removed = note.text.trim().is_empty();
+        self.notes.set(note);
+        self.locate_notes();
+        match self.notes.save(&self.git_dir) {
+            Ok(_) if removed => self.status_message = Some(format!("Note on {} removed", location)),
+            Ok(_) => self.status_message = Some(format!("Note on {} saved", location)),
+            Err(e) => self.error_message = Some(format!("Failed to save notes: {}", e)),
+        }
+    }
+
+    /// C key: the notes popup
+    fn open_notes_list(&mut self) {
+        if self.notes.is_empty() {
+            self.status_message = Some(format!(
+                "No notes yet ({} comments on a diff line)",
+                self.keymap.key(Action::Annotate)
+            ));
+        } else {
+            self.notes_list = Some(0);
+        }
+    }
+
+    fn handle_notes_list_key(&mut self, key: KeyEvent) {
+        let Some(cursor) = self.notes_list else {
+            return;
+        };
+        let last = self.notes.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('C') => self.notes_list = None,
+            KeyCode::Char('j') | KeyCode::Down => self.notes_list = Some((cursor + 1).min(last)),
+            KeyCode::Char('k') | KeyCode::Up => self.notes_list = Some(cursor.saturating_sub(1)),
+            KeyCode::Enter => {
+                self.notes_list = None;
+                self.open_note(cursor);
             }
-            KeyCode::Char('v') => {
-                if self.is_commit_mode() {
-                    self.error_message = Some("Commit diff is read-only".to_string());
-                } else if self.tool.supports_line_ops() {
-                    if self.file_diff.hunks.is_empty() {
-                        self.error_message = Some("No hunks to select lines from".to_string());
-                    } else {
-                        self.focus = Focus::InlineSelect;
-                        self.diff_cursor = self.diff_scroll;
-                        self.status_message =
-                            Some("Inline select: j/k move  Enter apply  v/h exit".to_string());
+            KeyCode::Char('d') => {
+                if let Some(note) = self.notes.remove(cursor) {
+                    self.locate_notes();
+                    match self.notes.save(&self.git_dir) {
+                        Ok(_) => {
+                            self.status_message =
+                                Some(format!("Note on {} removed", note.location()))
+                        }
+                        Err(e) => self.error_message = Some(format!("Failed to save notes: {}", e)),
                     }
-                } else {
-                    self.error_message =
-                        Some("Line selection unavailable with difftastic".to_string());
                 }
+                self.notes_list =
+                    (!self.notes.is_empty()).then(|| cursor.min(last.saturating_sub(1)));
             }
+            KeyCode::Char('y') => self.export_notes(false),
+            KeyCode::Char('w') => self.export_notes(true),
             _ => {}
         }
-        Ok(())
     }
 
-    fn jump_next_hunk(&mut self) {
-        let count = self.file_diff.hunks.len();
-        if count == 0 {
+    /// Copy the notes as markdown, or write them to `notes.md`
+    fn export_notes(&mut self, write: bool) {
+        let text = notes::to_markdown(&self.notes.resolved(&self.repo_root));
+        let count = self.notes.len();
+        let what = format!("{} note{}", count, if count == 1 { "" } else { "s" });
+        if !write {
+            match clipboard::copy_text(&text) {
+                Ok(_) => self.status_message = Some(format!("Copied {} as markdown", what)),
+                Err(e) => self.error_message = Some(format!("Clipboard error: {}", e)),
+            }
             return;
         }
-        if self.hunk_cursor + 1 < count {
-            self.hunk_cursor += 1;
+        let path = unused_path(&self.export_dir, "notes.md");
+        match std::fs::write(&path, text) {
+            Ok(_) => self.status_message = Some(format!("Wrote {} ({})", path.display(), what)),
+            Err(e) => self.error_message = Some(format!("Export: {}: {}", path.display(), e)),
         }
-        self.scroll_to_hunk(self.hunk_cursor);
     }
 
-    fn jump_prev_hunk(&mut self) {
-        if self.file_diff.hunks.is_empty() {
+    /// Show the diff a note is on, scrolled to its line
+    fn open_note(&mut self, idx: usize) {
+        let Some(note) = self.notes.notes.get(idx).cloned() else {
+            return;
+        };
+        if !self.diff_files.iter().any(|f| f.path == note.path) {
+            let panes: &[TreePane] = if self.has_single_tree() {
+                &[TreePane::Unstaged]
+            } else {
+                &[TreePane::Unstaged, TreePane::Staged]
+            };
+            let pane = panes.iter().copied().find(|&pane| {
+                self.tree(pane)
+                    .files
+                    .iter()
+                    .any(|(path, ..)| *path == note.path)
+                    && !self.has_untracked_file_in_pane(pane, &note.path)
+            });
+            let Some(pane) = pane else {
+                self.error_message = Some(format!("{} has no changes to show", note.path));
+                return;
+            };
+            if self.focus == Focus::InlineSelect {
+                self.focus = Focus::DiffView;
+            }
+            if self.continuous {
+                self.show_all_files(pane, Some(&note.path), false);
+            } else {
+                let (raw, display) = self.fetch_diff(&note.path, pane);
+                self.set_diff_content(&note.path, pane, raw, display, false);
+            }
+        }
+
+        let Some(&line) = self.note_lines.get(&idx) else {
+            self.error_message = Some(format!(
+                "{}:{} is not in the diff any more",
+                note.path, note.line
+            ));
             return;
+        };
+        if self.focus != Focus::InlineSelect {
+            self.focus = Focus::DiffView;
         }
-        if self.hunk_cursor > 0 {
-            self.hunk_cursor -= 1;
+        if let Some(hunk_idx) = self.line_infos[line].hunk_idx {
+            self.hunk_cursor = hunk_idx;
         }
-        self.scroll_to_hunk(self.hunk_cursor);
+        if self.note_on(line).is_some() {
+            self.diff_cursor = line;
+            self.diff_scroll = line.saturating_sub(2);
+        } else {
+            self.scroll_to_hunk(self.hunk_cursor);
+        }
+        self.status_message = Some(format!("{}:{} {}", note.path, note.line, note.text));
     }
 
-    fn scroll_to_hunk(&mut self, hunk_idx: usize) {
-        let mut hunk_count = 0usize;
-        let content = if self.focus == Focus::InlineSelect {
-            &self.raw_diff
+    // ─── Fixup commits ──────────────────────────────────────────────────
+
+    /// F key: preview `fixup!` commits for the staged hunks of the selected
+    /// file or directory, or for the current hunk of a staged diff
+    fn plan_fixups(&mut self) {
+        if self.patch.is_some() || self.is_commit_mode() {
+            self.error_message = Some("Fixups are made from staged changes".to_string());
+            return;
+        }
+        let plan = self
+            .hunks_for_fixup()
+            .and_then(|hunks| fixup::plan(hunks, &self.repo_root));
+        match plan {
+            Ok(plan) if plan.fixups.is_empty() => {
+                self.error_message =
+                    Some("No recent commit of the branch touched these lines".to_string())
+            }
+            Ok(plan) => self.fixup_plan = Some(plan),
+            Err(e) => self.error_message = Some(format!("Fixup: {:#}", e)),
+        }
+    }
+
+    /// The staged hunks the fixup key is on
+    fn hunks_for_fixup(&self) -> Result<Vec<StagedHunk>> {
+        let mut hunks = Vec::new();
+        if self.key_context() == KeyContext::Tree {
+            if self.focused_pane() != Some(TreePane::Staged) {
+                anyhow::bail!("select staged files");
+            }
+            let section = self.tree(TreePane::Staged);
+            let node = section.current_node().context("No file selected")?;
+            let paths = if node.is_dir {
+                section.files_under_current_dir()
+            } else {
+                vec![node.path.to_string_lossy().to_string()]
+            };
+            // A renamed file's lines are blamed where they were before
+            let sources: HashMap<String, String> = self
+                .git
+                .status()?
+                .into_iter()
+                .filter_map(|f| Some((f.path, f.orig_path?)))
+                .collect();
+            for path in paths {
+                let raw = match sources.get(&path) {
+                    Some(orig_path) => self.git.staged_rename_diff(orig_path, &path)?,
+                    None => self.git.diff(&path, true)?,
+                };
+                for (idx, hunk) in parse_diff(&raw).hunks.into_iter().enumerate() {
+                    hunks.push(StagedHunk {
+                        path: path.clone(),
+                        raw: raw.clone(),
+                        hunk,
+                        number: idx + 1,
+                    });
+                }
+            }
         } else {
-            &self.display_diff
-        };
-        for (line_no, line) in content.lines().enumerate() {
-            if line.starts_with("@@") {
-                if hunk_count == hunk_idx {
-                    if self.focus == Focus::InlineSelect {
-                        self.diff_cursor = line_no;
-                        self.diff_scroll = line_no;
-                    } else {
-                        self.diff_scroll = line_no;
+            if self.diff_origin != Some(TreePane::Staged) {
+                anyhow::bail!("open a staged diff");
+            }
+            if let (Some(hunk), Some(file)) = (
+                self.file_diff.hunks.get(self.hunk_cursor),
+                self.file_of_hunk(self.hunk_cursor),
+            ) {
+                hunks.push(StagedHunk {
+                    path: file.path.clone(),
+                    raw: file.raw.clone(),
+                    hunk: hunk.clone(),
+                    number: self.hunk_cursor - file.first_hunk + 1,
+                });
+            }
+        }
+        if hunks.is_empty() {
+            anyhow::bail!("no staged hunks here");
+        }
+        Ok(hunks)
+    }
+
+    /// Keys of the fixup preview: Enter commits, Esc cancels
+    fn handle_fixup_key(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Enter => {
+                let Some(plan) = self.fixup_plan.take() else {
+                    return Ok(());
+                };
+                match fixup::create_fixups(&plan, self.git.as_ref()) {
+                    Ok(count) => {
+                        let hunks = plan.hunk_count();
+                        self.status_message = Some(format!(
+                            "Created {} fixup commit{} for {} hunk{}",
+                            count,
+                            if count == 1 { "" } else { "s" },
+                            hunks,
+                            if hunks == 1 { "" } else { "s" },
+                        ));
                     }
-                    return;
+                    Err(e) => self.error_message = Some(format!("Fixup failed: {:#}", e)),
                 }
-                hunk_count += 1;
+                self.refresh_after_tree_op()?;
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.fixup_plan = None;
+                self.status_message = Some("No fixups made".to_string());
             }
+            _ => {}
         }
+        Ok(())
     }
 
     // ─── Inline select key handling ─────────────────────────────────────
 
-    fn handle_inline_select_key(&mut self, key: KeyEvent) -> Result<()> {
-        let line_count = self.raw_diff.lines().count();
+    fn handle_inline_select_action(&mut self, action: Action) -> Result<()> {
+        let line_count = self.raw_text.len();
         let half_page = (self.diff_pane_height / 2).max(1);
 
-        match key.code {
-            KeyCode::Char('q') => {
-                self.should_quit = true;
-            }
-            KeyCode::Char('j') | KeyCode::Down => {
-                if self.diff_cursor + 1 < line_count {
-                    self.diff_cursor += 1;
-                    self.sync_hunk_cursor();
-                    if self.diff_cursor >= self.diff_scroll + self.diff_pane_height {
-                        self.diff_scroll = self.diff_cursor + 1 - self.diff_pane_height;
-                    }
+        match action {
+            Action::Down if self.diff_cursor + 1 < line_count => {
+                self.diff_cursor += 1;
+                self.sync_hunk_cursor();
+                if self.diff_cursor >= self.diff_scroll + self.diff_pane_height {
+                    self.diff_scroll = self.diff_cursor + 1 - self.diff_pane_height;
                 }
             }
-            KeyCode::Char('k') | KeyCode::Up => {
-                if self.diff_cursor > 0 {
-                    self.diff_cursor -= 1;
-                    self.sync_hunk_cursor();
-                    if self.diff_cursor < self.diff_scroll {
-                        self.diff_scroll = self.diff_cursor;
-                    }
+            Action::Up if self.diff_cursor > 0 => {
+                self.diff_cursor -= 1;
+                self.sync_hunk_cursor();
+                if self.diff_cursor < self.diff_scroll {
+                    self.diff_scroll = self.diff_cursor;
                 }
             }
-            KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Action::HalfPageDown => {
                 self.diff_cursor = (self.diff_cursor + half_page).min(line_count.saturating_sub(1));
                 self.sync_hunk_cursor();
                 if self.diff_cursor >= self.diff_scroll + self.diff_pane_height {
                     self.diff_scroll = self.diff_cursor + 1 - self.diff_pane_height;
                 }
             }
-            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Action::HalfPageUp => {
                 self.diff_cursor = self.diff_cursor.saturating_sub(half_page);
                 self.sync_hunk_cursor();
                 if self.diff_cursor < self.diff_scroll {
                     self.diff_scroll = self.diff_cursor;
                 }
             }
-            KeyCode::Char('n') => self.jump_next_hunk(),
-            KeyCode::Char('p') => self.jump_prev_hunk(),
-            KeyCode::Enter => {
-                self.apply_current_line()?;
+            Action::NextHunk => self.jump_next_hunk(),
+            Action::PrevHunk => self.jump_prev_hunk(),
+            Action::NextFile => self.jump_file(true),
+            Action::PrevFile => self.jump_file(false),
+            Action::Annotate => self.start_note(),
+            Action::Apply => {
+                self.apply_selected_lines()?;
+            }
+            Action::RangeSelect => {
+                self.selection_anchor = match self.selection_anchor {
+                    Some(_) => None,
+                    None => Some(self.diff_cursor),
+                };
+            }
+            Action::Cancel => {
+                self.selection_anchor = None;
             }
-            KeyCode::Char('v') => {
+            Action::LineSelect => {
+                self.selection_anchor = None;
                 self.focus = Focus::DiffView;
             }
-            KeyCode::Char('h') | KeyCode::Left => {
+            Action::Back => {
+                self.selection_anchor = None;
                 self.focus = self
                     .diff_origin
                     .map(|p| p.to_focus())
@@ -1135,54 +3718,84 @@ impl App {
         }
     }
 
-    fn apply_current_line(&mut self) -> Result<()> {
+    /// Display lines covered by the selection: the anchored range, or just
+    /// the cursor line
+    pub fn selected_range(&self) -> (usize, usize) {
+        match self.selection_anchor {
+            Some(anchor) => (anchor.min(self.diff_cursor), anchor.max(self.diff_cursor)),
+            None => (self.diff_cursor, self.diff_cursor),
+        }
+    }
+
+    /// The selected `+`/`-` lines, as line indices per hunk index per
+    /// index into `diff_files`
+    fn selected_lines_by_file(&self) -> BTreeMap<usize, BTreeMap<usize, HashSet<usize>>> {
+        let (from, to) = self.selected_range();
+        let mut by_file: BTreeMap<usize, BTreeMap<usize, HashSet<usize>>> = BTreeMap::new();
+        for info in self.line_infos.iter().take(to + 1).skip(from) {
+            if let (true, Some(h), Some(l)) = (info.is_selectable, info.hunk_idx, info.line_in_hunk)
+            {
+                by_file
+                    .entry(info.file_idx)
+                    .or_default()
+                    .entry(h)
+                    .or_default()
+                    .insert(l);
+            }
+        }
+        by_file
+    }
+
+    fn apply_selected_lines(&mut self) -> Result<()> {
         if self.is_commit_mode() {
-            self.error_message = Some("Commit diff is read-only".to_string());
+            self.commit_read_only();
             return Ok(());
         }
+        if self.patch.is_some() {
+            return self.apply_from_patch(false);
+        }
 
-        let info = match self.line_infos.get(self.diff_cursor) {
-            Some(i) => i.clone(),
-            None => return Ok(()),
-        };
+        let (from, _) = self.selected_range();
+        let by_file = self.selected_lines_by_file();
 
-        if !info.is_selectable {
+        if by_file.is_empty() {
             self.error_message = Some("Only +/- lines can be applied".to_string());
             return Ok(());
         }
 
-        let hunk_idx = match info.hunk_idx {
-            Some(h) => h,
-            None => return Ok(()),
-        };
-        let line_in_hunk = match info.line_in_hunk {
-            Some(l) => l,
-            None => return Ok(()),
-        };
-
-        let file = match &self.current_file {
-            Some(f) => f.clone(),
-            None => return Ok(()),
-        };
-        let hunk = match self.file_diff.hunks.get(hunk_idx).cloned() {
-            Some(h) => h,
-            None => return Ok(()),
-        };
         let pane = match self.diff_origin {
             Some(p) => p,
             None => return Ok(()),
         };
+        let file_idxs: Vec<usize> = by_file.keys().copied().collect();
+        if !self.shown_hunks_current(&file_idxs)? {
+            return Ok(());
+        }
 
-        let selected: HashSet<usize> = [line_in_hunk].into_iter().collect();
-
-        let result = match pane {
-            TreePane::Unstaged => {
-                crate::git::apply::stage_lines(&file, &hunk, &selected, &self.repo_root)
-            }
-            TreePane::Staged => {
-                crate::git::apply::unstage_lines(&file, &hunk, &selected, &self.repo_root)
+        // Apply bottom-up so earlier hunks keep their line numbers
+        let mut applied = 0usize;
+        let mut result = Ok(());
+        'files: for (file_idx, by_hunk) in &by_file {
+            let file = &self.diff_files[*file_idx].path;
+            for (hunk_idx, selected) in by_hunk.iter().rev() {
+                let hunk = match self.file_diff.hunks.get(*hunk_idx) {
+                    Some(h) => h,
+                    None => continue,
+                };
+                result = match pane {
+                    TreePane::Unstaged => {
+                        crate::git::apply::stage_lines(file, hunk, selected, self.git.as_ref())
+                    }
+                    TreePane::Staged => {
+                        crate::git::apply::unstage_lines(file, hunk, selected, self.git.as_ref())
+                    }
+                };
+                if result.is_err() {
+                    break 'files;
+                }
+                applied += selected.len();
             }
-        };
+        }
 
         match result {
             Ok(_) => {
@@ -1191,17 +3804,19 @@ impl App {
                 } else {
                     "Staged"
                 };
-                self.status_message = Some(format!("{} 1 line", action));
+                let noun = if applied == 1 { "line" } else { "lines" };
+                self.status_message = Some(format!("{} {} {}", action, applied, noun));
+                self.selection_anchor = None;
                 self.refresh_trees()?;
 
-                let prev_cursor = self.diff_cursor;
+                self.diff_cursor = from;
                 self.reload_current_diff()?;
 
                 if self.file_diff.hunks.is_empty() && self.raw_diff.trim().is_empty() {
                     self.clear_diff();
                     self.focus = pane.to_focus();
                 } else {
-                    self.move_to_next_selectable(prev_cursor);
+                    self.move_to_next_selectable(from);
                 }
             }
             Err(e) => self.error_message = Some(format!("Error: {}", e)),
@@ -1247,15 +3862,40 @@ fn rebuild_section_visible(section: &mut TreeSection) {
     section.clamp_cursor();
 }
 
-/// Build tree nodes from a list of (path, staged, unstaged) tuples.
-/// Preserves existing expansion states from `target_nodes`.
-fn build_section(target_nodes: &mut Vec<TreeNode>, files: &[(String, char, char)]) {
-    let prev_expanded: std::collections::HashMap<PathBuf, bool> = target_nodes
+/// Build tree nodes from a list of (path, staged, unstaged) tuples using
+/// the given layout. Preserves existing expansion states from `target_nodes`.
+fn build_section(
+    target_nodes: &mut Vec<TreeNode>,
+    files: &[SectionFile],
+    layout: TreeLayout,
+    pane: TreePane,
+) {
+    // Keyed by kind too, so a group never takes a same-named directory's state
+    let prev_expanded: HashMap<(bool, PathBuf), bool> = target_nodes
         .iter()
         .filter(|n| n.is_dir)
-        .map(|n| (n.path.clone(), n.expanded))
+        .map(|n| ((n.is_group, n.path.clone()), n.expanded))
         .collect();
 
+    let mut nodes = match layout {
+        TreeLayout::Nested => build_dir_nodes(files, false),
+        TreeLayout::Compact => build_dir_nodes(files, true),
+        TreeLayout::Flat => build_flat_nodes(files),
+        TreeLayout::Grouped => build_grouped_nodes(files, pane),
+    };
+
+    for node in nodes.iter_mut().filter(|n| n.is_dir) {
+        node.expanded = *prev_expanded
+            .get(&(node.is_group, node.path.clone()))
+            .unwrap_or(&true);
+    }
+
+    *target_nodes = nodes;
+}
+
+/// Nested directory tree. With `compact`, a directory whose only child is
+/// another directory is folded into that child's row.
+fn build_dir_nodes(files: &[SectionFile], compact: bool) -> Vec<TreeNode> {
     let mut map: BTreeMap<String, (bool, char, char)> = BTreeMap::new();
 
     for (path, staged, unstaged) in files {
@@ -1275,36 +3915,314 @@ fn build_section(target_nodes: &mut Vec<TreeNode>, files: &[(String, char, char)
         map.insert(path.clone(), (false, *staged, *unstaged));
     }
 
+    // Direct children per directory: (total, directories)
+    let mut children: HashMap<PathBuf, (usize, usize)> = HashMap::new();
+    for (key, (is_dir, _, _)) in &map {
+        let path = PathBuf::from(key.trim_end_matches('/'));
+        if let Some(parent) = path.parent().filter(|p| *p != Path::new("")) {
+            let entry = children.entry(parent.to_path_buf()).or_default();
+            entry.0 += 1;
+            if *is_dir {
+                entry.1 += 1;
+            }
+        }
+    }
+    let absorbed = |dir: &Path| compact && children.get(dir) == Some(&(1, 1));
+
     let mut nodes: Vec<TreeNode> = Vec::new();
+    let mut dir_index: HashMap<PathBuf, usize> = HashMap::new();
     for (key, (is_dir, staged, unstaged)) in &map {
-        let path = if *is_dir {
-            PathBuf::from(key.trim_end_matches('/'))
-        } else {
-            PathBuf::from(key)
-        };
-
-        let name = path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| key.clone());
+        let path = PathBuf::from(key.trim_end_matches('/'));
+        if *is_dir && absorbed(&path) {
+            continue;
+        }
 
-        let depth = path.components().count().saturating_sub(1);
-        let expanded = if *is_dir {
-            *prev_expanded.get(&path).unwrap_or(&true)
-        } else {
-            false
+        let parent = path
+            .ancestors()
+            .skip(1)
+            .take_while(|p| *p != Path::new(""))
+            .find(|p| !absorbed(p))
+            .and_then(|p| dir_index.get(p).copied());
+
+        let name = match parent {
+            Some(p) => path
+                .strip_prefix(&nodes[p].path)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string(),
+            None => path.to_string_lossy().to_string(),
         };
+        let depth = parent.map(|p| nodes[p].depth + 1).unwrap_or(0);
 
+        if *is_dir {
+            dir_index.insert(path.clone(), nodes.len());
+        }
         nodes.push(TreeNode {
             path,
             name,
             depth,
             is_dir: *is_dir,
-            expanded,
+            is_group: false,
+            expanded: *is_dir,
             staged: *staged,
             unstaged: *unstaged,
+            parent,
+            stat: DiffStat::default(),
         });
     }
 
-    *target_nodes = nodes;
+    nodes
+}
+
+fn build_flat_nodes(files: &[SectionFile]) -> Vec<TreeNode> {
+    let mut sorted: Vec<&SectionFile> = files.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    sorted
+        .into_iter()
+        .map(|(path, staged, unstaged)| file_row(path, *staged, *unstaged, 0, None))
+        .collect()
+}
+
+fn build_grouped_nodes(files: &[SectionFile], pane: TreePane) -> Vec<TreeNode> {
+    let mut groups: BTreeMap<(usize, &'static str), Vec<&SectionFile>> = BTreeMap::new();
+    for file in files {
+        groups
+            .entry(status_group(file.1, file.2, pane))
+            .or_default()
+            .push(file);
+    }
+
+    let mut nodes: Vec<TreeNode> = Vec::new();
+    for ((_, label), mut members) in groups {
+        members.sort_by(|a, b| a.0.cmp(&b.0));
+        let group_idx = nodes.len();
+        nodes.push(TreeNode {
+            path: PathBuf::from(label),
+            name: label.to_string(),
+            depth: 0,
+            is_dir: true,
+            is_group: true,
+            expanded: true,
+            staged: ' ',
+            unstaged: ' ',
+            parent: None,
+            stat: DiffStat::default(),
+        });
+        for (path, staged, unstaged) in members {
+            nodes.push(file_row(path, *staged, *unstaged, 1, Some(group_idx)));
+        }
+    }
+    nodes
+}
+
+fn file_row(
+    path: &str,
+    staged: char,
+    unstaged: char,
+    depth: usize,
+    parent: Option<usize>,
+) -> TreeNode {
+    TreeNode {
+        path: PathBuf::from(path),
+        name: path.to_string(),
+        depth,
+        is_dir: false,
+        is_group: false,
+        expanded: false,
+        staged,
+        unstaged,
+        parent,
+        stat: DiffStat::default(),
+    }
+}
+
+/// Sort key and label of the status group a file belongs to
+fn status_group(staged: char, unstaged: char, pane: TreePane) -> (usize, &'static str) {
+    if staged == 'U' || unstaged == 'U' {
+        return (0, "Unmerged");
+    }
+    let status = match pane {
+        TreePane::Unstaged => unstaged,
+        TreePane::Staged => staged,
+    };
+    match status {
+        'M' => (1, "Modified"),
+        'A' => (2, "Added"),
+        'D' => (3, "Deleted"),
+        'R' => (4, "Renamed"),
+        'C' => (5, "Copied"),
+        'T' => (6, "Type changed"),
+        '?' => (7, "Untracked"),
+        _ => (8, "Other"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(paths: &[&str]) -> Vec<SectionFile> {
+        paths.iter().map(|p| (p.to_string(), ' ', 'M')).collect()
+    }
+
+    fn rows(nodes: &[TreeNode]) -> Vec<(usize, String)> {
+        nodes.iter().map(|n| (n.depth, n.name.clone())).collect()
+    }
+
+    #[test]
+    fn test_double_click_needs_same_cell() {
+        let mut mouse = MouseState::default();
+        assert!(!mouse.register_click(3, 4));
+        assert!(mouse.register_click(3, 4));
+        // A third click starts a new sequence
+        assert!(!mouse.register_click(3, 4));
+        assert!(!mouse.register_click(5, 4));
+    }
+
+    #[test]
+    fn test_compact_layout_collapses_single_child_dirs() {
+        let nodes = build_dir_nodes(
+            &files(&[
+                "src/main/java/com/App.java",
+                "src/main/java/com/Util.java",
+                "README.md",
+            ]),
+            true,
+        );
+        assert_eq!(
+            rows(&nodes),
+            vec![
+                (0, "README.md".to_string()),
+                (0, "src/main/java/com".to_string()),
+                (1, "App.java".to_string()),
+                (1, "Util.java".to_string()),
+            ]
+        );
+        assert_eq!(nodes[2].parent, Some(1));
+    }
+
+    #[test]
+    fn test_nested_layout_keeps_every_level() {
+        let nodes = build_dir_nodes(&files(&["a/b/c.rs"]), false);
+        assert_eq!(
+            rows(&nodes),
+            vec![
+                (0, "a".to_string()),
+                (1, "b".to_string()),
+                (2, "c.rs".to_string())
+            ]
+        );
+        assert_eq!(nodes[2].parent, Some(1));
+        assert_eq!(nodes[1].parent, Some(0));
+    }
+
+    #[test]
+    fn test_grouped_layout_groups_by_pane_status() {
+        let input = vec![
+            ("b.rs".to_string(), 'A', 'M'),
+            ("a.rs".to_string(), ' ', 'M'),
+            ("new.txt".to_string(), '?', '?'),
+        ];
+        let unstaged = build_grouped_nodes(&input, TreePane::Unstaged);
+        assert_eq!(
+            rows(&unstaged),
+            vec![
+                (0, "Modified".to_string()),
+                (1, "a.rs".to_string()),
+                (1, "b.rs".to_string()),
+                (0, "Untracked".to_string()),
+                (1, "new.txt".to_string()),
+            ]
+        );
+        let staged = build_grouped_nodes(&input[..1], TreePane::Staged);
+        assert_eq!(staged[0].name, "Added");
+    }
+
+    #[test]
+    fn test_group_does_not_share_state_with_a_same_named_dir() {
+        let mut section = TreeSection::new(TreePane::Unstaged, TreeLayout::Nested);
+        section.set_files(files(&["Modified/x.rs"]), HashMap::new());
+        section.all_nodes[0].expanded = false;
+        section.set_layout(TreeLayout::Grouped);
+        let group = &section.all_nodes[0];
+        assert!(group.is_group && group.expanded);
+        assert_eq!(section.visible.len(), 2);
+    }
+
+    #[test]
+    fn test_dir_stats_are_aggregated() {
+        let mut section = TreeSection::new(TreePane::Unstaged, TreeLayout::Nested);
+        let stat = |added, removed| DiffStat {
+            added,
+            removed,
+            binary: false,
+        };
+        let stats: HashMap<String, DiffStat> = [
+            ("src/a.rs".to_string(), stat(3, 1)),
+            ("src/git/c.rs".to_string(), stat(10, 0)),
+        ]
+        .into_iter()
+        .collect();
+        section.set_files(files(&["src/a.rs", "src/git/c.rs", "x.md"]), stats);
+
+        let src = section.all_nodes.iter().find(|n| n.name == "src").unwrap();
+        assert_eq!(src.stat, stat(13, 1));
+        assert_eq!(section.total_stat(), stat(13, 1));
+
+        // Files hidden by the filter count neither in the title nor the dirs
+        section.set_filter(TreeFilter::parse("a.rs"));
+        let src = section.all_nodes.iter().find(|n| n.name == "src").unwrap();
+        assert_eq!(src.stat, stat(3, 1));
+        assert_eq!(section.total_stat(), stat(3, 1));
+    }
+
+    #[test]
+    fn test_dir_staging_respects_filter_and_groups() {
+        let mut section = TreeSection::new(TreePane::Unstaged, TreeLayout::Nested);
+        section.set_files(
+            files(&["src/a.rs", "src/b.md", "src/git/c.rs"]),
+            HashMap::new(),
+        );
+        section.set_filter(TreeFilter::parse("*.rs"));
+        section.cursor = 0;
+        assert_eq!(section.current_node().unwrap().name, "src");
+        assert_eq!(
+            section.files_under_current_dir(),
+            vec!["src/a.rs".to_string(), "src/git/c.rs".to_string()]
+        );
+
+        section.set_layout(TreeLayout::Grouped);
+        section.cursor = 0;
+        assert_eq!(section.files_under_current_dir().len(), 2);
+    }
+
+    #[test]
+    fn test_load_status_from_the_backend() {
+        use crate::git::backend::memory::MemoryBackend;
+
+        let git = MemoryBackend::with_files(&[("a.rs", "1\n2\n"), ("b.rs", "x\n")]);
+        git.write("a.rs", "1\ntwo\n3\n");
+        git.stage_all();
+        git.write("b.rs", "y\n");
+        let root = Path::new("/nonexistent");
+        let tree = TreeConfig::default();
+        let stat = |added, removed| DiffStat {
+            added,
+            removed,
+            binary: false,
+        };
+        let snapshot = load_status(&git, root, None, &tree, &UntrackedStats::default()).unwrap();
+
+        assert_eq!(snapshot.staged, vec![("a.rs".to_string(), 'M', ' ')]);
+        assert_eq!(snapshot.unstaged, vec![("b.rs".to_string(), ' ', 'M')]);
+        assert_eq!(snapshot.staged_stats["a.rs"], stat(2, 1));
+        assert_eq!(snapshot.unstaged_stats["b.rs"], stat(1, 1));
+        assert_eq!(snapshot.unstaged_blobs["b.rs"].1, "0".repeat(40));
+
+        let commit = git.commit();
+        let snapshot =
+            load_status(&git, root, Some(&commit), &tree, &UntrackedStats::default()).unwrap();
+        assert_eq!(snapshot.unstaged, vec![("a.rs".to_string(), 'M', 'M')]);
+        assert_eq!(snapshot.unstaged_stats["a.rs"], stat(2, 1));
+    }
 }
@@ -0,0 +1,226 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::git::diff::{untracked_stat, DiffStat, DiffTarget};
+
+/// Upper bound for the text kept in the cache
+const MAX_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Identifies one loaded diff.
+///
+/// The sides are blob IDs (or the commit ID in commit mode), so any change
+/// to the index or HEAD produces a new key. The worktree side is not hashed
+/// by git; it is identified by [`worktree_fingerprint`], so staging must not
+/// trust a cached worktree diff without checking it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DiffKey {
+    pub path: String,
+    pub target: DiffTarget,
+    pub base: String,
+    pub head: String,
+    pub tool: String,
+    /// Only delta's output depends on the width; 0 for the other tools
+    pub width: u16,
+}
+
+/// Raw and display diffs by [`DiffKey`], least recently used evicted first.
+#[derive(Debug, Default)]
+pub struct DiffCache {
+    entries: HashMap<DiffKey, (String, String)>,
+    order: VecDeque<DiffKey>,
+    bytes: usize,
+}
+
+impl DiffCache {
+    pub fn get(&mut self, key: &DiffKey) -> Option<(String, String)> {
+        let entry = self.entries.get(key)?.clone();
+        self.touch(key);
+        Some(entry)
+    }
+
+    pub fn contains(&self, key: &DiffKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: DiffKey, raw: String, display: String) {
+        let size = raw.len() + display.len();
+        if size > MAX_CACHE_BYTES / 4 {
+            return;
+        }
+
+        if let Some((old_raw, old_display)) = self.entries.insert(key.clone(), (raw, display)) {
+            self.bytes -= old_raw.len() + old_display.len();
+        }
+        self.bytes += size;
+        self.touch(&key);
+
+        while self.bytes > MAX_CACHE_BYTES {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some((raw, display)) = self.entries.remove(&oldest) {
+                self.bytes -= raw.len() + display.len();
+            }
+        }
+    }
+
+    /// Drop entries that can no longer be hit, e.g. after a refresh
+    pub fn retain(&mut self, mut keep: impl FnMut(&DiffKey) -> bool) {
+        let bytes = &mut self.bytes;
+        self.entries.retain(|key, (raw, display)| {
+            let keep = keep(key);
+            if !keep {
+                *bytes -= raw.len() + display.len();
+            }
+            keep
+        });
+        let entries = &self.entries;
+        self.order.retain(|key| entries.contains_key(key));
+    }
+
+    pub fn remove(&mut self, key: &DiffKey) {
+        if let Some((raw, display)) = self.entries.remove(key) {
+            self.bytes -= raw.len() + display.len();
+            self.order.retain(|k| k != key);
+        }
+    }
+
+    fn touch(&mut self, key: &DiffKey) {
+        if let Some(pos) = self.order.iter().position(|k| k == key) {
+            self.order.remove(pos);
+        }
+        self.order.push_back(key.clone());
+    }
+}
+
+/// Size, modification time and (on Unix) inode and change time of a
+/// worktree file, as a stand-in for its blob ID. Like git's stat cache it
+/// can miss a same-size rewrite within the filesystem's time granularity.
+pub fn worktree_fingerprint(path: &Path) -> Option<String> {
+    let meta = std::fs::symlink_metadata(path).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    #[cfg(unix)]
+    let inode = {
+        use std::os::unix::fs::MetadataExt;
+        format!(
+            ":{}:{}:{}.{}",
+            meta.dev(),
+            meta.ino(),
+            meta.ctime(),
+            meta.ctime_nsec()
+        )
+    };
+    #[cfg(not(unix))]
+    let inode = String::new();
+    Some(format!("wt:{}:{}{}", meta.len(), mtime.as_nanos(), inode))
+}
+
+/// Line counts of untracked files, reused while a file's fingerprint stays
+/// the same, so a refresh only reads new or changed files. Shared with the
+/// background status load.
+#[derive(Debug, Default)]
+pub struct UntrackedStats {
+    entries: Mutex<HashMap<String, (String, Option<DiffStat>)>>,
+}
+
+impl UntrackedStats {
+    /// Line counts of `paths`; entries of other paths are dropped
+    pub fn stats(&self, paths: &[&str], repo_root: &Path) -> HashMap<String, DiffStat> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stats = HashMap::new();
+        let mut kept = HashMap::new();
+        for &path in paths {
+            let Some(fingerprint) = worktree_fingerprint(&repo_root.join(path)) else {
+                continue;
+            };
+            let stat = match entries.remove(path) {
+                Some((known, stat)) if known == fingerprint => stat,
+                _ => untracked_stat(path, repo_root),
+            };
+            if let Some(stat) = stat {
+                stats.insert(path.to_string(), stat);
+            }
+            kept.insert(path.to_string(), (fingerprint, stat));
+        }
+        *entries = kept;
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(path: &str, head: &str) -> DiffKey {
+        DiffKey {
+            path: path.to_string(),
+            target: DiffTarget::Worktree { staged: true },
+            base: "base".to_string(),
+            head: head.to_string(),
+            tool: "raw".to_string(),
+            width: 0,
+        }
+    }
+
+    #[test]
+    fn test_hits_only_matching_blobs() {
+        let mut cache = DiffCache::default();
+        cache.insert(key("a.rs", "1"), "raw".into(), "display".into());
+        assert_eq!(
+            cache.get(&key("a.rs", "1")),
+            Some(("raw".to_string(), "display".to_string()))
+        );
+        assert_eq!(cache.get(&key("a.rs", "2")), None);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = DiffCache::default();
+        let big = "x".repeat(MAX_CACHE_BYTES / 8);
+        for i in 0..8 {
+            cache.insert(key("f", &i.to_string()), big.clone(), String::new());
+            // Keep the first entry hot
+            cache.get(&key("f", "0"));
+        }
+        cache.insert(key("f", "8"), big.clone(), String::new());
+        assert!(cache.get(&key("f", "0")).is_some());
+        assert!(cache.get(&key("f", "1")).is_none());
+        assert!(cache.bytes <= MAX_CACHE_BYTES);
+    }
+
+    #[test]
+    fn test_retain_updates_size() {
+        let mut cache = DiffCache::default();
+        cache.insert(key("a", "1"), "12345".into(), String::new());
+        cache.insert(key("b", "1"), "123".into(), String::new());
+        cache.retain(|k| k.path == "b");
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.bytes, 3);
+    }
+
+    #[test]
+    fn test_untracked_stats_reread_changed_files_only() {
+        let dir = std::env::temp_dir().join(format!("diffview-untracked-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a"), "1\n2\n").unwrap();
+        let cache = UntrackedStats::default();
+        assert_eq!(cache.stats(&["a"], &dir)["a"].added, 2);
+
+        // A known fingerprint is not read again
+        let mut entries = cache.entries.lock().unwrap();
+        entries.get_mut("a").unwrap().1 = Some(DiffStat {
+            added: 7,
+            ..DiffStat::default()
+        });
+        drop(entries);
+        assert_eq!(cache.stats(&["a"], &dir)["a"].added, 7);
+
+        std::fs::write(dir.join("a"), "1\n2\n3\n").unwrap();
+        assert_eq!(cache.stats(&["a"], &dir)["a"].added, 3);
+        assert!(cache.stats(&[], &dir).is_empty());
+        assert!(cache.entries.lock().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,347 @@
+//! Subcommands that print results instead of starting the TUI.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::git::backend::{self, GitBackend};
+use crate::git::diff::{hunk_ids, parse_diff, DiffLine, DiffTarget, FileChange, Hunk};
+use crate::notes::{to_json_lines, to_markdown, Notes};
+
+/// Repository access for a subcommand, using the configured backend
+fn open_backend() -> Result<(PathBuf, Arc<dyn GitBackend>)> {
+    let repo_root = crate::git::get_repo_root()?;
+    let config = Config::load(Some(&repo_root))?.config;
+    let git = backend::open(&config.git.backend, &repo_root)?;
+    Ok((repo_root, git))
+}
+
+/// A path argument relative to the repository root, so subcommands accept
+/// paths relative to the current directory like git does
+fn repo_path(repo_root: &Path, path: &str) -> String {
+    let prefix = std::env::current_dir()
+        .and_then(|cwd| cwd.canonicalize())
+        .ok()
+        .zip(repo_root.canonicalize().ok())
+        .and_then(|(cwd, root)| cwd.strip_prefix(root).ok().map(Path::to_path_buf));
+    let Some(prefix) = prefix.filter(|p| !p.as_os_str().is_empty()) else {
+        return path.to_string();
+    };
+    // Resolve `..` lexically; git wants plain repository paths
+    let mut parts: Vec<String> = Vec::new();
+    for component in prefix.join(path).components() {
+        match component {
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            _ => {}
+        }
+    }
+    parts.join("/")
+}
+
+/// Write to stdout; a closed pipe (`| head`) is not an error
+fn output(text: &str) -> Result<()> {
+    use std::io::Write;
+    match std::io::stdout().lock().write_all(text.as_bytes()) {
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    output(&format!("{}\n", serde_json::to_string_pretty(value)?))
+}
+
+/// `diffview status`: changed files, as `XY path` lines or a JSON array
+pub fn status(json: bool) -> Result<()> {
+    let (_, git) = open_backend()?;
+    let files = git.status()?;
+    if json {
+        return print_json(&files);
+    }
+    let lines: String = files
+        .iter()
+        .map(|file| format!("{}{} {}\n", file.staged, file.unstaged, file.path))
+        .collect();
+    output(&lines)
+}
+
+#[derive(Serialize)]
+struct DiffOutput<'a> {
+    path: &'a str,
+    /// `worktree`, `staged` or the commit ID
+    target: String,
+    old_path: &'a str,
+    change: FileChange,
+    old_mode: Option<&'a str>,
+    new_mode: Option<&'a str>,
+    is_binary: bool,
+    hunks: Vec<HunkOutput<'a>>,
+}
+
+#[derive(Serialize)]
+struct HunkOutput<'a> {
+    id: String,
+    #[serde(flatten)]
+    hunk: &'a Hunk,
+}
+
+/// `diffview diff <path>`: one file's diff, as text or parsed into hunks
+pub fn diff(path: &str, staged: bool, revision: Option<&str>, json: bool) -> Result<()> {
+    let (repo_root, git) = open_backend()?;
+    let path = &repo_path(&repo_root, path);
+    let target = match revision {
+        Some(rev) => DiffTarget::Commit(
+            git.rev_parse(&format!("{}^{{commit}}", rev))
+                .with_context(|| format!("Unknown revision {}", rev))?,
+        ),
+        None => DiffTarget::Worktree { staged },
+    };
+    let raw = git.diff_target(&target, path)?;
+    if !json {
+        return output(&raw);
+    }
+
+    let file = parse_diff(&raw);
+    let ids = hunk_ids(path, &file.hunks);
+    print_json(&DiffOutput {
+        path,
+        target: match &target {
+            DiffTarget::Worktree { staged: false } => "worktree".to_string(),
+            DiffTarget::Worktree { staged: true } => "staged".to_string(),
+            DiffTarget::Commit(id) => id.clone(),
+        },
+        old_path: if file.old_path.is_empty() {
+            path
+        } else {
+            &file.old_path
+        },
+        change: file.change,
+        old_mode: file.old_mode.as_deref(),
+        new_mode: file.new_mode.as_deref(),
+        is_binary: file.is_binary,
+        hunks: ids
+            .into_iter()
+            .zip(&file.hunks)
+            .map(|(id, hunk)| HunkOutput { id, hunk })
+            .collect(),
+    })
+}
+
+/// `diffview notes`: the review notes with `path:line` references, as
+/// markdown or one JSON object per line
+pub fn notes(json: bool) -> Result<()> {
+    let repo_root = crate::git::get_repo_root()?;
+    let git_dir = crate::git::get_git_dir(&repo_root)?;
+    let notes = Notes::load(&git_dir)?.resolved(&repo_root);
+    if json {
+        output(&to_json_lines(&notes)?)
+    } else {
+        output(&to_markdown(&notes))
+    }
+}
+
+/// A `stage`/`unstage` argument: `path`, `path:LINE` or `path:FROM-TO`
+#[derive(Debug, PartialEq)]
+struct Target {
+    path: String,
+    lines: Option<(u32, u32)>,
+}
+
+impl Target {
+    fn parse(spec: &str) -> Result<Self> {
+        let whole = || Target {
+            path: spec.to_string(),
+            lines: None,
+        };
+        // Paths may contain ':', so only a numeric suffix is a range
+        let Some((path, range)) = spec.rsplit_once(':') else {
+            return Ok(whole());
+        };
+        if range.is_empty() || !range.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return Ok(whole());
+        }
+        let (from, to) = range.split_once('-').unwrap_or((range, range));
+        let (Ok(from), Ok(to)) = (from.parse::<u32>(), to.parse::<u32>()) else {
+            bail!("Invalid line range {:?} (expected LINE or FROM-TO)", range);
+        };
+        if from == 0 || to < from {
+            bail!("Invalid line range {:?} (lines start at 1)", range);
+        }
+        Ok(Target {
+            path: path.to_string(),
+            lines: Some((from, to)),
+        })
+    }
+}
+
+/// Selected `+`/`-` line indices per hunk index
+type Selection = BTreeMap<usize, HashSet<usize>>;
+
+/// The changed lines within `from..=to` of the diff's new side. A removed
+/// line sits at the position of the new-side line that follows it, so a
+/// range covering a replaced line takes both its `-` and `+` lines.
+fn select_lines(hunks: &[Hunk], from: u32, to: u32) -> Selection {
+    let mut selection = Selection::new();
+    for (hunk_idx, hunk) in hunks.iter().enumerate() {
+        // A hunk with no new lines starts at the line before it
+        let mut line_no = hunk.new_start + u32::from(hunk.new_count == 0);
+        for (line_idx, line) in hunk.lines.iter().enumerate() {
+            let in_range = (from..=to).contains(&line_no);
+            match line {
+                DiffLine::Context(_) => line_no += 1,
+                DiffLine::Added(_) => {
+                    if in_range {
+                        selection.entry(hunk_idx).or_default().insert(line_idx);
+                    }
+                    line_no += 1;
+                }
+                DiffLine::Removed(_) => {
+                    if in_range {
+                        selection.entry(hunk_idx).or_default().insert(line_idx);
+                    }
+                }
+            }
+        }
+    }
+    selection
+}
+
+/// Every changed line of the hunks named by `specs`: 1-based indices as
+/// shown in the UI, or hunk IDs from `diffview --json diff`
+fn select_hunks(path: &str, hunks: &[Hunk], specs: &[String]) -> Result<Selection> {
+    let ids = hunk_ids(path, hunks);
+    let mut selection = Selection::new();
+    for spec in specs {
+        let hunk_idx = match spec.parse::<usize>() {
+            Ok(n) if (1..=hunks.len()).contains(&n) => n - 1,
+            Ok(n) => bail!("{} has {} hunks, no hunk {}", path, hunks.len(), n),
+            Err(_) => ids
+                .iter()
+                .position(|id| id == spec)
+                .with_context(|| format!("No hunk with ID {} in {}", spec, path))?,
+        };
+        let changed = hunks[hunk_idx]
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, DiffLine::Context(_)))
+            .map(|(idx, _)| idx);
+        selection.entry(hunk_idx).or_default().extend(changed);
+    }
+    Ok(selection)
+}
+
+/// `diffview stage` / `diffview unstage`: apply whole files, line ranges
+/// or hunks to the index without the UI
+pub fn stage(targets: &[String], hunks: &[String], unstage: bool) -> Result<()> {
+    let (repo_root, git) = open_backend()?;
+    let action = if unstage { "Unstaged" } else { "Staged" };
+    if !hunks.is_empty() && targets.len() != 1 {
+        bail!("--hunk needs exactly one path");
+    }
+
+    for spec in targets {
+        let target = Target::parse(spec)?;
+        if target.lines.is_some() && !hunks.is_empty() {
+            bail!("Use either --hunk or a line range, not both");
+        }
+        let path = repo_path(&repo_root, &target.path);
+        if target.lines.is_none() && hunks.is_empty() {
+            if unstage {
+                git.unstage_file(&path)?;
+            } else {
+                git.stage_file(&path)?;
+            }
+            output(&format!("{} {}\n", action, path))?;
+            continue;
+        }
+
+        // Stage from the worktree diff, unstage from the staged diff
+        let file = parse_diff(&git.diff(&path, unstage)?);
+        let selection = match target.lines {
+            Some((from, to)) => select_lines(&file.hunks, from, to),
+            None => select_hunks(&path, &file.hunks, hunks)?,
+        };
+        if selection.is_empty() {
+            bail!(
+                "No {} changes in {}",
+                if unstage { "staged" } else { "unstaged" },
+                spec
+            );
+        }
+
+        // Bottom-up, so earlier hunks keep their line numbers
+        let mut applied = 0;
+        for (hunk_idx, selected) in selection.iter().rev() {
+            let hunk = &file.hunks[*hunk_idx];
+            if unstage {
+                crate::git::apply::unstage_lines(&path, hunk, selected, git.as_ref())?;
+            } else {
+                crate::git::apply::stage_lines(&path, hunk, selected, git.as_ref())?;
+            }
+            applied += selected.len();
+        }
+        let noun = if applied == 1 { "line" } else { "lines" };
+        output(&format!("{} {} {} of {}\n", action, applied, noun, path))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunks(raw: &str) -> Vec<Hunk> {
+        parse_diff(raw).hunks
+    }
+
+    const DIFF: &str = "diff --git a/f b/f\n--- a/f\n+++ b/f\n\
+        @@ -1,4 +1,5 @@\n a\n-b\n+B\n c\n+new\n d\n\
+        @@ -20,2 +21,1 @@\n x\n-y\n";
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            Target::parse("src/app.rs:120-140").unwrap(),
+            Target {
+                path: "src/app.rs".into(),
+                lines: Some((120, 140))
+            }
+        );
+        assert_eq!(Target::parse("a.rs:7").unwrap().lines, Some((7, 7)));
+        assert_eq!(Target::parse("a:b.rs").unwrap().lines, None);
+        assert_eq!(Target::parse("a:b.rs").unwrap().path, "a:b.rs");
+        assert!(Target::parse("a.rs:9-3").is_err());
+        assert!(Target::parse("a.rs:0").is_err());
+    }
+
+    #[test]
+    fn test_select_lines_by_new_line_number() {
+        let hunks = hunks(DIFF);
+        // Line 2 is "B", which replaced "b"
+        let selection = select_lines(&hunks, 2, 2);
+        assert_eq!(selection[&0], HashSet::from([1, 2]));
+        assert_eq!(selection.len(), 1);
+        // "new" is line 4; the trailing "-y" sits before line 22
+        assert_eq!(select_lines(&hunks, 4, 4)[&0], HashSet::from([4]));
+        assert_eq!(select_lines(&hunks, 22, 30)[&1], HashSet::from([1]));
+        assert!(select_lines(&hunks, 10, 20).is_empty());
+    }
+
+    #[test]
+    fn test_select_hunks_by_index_or_id() {
+        let hunks = hunks(DIFF);
+        let id = hunk_ids("f", &hunks)[1].clone();
+        let selection = select_hunks("f", &hunks, &["1".into(), id]).unwrap();
+        assert_eq!(selection[&0], HashSet::from([1, 2, 4]));
+        assert_eq!(selection[&1], HashSet::from([1]));
+        assert!(select_hunks("f", &hunks, &["3".into()]).is_err());
+        assert!(select_hunks("f", &hunks, &["nope".into()]).is_err());
+    }
+}
@@ -1,11 +1,15 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiffConfig {
     #[serde(default = "default_tool")]
     pub tool: String,
+    /// Start in the continuous view, with every file of a section in one diff
+    #[serde(default)]
+    pub continuous: bool,
 }
 
 fn default_tool() -> String {
@@ -16,6 +20,188 @@ impl Default for DiffConfig {
     fn default() -> Self {
         Self {
             tool: default_tool(),
+            continuous: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeConfig {
+    /// Show `+N -M` line counts next to tree entries
+    #[serde(default = "default_true")]
+    pub diffstat: bool,
+    /// Files changing at least this many lines are highlighted
+    #[serde(default = "default_heavy_lines")]
+    pub heavy_lines: u32,
+    /// Globs for paths hidden from the trees (e.g. generated files),
+    /// in the same syntax as the filter bar
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_heavy_lines() -> u32 {
+    300
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        Self {
+            diffstat: true,
+            heavy_lines: default_heavy_lines(),
+            ignore: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchConfig {
+    /// Refresh automatically when the worktree or index changes
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Quiet period before a burst of changes triggers a refresh
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+fn default_debounce_ms() -> u64 {
+    200
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            debounce_ms: default_debounce_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitConfig {
+    /// `process` runs `git`; `gix` reads status and diffs in-process
+    /// (needs a build with `--features gix`)
+    #[serde(default = "default_backend")]
+    pub backend: String,
+}
+
+fn default_backend() -> String {
+    "process".to_string()
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            backend: default_backend(),
+        }
+    }
+}
+
+/// `[theme]`: a preset plus optional per-element colour overrides.
+/// Colours are names (`red`, `lightblue`), `#rrggbb` or a 256-colour index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    /// `dark` or `light`
+    #[serde(default = "default_preset")]
+    pub preset: String,
+    /// `auto`, `none`, `16`, `256` or `truecolor`
+    #[serde(default)]
+    pub color_depth: Option<String>,
+    #[serde(default)]
+    pub modified: Option<String>,
+    #[serde(default)]
+    pub added: Option<String>,
+    #[serde(default)]
+    pub deleted: Option<String>,
+    #[serde(default)]
+    pub renamed: Option<String>,
+    #[serde(default)]
+    pub untracked: Option<String>,
+    #[serde(default)]
+    pub unmerged: Option<String>,
+    #[serde(default)]
+    pub directory: Option<String>,
+    #[serde(default)]
+    pub diff_added_fg: Option<String>,
+    #[serde(default)]
+    pub diff_added_bg: Option<String>,
+    #[serde(default)]
+    pub diff_removed_fg: Option<String>,
+    #[serde(default)]
+    pub diff_removed_bg: Option<String>,
+    #[serde(default)]
+    pub hunk_header: Option<String>,
+    #[serde(default)]
+    pub file_header: Option<String>,
+    #[serde(default)]
+    pub cursor_fg: Option<String>,
+    #[serde(default)]
+    pub cursor_bg: Option<String>,
+    #[serde(default)]
+    pub selection_bg: Option<String>,
+    #[serde(default)]
+    pub border: Option<String>,
+    #[serde(default)]
+    pub border_focused: Option<String>,
+    #[serde(default)]
+    pub dim: Option<String>,
+    #[serde(default)]
+    pub statusbar_fg: Option<String>,
+    #[serde(default)]
+    pub statusbar_bg: Option<String>,
+}
+
+fn default_preset() -> String {
+    "dark".to_string()
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            preset: default_preset(),
+            color_depth: None,
+            modified: None,
+            added: None,
+            deleted: None,
+            renamed: None,
+            untracked: None,
+            unmerged: None,
+            directory: None,
+            diff_added_fg: None,
+            diff_added_bg: None,
+            diff_removed_fg: None,
+            diff_removed_bg: None,
+            hunk_header: None,
+            file_header: None,
+            cursor_fg: None,
+            cursor_bg: None,
+            selection_bg: None,
+            border: None,
+            border_focused: None,
+            dim: None,
+            statusbar_fg: None,
+            statusbar_bg: None,
+        }
+    }
+}
+
+/// Keys bound to one action: a single key sequence or a list of alternatives.
+/// A sequence is space separated, e.g. `"g g"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeySpec {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeySpec {
+    pub fn keys(&self) -> Vec<String> {
+        match self {
+            KeySpec::One(key) => vec![key.clone()],
+            KeySpec::Many(keys) => keys.clone(),
         }
     }
 }
@@ -24,18 +210,127 @@ impl Default for DiffConfig {
 pub struct Config {
     #[serde(default)]
     pub diff: DiffConfig,
+    #[serde(default)]
+    pub tree: TreeConfig,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    #[serde(default)]
+    pub watch: WatchConfig,
+    #[serde(default)]
+    pub git: GitConfig,
+    /// Action name → key(s), replacing the default bindings of that action
+    #[serde(default)]
+    pub keys: HashMap<String, KeySpec>,
+}
+
+/// Where a configuration value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    User(PathBuf),
+    Repo(PathBuf),
+    GitConfig(String),
+    Env(String),
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::User(path) => write!(f, "user config {}", path.display()),
+            ConfigSource::Repo(path) => write!(f, "repo config {}", path.display()),
+            ConfigSource::GitConfig(name) => write!(f, "git config {}", name),
+            ConfigSource::Env(name) => write!(f, "env {}", name),
+        }
+    }
+}
+
+/// The effective configuration together with the origin of each value.
+#[derive(Debug, Clone, Default)]
+pub struct LoadedConfig {
+    pub config: Config,
+    /// Dotted key (`diff.tool`, `keys.next_hunk`) → layer that set it last
+    pub sources: BTreeMap<String, ConfigSource>,
+    /// Settings that could not be mapped to a config key
+    pub problems: Vec<String>,
+}
+
+impl LoadedConfig {
+    /// Effective config as TOML, each value annotated with its source
+    pub fn describe(&self) -> String {
+        let value = toml::Value::try_from(&self.config)
+            .unwrap_or_else(|_| toml::Value::Table(Default::default()));
+        let Some(sections) = value.as_table() else {
+            return String::new();
+        };
+
+        let mut out = String::new();
+        for (section, table) in sections {
+            let Some(table) = table.as_table().filter(|t| !t.is_empty()) else {
+                continue;
+            };
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("[{}]\n", section));
+
+            let mut keys: Vec<&String> = table.keys().collect();
+            keys.sort();
+            for key in keys {
+                let dotted = format!("{}.{}", section, key);
+                let source = self
+                    .sources
+                    .get(&dotted)
+                    .cloned()
+                    .unwrap_or(ConfigSource::Default);
+                let line = format!("{} = {}", toml_key(key), table[key]);
+                out.push_str(&format!("{:<40} # {}\n", line, source));
+            }
+        }
+        out
+    }
+}
+
+fn toml_key(key: &str) -> String {
+    if key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        format!("{:?}", key)
+    }
 }
 
 impl Config {
-    pub fn load() -> Result<Self> {
-        let path = Self::config_path();
-        if path.exists() {
-            let content = std::fs::read_to_string(&path)?;
-            let config: Config = toml::from_str(&content)?;
-            Ok(config)
-        } else {
-            Ok(Config::default())
+    /// Merge every configuration layer. Later layers win, per key:
+    ///   1. built-in defaults
+    ///   2. user config (`~/.config/diffview/config.toml`)
+    ///   3. repo config (`<repo>/.diffview.toml`)
+    ///   4. `git config diffview.<section>.<key>` (e.g. `diffview.diff.tool`)
+    ///   5. environment (`DIFFVIEW_<SECTION>_<KEY>`, e.g. `DIFFVIEW_DIFF_TOOL`)
+    ///
+    /// Command-line flags are applied on top by the caller.
+    pub fn load(repo_root: Option<&Path>) -> Result<LoadedConfig> {
+        let mut layers = ConfigLayers::default();
+
+        let user_path = Self::config_path();
+        layers.apply_file(&user_path, &ConfigSource::User(user_path.clone()));
+
+        if let Some(root) = repo_root {
+            let repo_path = Self::repo_config_path(root);
+            layers.apply_file(&repo_path, &ConfigSource::Repo(repo_path.clone()));
+
+            for (name, value) in crate::git::get_config_entries("diffview", root)? {
+                layers.apply_git_entry(&name, &value);
+            }
         }
+
+        for (name, value) in std::env::vars() {
+            layers.apply_env_var(&name, &value);
+        }
+
+        layers.finish()
     }
 
     pub fn config_path() -> PathBuf {
@@ -45,4 +340,279 @@ impl Config {
             .join("diffview")
             .join("config.toml")
     }
+
+    pub fn repo_config_path(repo_root: &Path) -> PathBuf {
+        repo_root.join(".diffview.toml")
+    }
+}
+
+fn read_table(path: &Path) -> Result<Option<toml::Table>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let table =
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(Some(table))
+}
+
+/// Accumulates layers into one TOML table, remembering the source of every
+/// leaf value.
+#[derive(Default)]
+struct ConfigLayers {
+    merged: toml::Table,
+    sources: BTreeMap<String, ConfigSource>,
+    problems: Vec<String>,
+}
+
+impl ConfigLayers {
+    /// A config file that cannot be read or parsed is reported and skipped
+    fn apply_file(&mut self, path: &Path, source: &ConfigSource) {
+        match read_table(path) {
+            Ok(Some(table)) => self.apply_table(table, source),
+            Ok(None) => {}
+            Err(err) => self.problems.push(format!("{:#}", err)),
+        }
+    }
+
+    fn apply_table(&mut self, table: toml::Table, source: &ConfigSource) {
+        for (section, value) in table {
+            match value {
+                toml::Value::Table(entries) => {
+                    for (key, value) in entries {
+                        self.set(&section, &key, value, source.clone());
+                    }
+                }
+                other => {
+                    let mut root = toml::Table::new();
+                    root.insert(section.clone(), other.clone());
+                    if let Err(err) = toml::Value::Table(root).try_into::<Config>() {
+                        self.problems
+                            .push(format!("{} {}: {}", source, section, err.message()));
+                        continue;
+                    }
+                    self.merged.insert(section.clone(), other);
+                    self.sources.insert(section, source.clone());
+                }
+            }
+        }
+    }
+
+    /// `diffview.tree.heavy-lines` → `tree.heavy_lines`
+    fn apply_git_entry(&mut self, name: &str, raw: &str) {
+        let rest = name.strip_prefix("diffview.").unwrap_or(name);
+        match rest.split_once('.') {
+            Some((section, key)) => {
+                let key = key.replace('-', "_");
+                let value = self.parse_value(section, &key, raw);
+                self.set(
+                    section,
+                    &key,
+                    value,
+                    ConfigSource::GitConfig(name.to_string()),
+                );
+            }
+            None => self.problems.push(format!(
+                "git config {}: expected diffview.<section>.<key>",
+                name
+            )),
+        }
+    }
+
+    /// `DIFFVIEW_TREE_HEAVY_LINES` → `tree.heavy_lines`
+    fn apply_env_var(&mut self, name: &str, raw: &str) {
+        let Some(rest) = name.strip_prefix("DIFFVIEW_") else {
+            return;
+        };
+        let rest = rest.to_lowercase();
+        match rest.split_once('_') {
+            Some((section, key)) if SECTIONS.contains(&section) => {
+                let value = self.parse_value(section, key, raw);
+                self.set(section, key, value, ConfigSource::Env(name.to_string()));
+            }
+            _ => self
+                .problems
+                .push(format!("env {}: not a diffview setting", name)),
+        }
+    }
+
+    /// Git and environment values are plain strings; convert them to the
+    /// type of the built-in default for that key.
+    fn parse_value(&self, section: &str, key: &str, raw: &str) -> toml::Value {
+        let defaults = toml::Value::try_from(Config::default()).ok();
+        let default = defaults
+            .as_ref()
+            .and_then(|d| d.get(section))
+            .and_then(|s| s.get(key));
+
+        match default {
+            Some(toml::Value::Boolean(_)) => match raw.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => toml::Value::Boolean(true),
+                "false" | "no" | "off" | "0" | "" => toml::Value::Boolean(false),
+                _ => toml::Value::String(raw.to_string()),
+            },
+            Some(toml::Value::Integer(_)) => raw
+                .trim()
+                .parse()
+                .map(toml::Value::Integer)
+                .unwrap_or_else(|_| toml::Value::String(raw.to_string())),
+            Some(toml::Value::Array(_)) => parse_list(raw),
+            _ if raw.trim_start().starts_with('[') => parse_list(raw),
+            _ => toml::Value::String(raw.to_string()),
+        }
+    }
+
+    /// Set one value. A value of the wrong type is reported and skipped, so
+    /// the other settings still apply.
+    fn set(&mut self, section: &str, key: &str, value: toml::Value, source: ConfigSource) {
+        if let Err(err) = check_value(section, key, &value) {
+            self.problems
+                .push(format!("{} {}.{}: {}", source, section, key, err.message()));
+            return;
+        }
+        let entry = self
+            .merged
+            .entry(section.to_string())
+            .or_insert_with(|| toml::Value::Table(Default::default()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(Default::default());
+        }
+        if let Some(table) = entry.as_table_mut() {
+            table.insert(key.to_string(), value);
+        }
+        self.sources.insert(format!("{}.{}", section, key), source);
+    }
+
+    fn finish(self) -> Result<LoadedConfig> {
+        let config: Config = toml::Value::Table(self.merged)
+            .try_into()
+            .context("Invalid configuration")?;
+        Ok(LoadedConfig {
+            config,
+            sources: self.sources,
+            problems: self.problems,
+        })
+    }
+}
+
+/// Whether `section.key = value` deserializes on its own
+fn check_value(section: &str, key: &str, value: &toml::Value) -> Result<(), toml::de::Error> {
+    let mut entries = toml::Table::new();
+    entries.insert(key.to_string(), value.clone());
+    let mut root = toml::Table::new();
+    root.insert(section.to_string(), toml::Value::Table(entries));
+    toml::Value::Table(root).try_into::<Config>().map(|_| ())
+}
+
+/// Config sections that environment variables may target
+const SECTIONS: &[&str] = &["diff", "tree", "theme", "watch", "git", "keys"];
+
+/// A TOML array (`["a", "b"]`) or a comma separated list
+fn parse_list(raw: &str) -> toml::Value {
+    if let Ok(table) = toml::from_str::<toml::Table>(&format!("v = {}", raw)) {
+        if let Some(value @ toml::Value::Array(_)) = table.get("v") {
+            return value.clone();
+        }
+    }
+    toml::Value::Array(
+        raw.split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| toml::Value::String(s.to_string()))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(content: &str) -> toml::Table {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_later_layers_override_per_key() {
+        let user = ConfigSource::User(PathBuf::from("user.toml"));
+        let repo = ConfigSource::Repo(PathBuf::from(".diffview.toml"));
+
+        let mut layers = ConfigLayers::default();
+        layers.apply_table(
+            table("[diff]\ntool = \"delta\"\n[tree]\nheavy_lines = 50\n"),
+            &user,
+        );
+        layers.apply_table(table("[diff]\ntool = \"difftastic\"\n"), &repo);
+        layers.apply_git_entry("diffview.tree.diffstat", "off");
+        layers.apply_env_var("DIFFVIEW_KEYS_NEXT_HUNK", "] h");
+
+        let loaded = layers.finish().unwrap();
+        assert_eq!(loaded.config.diff.tool, "difftastic");
+        assert_eq!(loaded.config.tree.heavy_lines, 50);
+        assert!(!loaded.config.tree.diffstat);
+        assert_eq!(
+            loaded.config.keys.get("next_hunk"),
+            Some(&KeySpec::One("] h".to_string()))
+        );
+        assert_eq!(loaded.sources["diff.tool"], repo);
+        assert_eq!(loaded.sources["tree.heavy_lines"], user);
+        assert_eq!(
+            loaded.sources["tree.diffstat"],
+            ConfigSource::GitConfig("diffview.tree.diffstat".to_string())
+        );
+    }
+
+    #[test]
+    fn test_string_values_follow_default_types() {
+        let mut layers = ConfigLayers::default();
+        layers.apply_env_var("DIFFVIEW_TREE_HEAVY_LINES", "120");
+        layers.apply_env_var("DIFFVIEW_TREE_IGNORE", "*.lock, gen/");
+        layers.apply_git_entry("diffview.keys.open", "[\"o\", \"right\"]");
+        layers.apply_git_entry("diffview.theme.cursor-bg", "236");
+        layers.apply_env_var("DIFFVIEW_UNKNOWN", "1");
+
+        let loaded = layers.finish().unwrap();
+        assert_eq!(loaded.config.tree.heavy_lines, 120);
+        assert_eq!(loaded.config.tree.ignore, vec!["*.lock", "gen/"]);
+        assert_eq!(
+            loaded.config.keys["open"],
+            KeySpec::Many(vec!["o".to_string(), "right".to_string()])
+        );
+        assert_eq!(loaded.config.theme.cursor_bg.as_deref(), Some("236"));
+        assert_eq!(loaded.problems.len(), 1);
+    }
+
+    #[test]
+    fn test_bad_values_are_skipped_alone() {
+        let user = ConfigSource::User(PathBuf::from("user.toml"));
+        let mut layers = ConfigLayers::default();
+        layers.apply_table(
+            table("diff = 1\n[tree]\nheavy_lines = \"many\"\ndiffstat = false\n[keys]\nopen = 3\n"),
+            &user,
+        );
+        layers.apply_env_var("DIFFVIEW_WATCH_DEBOUNCE_MS", "soon");
+        layers.apply_env_var("DIFFVIEW_DIFF_TOOL", "delta");
+
+        let loaded = layers.finish().unwrap();
+        assert_eq!(loaded.config.diff.tool, "delta");
+        assert!(!loaded.config.tree.diffstat);
+        assert_eq!(loaded.config.tree.heavy_lines, 300);
+        assert!(loaded.config.keys.is_empty());
+        assert_eq!(loaded.config.watch.debounce_ms, 200);
+        assert_eq!(loaded.problems.len(), 4, "{:?}", loaded.problems);
+        assert!(loaded.problems[2].starts_with("user config user.toml tree.heavy_lines: "));
+    }
+
+    #[test]
+    fn test_describe_annotates_sources() {
+        let mut layers = ConfigLayers::default();
+        layers.apply_env_var("DIFFVIEW_DIFF_TOOL", "delta");
+        let text = layers.finish().unwrap().describe();
+        assert!(text.contains("[diff]"));
+        let tool = text.lines().find(|l| l.starts_with("tool")).unwrap();
+        assert!(tool.contains("\"delta\""));
+        assert!(tool.ends_with("# env DIFFVIEW_DIFF_TOOL"));
+        let diffstat = text.lines().find(|l| l.starts_with("diffstat")).unwrap();
+        assert!(diffstat.ends_with("# default"));
+    }
 }
@@ -0,0 +1,696 @@
+//! End-to-end tests: real repositories in a temporary directory, driven by
+//! scripted key presses through `App::handle_key` and rendered with
+//! ratatui's `TestBackend`. They check what `git apply` actually accepts,
+//! which the patch-builder unit tests cannot.
+
+use crate::app::{App, Focus};
+use crate::config::LoadedConfig;
+use crate::keymap::KeyChord;
+use crate::testing::TestRepo;
+use crossterm::event::KeyEvent;
+use ratatui::{backend::TestBackend, Terminal};
+
+fn open_app(repo: &TestRepo, revision: Option<&str>) -> App {
+    App::open(
+        repo.root.clone(),
+        LoadedConfig::default(),
+        Some("raw".to_string()),
+        revision.map(String::from),
+    )
+    .unwrap()
+}
+
+/// Press space-separated keys in config syntax, e.g. `"j j v enter"`
+fn press(app: &mut App, keys: &str) {
+    for spec in keys.split_whitespace() {
+        let chord = KeyChord::parse(spec).unwrap_or_else(|| panic!("bad key {:?}", spec));
+        app.handle_key(KeyEvent::new(chord.code, chord.modifiers))
+            .unwrap();
+    }
+}
+
+/// The frame as text, one line per row
+fn render(app: &App) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(100, 24)).unwrap();
+    terminal.draw(|f| crate::ui::render(f, app)).unwrap();
+    let buffer = terminal.backend().buffer();
+    (0..buffer.area.height)
+        .map(|y| {
+            (0..buffer.area.width)
+                .map(|x| buffer[(x, y)].symbol())
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Lines of the frame's left column (the tree panes), for matching entries
+fn tree_rows(frame: &str) -> Vec<String> {
+    frame
+        .lines()
+        .map(|line| line.chars().take(25).collect::<String>())
+        .collect()
+}
+
+#[test]
+fn test_stage_single_added_line() {
+    let repo = TestRepo::new("stage-line");
+    repo.write("f.txt", "a\nb\nc\n");
+    repo.commit_all("init");
+    repo.write("f.txt", "a\nB\nc\nd\n");
+
+    let mut app = open_app(&repo, None);
+    // Open the diff and move the cursor from `diff --git` down to "+d"
+    press(&mut app, "l v");
+    assert_eq!(app.focus, Focus::InlineSelect);
+    press(&mut app, &"j ".repeat(9));
+    press(&mut app, "enter");
+
+    assert_eq!(repo.index("f.txt"), "a\nb\nc\nd\n");
+    assert_eq!(app.error_message, None);
+
+    // The file is now both partially staged and still modified
+    press(&mut app, "h");
+    let frame = render(&app);
+    assert!(frame.contains("Unstaged (1)"), "{}", frame);
+    assert!(frame.contains("Staged (1)"), "{}", frame);
+}
+
+#[test]
+fn test_stage_line_range() {
+    let repo = TestRepo::new("stage-range");
+    repo.write("f.txt", "a\nb\nc\n");
+    repo.commit_all("init");
+    repo.write("f.txt", "a\nB\nc\nd\n");
+
+    let mut app = open_app(&repo, None);
+    // Select "-b" and "+B" together
+    press(&mut app, "l v j j j j j j V j enter");
+
+    assert_eq!(repo.index("f.txt"), "a\nB\nc\n");
+}
+
+#[test]
+fn test_unstage_single_removed_line() {
+    let repo = TestRepo::new("unstage-line");
+    repo.write("f.txt", "a\nb\nc\n");
+    repo.commit_all("init");
+    repo.write("f.txt", "a\nB\nc\nd\n");
+    repo.git(&["add", "f.txt"]);
+
+    let mut app = open_app(&repo, None);
+    assert_eq!(app.focus, Focus::Staged);
+    // "-b" is the seventh line of the staged diff
+    press(&mut app, "l v j j j j j j enter");
+
+    assert_eq!(repo.index("f.txt"), "a\nb\nB\nc\nd\n");
+    assert_eq!(app.error_message, None);
+    let frame = render(&app);
+    assert!(frame.contains("f.txt [staged]"), "{}", frame);
+}
+
+#[test]
+fn test_stage_and_unstage_directory() {
+    let repo = TestRepo::new("stage-dir");
+    repo.write("top.txt", "1\n");
+    repo.write("src/x.rs", "x\n");
+    repo.commit_all("init");
+    repo.write("top.txt", "2\n");
+    repo.write("src/x.rs", "x2\n");
+    repo.write("src/y.rs", "y\n");
+
+    let mut app = open_app(&repo, None);
+    let frame = render(&app);
+    let rows = tree_rows(&frame);
+    // Directories sort first, so the cursor starts on `src`
+    assert!(rows[1].contains("▼ src"), "{}", frame);
+    assert!(rows[4].contains("top.txt"), "{}", frame);
+
+    press(&mut app, "enter");
+    assert_eq!(repo.staged_paths(), vec!["src/x.rs", "src/y.rs"]);
+    let frame = render(&app);
+    assert!(frame.contains("Staged (2)"), "{}", frame);
+
+    // Moving past the last unstaged entry continues in the staged tree
+    press(&mut app, "j");
+    assert_eq!(app.focus, Focus::Staged);
+    press(&mut app, "enter");
+    assert!(repo.staged_paths().is_empty());
+    assert!(render(&app).contains("Staged (0)"));
+}
+
+#[test]
+fn test_commit_mode_is_read_only() {
+    let repo = TestRepo::new("commit-mode");
+    repo.write("f.txt", "a\n");
+    repo.commit_all("init");
+    repo.write("f.txt", "a\nb\n");
+    repo.write("g.txt", "g\n");
+    repo.commit_all("second");
+    let head = repo.git(&["rev-parse", "--short", "HEAD"]);
+
+    let app = open_app(&repo, Some("HEAD"));
+    let frame = render(&app);
+    assert!(frame.contains("f.txt"), "{}", frame);
+    assert!(frame.contains("g.txt"), "{}", frame);
+    assert!(frame.contains(head.trim()), "{}", frame);
+    assert!(frame.contains("+b"), "{}", frame);
+
+    // Go back before the commit, so it would apply cleanly
+    repo.git(&["reset", "-q", "--hard", "HEAD~1"]);
+    let mut app = open_app(&repo, Some(head.trim()));
+    let worktree = || std::fs::read_to_string(repo.root.join("f.txt")).unwrap();
+    let read_only = |app: &App| {
+        assert!(
+            app.error_message
+                .as_deref()
+                .is_some_and(|e| e.starts_with("The commit is read-only")),
+            "{:?}",
+            app.error_message
+        );
+        assert_eq!(worktree(), "a\n");
+        assert!(!repo.root.join("g.txt").exists());
+        assert!(repo.staged_paths().is_empty());
+    };
+
+    // Enter opens the diff instead of applying the file
+    press(&mut app, "enter");
+    assert_eq!(app.focus, Focus::DiffView);
+    assert_eq!(app.error_message, None);
+    press(&mut app, "enter");
+    read_only(&app);
+    press(&mut app, "v enter");
+    assert_eq!(app.focus, Focus::InlineSelect);
+    read_only(&app);
+
+    // Cherry-picking asks first; declining leaves the worktree alone
+    press(&mut app, "h P");
+    assert!(render(&app).contains("CHERRY-PICK"));
+    press(&mut app, "esc");
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Worktree left untouched")
+    );
+    assert_eq!(worktree(), "a\n");
+}
+
+#[test]
+fn test_export_hunk_and_lines_as_patches() {
+    let repo = TestRepo::new("export");
+    let lines: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+    repo.write("f.txt", &format!("{}\n", lines.join("\n")));
+    repo.commit_all("init");
+    let mut changed = lines.clone();
+    changed[1] = "two".to_string();
+    changed.insert(2, "new".to_string());
+    changed[18] = "eighteen".to_string();
+    repo.write("f.txt", &format!("{}\n", changed.join("\n")));
+
+    let mut app = open_app(&repo, None);
+    app.export_dir = repo.root.join(".git");
+    // The second hunk from the diff view, then "+new" from line select
+    press(&mut app, "l n w");
+    assert_eq!(app.error_message, None);
+    press(&mut app, &format!("g v {}w", "j ".repeat(8)));
+    assert_eq!(app.error_message, None);
+
+    let apply = |name: &str| {
+        repo.git(&["checkout", "f.txt"]);
+        repo.git(&["apply", &format!(".git/{}", name)]);
+        std::fs::read_to_string(repo.root.join("f.txt")).unwrap()
+    };
+    let mut expected = lines.clone();
+    expected[17] = "eighteen".to_string();
+    assert_eq!(
+        apply("f.txt-hunk-2.patch"),
+        format!("{}\n", expected.join("\n"))
+    );
+    let mut expected = lines.clone();
+    expected.insert(2, "new".to_string());
+    assert_eq!(
+        apply("f.txt-lines.patch"),
+        format!("{}\n", expected.join("\n"))
+    );
+}
+
+#[test]
+fn test_export_commit_as_format_patch() {
+    let repo = TestRepo::new("export-commit");
+    repo.write("f.txt", "a\n");
+    repo.commit_all("init");
+    repo.write("f.txt", "a\nb\n");
+    repo.commit_all("Add b");
+
+    let mut app = open_app(&repo, Some("HEAD"));
+    app.export_dir = repo.root.join(".git");
+    press(&mut app, "w");
+    let patch = std::fs::read_to_string(repo.root.join(".git/0001-Add-b.patch")).unwrap();
+    assert!(patch.starts_with("From "), "{}", patch);
+    assert!(patch.contains("Subject: [PATCH] Add b\n"), "{}", patch);
+    assert!(patch.contains("+b\n"), "{}", patch);
+}
+
+#[test]
+fn test_stale_diff_is_reloaded_instead_of_staged() {
+    let repo = TestRepo::new("stale-hunk");
+    repo.write("f.txt", "a\nb\n");
+    repo.commit_all("init");
+    repo.write("f.txt", "a\nB\n");
+
+    let mut app = open_app(&repo, None);
+    // Enter in the worktree diff stages nothing; line-select does
+    press(&mut app, "l enter");
+    assert_eq!(repo.index("f.txt"), "a\nb\n");
+    // Changed behind diffview's back, before any refresh
+    repo.write("f.txt", "A\nB\n");
+    press(&mut app, "v V j j j j j j enter");
+    assert_eq!(repo.index("f.txt"), "a\nb\n");
+    assert!(app.error_message.unwrap().contains("f.txt changed"));
+    assert!(app.raw_diff.contains("+A"));
+}
+
+#[test]
+fn test_review_and_apply_patch() {
+    let repo = TestRepo::new("review");
+    let lines: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+    repo.write("a.txt", &format!("{}\n", lines.join("\n")));
+    repo.write("b.txt", "b\n");
+    repo.commit_all("init");
+    let mut changed = lines.clone();
+    changed[1] = "two".to_string();
+    changed[17] = "eighteen".to_string();
+    repo.write("a.txt", &format!("{}\n", changed.join("\n")));
+    repo.write("b.txt", "B\n");
+    let patch = repo.git(&["diff"]);
+    repo.git(&["checkout", "."]);
+
+    let mut app = App::open_patch(
+        repo.root.clone(),
+        LoadedConfig::default(),
+        Some("raw".to_string()),
+        "x.patch",
+        &patch,
+    )
+    .unwrap();
+    let frame = render(&app);
+    assert!(frame.contains("Patch (2)"), "{}", frame);
+    assert!(frame.contains("a.txt M"), "{}", frame);
+    // Nothing is applied by opening the patch
+    assert!(repo.git(&["status", "--porcelain"]).is_empty());
+
+    // The second hunk of a.txt to the worktree
+    press(&mut app, "l n enter");
+    assert_eq!(app.error_message, None);
+    let mut expected = lines.clone();
+    expected[17] = "eighteen".to_string();
+    let a = std::fs::read_to_string(repo.root.join("a.txt")).unwrap();
+    assert_eq!(a, format!("{}\n", expected.join("\n")));
+    // The whole file no longer applies
+    assert_eq!(app.unstaged.files[0], ("a.txt".to_string(), ' ', '!'));
+
+    // b.txt to the index only
+    press(&mut app, "h j i");
+    assert_eq!(app.error_message, None);
+    assert_eq!(repo.index("b.txt"), "B\n");
+    assert_eq!(
+        std::fs::read_to_string(repo.root.join("b.txt")).unwrap(),
+        "b\n"
+    );
+}
+
+#[test]
+fn test_pager_browses_log_output_read_only() {
+    let repo = TestRepo::new("pager");
+    repo.write("a.txt", "1\n2\n3\n");
+    repo.commit_all("init");
+    repo.write("a.txt", "1\ntwo\n3\n");
+    repo.write("b.txt", "b\n");
+    repo.commit_all("second");
+    repo.write("b.txt", "B\n");
+    repo.commit_all("third");
+    let log = repo.git(&["log", "-p", "--color=always", "HEAD~2..HEAD"]);
+
+    let text = crate::git::patch::strip_ansi(&log);
+    let mut app = App::open_pager(
+        repo.root.clone(),
+        LoadedConfig::default(),
+        Some("raw".to_string()),
+        &text,
+    )
+    .unwrap();
+    let frame = render(&app);
+    assert!(frame.contains("Diff (3)"), "{}", frame);
+    // Each commit's files are listed under it, newest first
+    let rows = tree_rows(&frame);
+    assert!(
+        rows[1].contains("1 ") && rows[1].contains("third"),
+        "{}",
+        frame
+    );
+    assert!(
+        rows[3].contains("2 ") && rows[3].contains("second"),
+        "{}",
+        frame
+    );
+
+    // Nothing can be applied; Enter in the tree opens the diff
+    press(&mut app, "j enter enter");
+    assert_eq!(
+        app.error_message.as_deref(),
+        Some("Diff from stdin is read-only")
+    );
+    assert_eq!(repo.git(&["status", "--porcelain"]), "");
+    assert!(app.raw_diff.contains("+B"));
+    assert!(render(&app).contains("stdin"));
+
+    // The same file in the older commit is a separate entry with its own
+    // diff; a lowercase search ignores case
+    press(&mut app, "h j j j l / + b enter");
+    assert!(app
+        .current_file
+        .as_deref()
+        .unwrap()
+        .ends_with(" second/b.txt"));
+    assert!(!app.raw_diff.contains("+B"));
+    assert_eq!(app.status_message.as_deref(), Some("/+b: match 1/1"));
+}
+
+#[test]
+fn test_pager_works_outside_a_repository() {
+    let dir = std::env::temp_dir().join(format!("diffview-pager-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let text = "--- f.txt\t2024-01-01\n+++ f.txt\t2024-01-02\n@@ -1 +1 @@\n-a\n+b\n";
+
+    let mut app = App::open_pager(
+        dir.clone(),
+        LoadedConfig::default(),
+        Some("raw".to_string()),
+        text,
+    )
+    .unwrap();
+    assert!(render(&app).contains("+b"));
+    press(&mut app, "t");
+    assert_eq!(app.error_message, None);
+    assert!(!dir.join(".git").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_continuous_view_stages_across_files() {
+    let repo = TestRepo::new("continuous");
+    let lines: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+    let original = format!("{}\n", lines.join("\n"));
+    repo.write("a.txt", &original);
+    repo.write("b.txt", "b\n");
+    repo.commit_all("init");
+    let mut changed = lines.clone();
+    changed[1] = "two".to_string();
+    changed[17] = "eighteen".to_string();
+    repo.write("a.txt", &format!("{}\n", changed.join("\n")));
+    repo.write("b.txt", "B\n");
+
+    let mut app = open_app(&repo, None);
+    press(&mut app, "a l");
+    assert!(app.continuous);
+    let frame = render(&app);
+    assert!(
+        frame.contains("All files [unstaged] (2 files, hunk 1/3)"),
+        "{}",
+        frame
+    );
+    assert!(frame.contains("▸ a.txt"), "{}", frame);
+
+    // b.txt's diff starts after a.txt's 19 lines
+    press(&mut app, "]");
+    assert_eq!(app.diff_scroll, 19);
+    assert!(render(&app).contains("▸ b.txt  file 2/2"));
+
+    // "+B" is mapped to b.txt, not to the file the view started with
+    press(&mut app, &format!("v {}enter", "j ".repeat(6)));
+    assert_eq!(app.error_message, None);
+    assert_eq!(repo.index("b.txt"), "b\nB\n");
+    assert_eq!(repo.index("a.txt"), original);
+
+    // n/p run across files; the third hunk is b.txt's
+    press(&mut app, "p p p n n V j j enter");
+    assert_eq!(app.error_message, None);
+    assert_eq!(repo.index("b.txt"), "B\n");
+    assert_eq!(app.diff_files.len(), 1);
+    assert_eq!(app.file_diff.hunks.len(), 2);
+}
+
+/// Type `text` into an open input line
+fn type_text(app: &mut App, text: &str) {
+    for c in text.chars() {
+        app.handle_key(KeyEvent::from(crossterm::event::KeyCode::Char(c)))
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_notes_follow_their_line() {
+    let repo = TestRepo::new("notes");
+    repo.write("f.txt", "a\nb\nc\n");
+    repo.commit_all("init");
+    repo.write("f.txt", "a\nB\nc\nd\n");
+
+    // c in the diff comments on the hunk's first changed line, "-b"
+    let mut app = open_app(&repo, None);
+    press(&mut app, "l c");
+    type_text(&mut app, "ask about this");
+    press(&mut app, "enter");
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Note on f.txt:2 (removed) saved")
+    );
+    assert!(render(&app).contains("-b  ◆ ask about this"));
+
+    // A line further down, in line select
+    press(&mut app, &format!("v {}c", "j ".repeat(9)));
+    type_text(&mut app, "TODO: rename");
+    press(&mut app, "enter");
+    assert_eq!(app.notes.len(), 2);
+
+    // Notes are kept per repository and shown again after staging
+    repo.git(&["add", "f.txt"]);
+    let mut app = open_app(&repo, None);
+    assert_eq!(app.focus, Focus::Staged);
+    press(&mut app, "l");
+    let frame = render(&app);
+    assert!(frame.contains("+d  ◆ TODO: rename"), "{}", frame);
+
+    press(&mut app, "C");
+    let frame = render(&app);
+    assert!(frame.contains("Notes (2)"), "{}", frame);
+    assert!(frame.contains("f.txt:4  TODO: rename"), "{}", frame);
+    // Opening the second note scrolls to it; d deletes the first
+    press(&mut app, "j enter");
+    assert_eq!(app.diff_cursor, 9);
+    press(&mut app, "C d esc");
+    assert_eq!(app.notes.len(), 1);
+    assert_eq!(
+        crate::notes::to_markdown(&app.notes.resolved(&repo.root)),
+        "- `f.txt:4` TODO: rename\n  > d\n"
+    );
+}
+
+#[test]
+fn test_viewed_marks_clear_when_the_file_changes() {
+    let repo = TestRepo::new("viewed");
+    repo.write("a.txt", "a\n");
+    repo.write("b.txt", "b\n");
+    repo.commit_all("init");
+    repo.write("a.txt", "A\n");
+    repo.write("b.txt", "B\n");
+
+    let mut app = open_app(&repo, None);
+    app.tree_width_percent = 50;
+    assert!(render(&app).contains("Unstaged (2) 0/2 viewed +2 -2"));
+    press(&mut app, "x");
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Viewed a.txt (1/2 viewed)")
+    );
+    assert!(render(&app).contains("Unstaged (2) 1/2 viewed +2 -2"));
+
+    // Kept across sessions while the file stays the same
+    let mut app = open_app(&repo, None);
+    assert!(app.unstaged.viewed.contains("a.txt"));
+
+    // Staging keeps the version, so the staged file is viewed too
+    repo.git(&["add", "a.txt"]);
+    press(&mut app, "r");
+    assert!(app.staged.viewed.contains("a.txt"));
+    assert!(app.unstaged.viewed.is_empty());
+
+    // A new edit clears the mark
+    repo.write("b.txt", "b2\n");
+    press(&mut app, "x");
+    repo.write("b.txt", "b3\n");
+    press(&mut app, "r");
+    assert!(app.unstaged.viewed.is_empty());
+    // The mark of the old version is dropped rather than kept forever
+    assert!(!app.viewed.has_path("b.txt"));
+
+    // Commit mode looks at the commit's blobs: a.txt is the version viewed
+    repo.commit_all("change");
+    let mut app = open_app(&repo, Some("HEAD"));
+    app.tree_width_percent = 50;
+    assert!(render(&app).contains("Files (2) 1/2 viewed"));
+    press(&mut app, "j x");
+    assert!(render(&app).contains("Files (2) 2/2 viewed"));
+    assert_eq!(open_app(&repo, Some("HEAD")).unstaged.viewed.len(), 2);
+}
+
+#[test]
+fn test_cherry_pick_and_revert_parts_of_a_commit() {
+    let repo = TestRepo::new("cherry-pick");
+    let lines: Vec<String> = (1..=20).map(|i| format!("l{}", i)).collect();
+    let original = format!("{}\n", lines.join("\n"));
+    repo.write("f.txt", &original);
+    repo.commit_all("init");
+    let mut changed = lines.clone();
+    changed[1] = "two".to_string();
+    changed[17] = "eighteen".to_string();
+    let changed = format!("{}\n", changed.join("\n"));
+    repo.write("f.txt", &changed);
+    repo.commit_all("change");
+    let rev = repo.git(&["rev-parse", "HEAD"]).trim().to_string();
+    repo.git(&["reset", "-q", "--hard", "HEAD~1"]);
+
+    let mut app = open_app(&repo, Some(&rev));
+    let worktree = || std::fs::read_to_string(repo.root.join("f.txt")).unwrap();
+
+    // P in the diff takes the first hunk into the worktree once confirmed
+    press(&mut app, "l P");
+    assert_eq!(worktree(), original);
+    press(&mut app, "y");
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Cherry-picked hunk 1 of f.txt to the worktree")
+    );
+    assert!(worktree().starts_with("l1\ntwo\nl3\n"));
+    assert!(worktree().contains("\nl18\n"));
+
+    // i takes the selected lines of the second hunk into the index
+    press(&mut app, "n v j j j j V j i");
+    assert_eq!(app.error_message, None);
+    assert!(repo.index("f.txt").contains("\nl17\neighteen\nl19\n"));
+    assert!(repo.index("f.txt").starts_with("l1\nl2\n"));
+
+    // R switches to reverting: the first hunk is taken out again
+    press(&mut app, "v p R P enter");
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Reverted hunk 1 of f.txt in the worktree")
+    );
+    assert_eq!(worktree(), original);
+    assert!(render(&app).contains(", reverting] (hunk 1/2)"));
+
+    // Back to cherry-picking the whole file from the tree
+    press(&mut app, "R h P y");
+    assert_eq!(worktree(), changed);
+}
+
+#[test]
+fn test_fixup_commits_for_staged_hunks() {
+    let repo = TestRepo::new("fixup");
+    let lines: Vec<String> = (1..=20).map(|i| format!("l{}", i)).collect();
+    let file = |changes: &[(usize, &str)]| {
+        let mut lines = lines.clone();
+        for &(idx, line) in changes {
+            lines[idx] = line.to_string();
+        }
+        format!("{}\n", lines.join("\n"))
+    };
+    repo.write("src/a.txt", &file(&[]));
+    repo.write("src/b.txt", &file(&[]));
+    repo.commit_all("init");
+    repo.write("src/a.txt", &file(&[(2, "three")]));
+    repo.commit_all("Spell out three");
+    repo.write("src/b.txt", &file(&[(9, "ten")]));
+    repo.commit_all("Spell out ten");
+
+    repo.write("src/a.txt", &file(&[(2, "Three")]));
+    repo.write("src/b.txt", &file(&[(9, "Ten")]));
+    repo.write("src/c.txt", "new\n");
+    repo.git(&["add", "-A"]);
+    repo.write("src/b.txt", &file(&[(9, "Ten"), (18, "unstaged")]));
+    repo.write("src/d.txt", "intended\n");
+    repo.git(&["add", "-N", "src/d.txt"]);
+    let entries = || repo.git(&["ls-files", "--stage"]);
+    let before = entries();
+
+    // F on the staged directory previews a fixup per commit
+    let mut app = open_app(&repo, None);
+    press(&mut app, "j j j F");
+    assert_eq!(app.focus, Focus::Staged);
+    assert_eq!(app.error_message, None);
+    let frame = render(&app);
+    assert!(frame.contains("Fixups: 2 commits for 2 hunks"), "{}", frame);
+    assert!(frame.contains("fixup! Spell out three"), "{}", frame);
+    assert!(frame.contains("not assigned, stays staged"), "{}", frame);
+
+    press(&mut app, "enter");
+    assert_eq!(
+        app.status_message.as_deref(),
+        Some("Created 2 fixup commits for 2 hunks")
+    );
+    let log = repo.git(&["log", "--format=%s", "-3"]);
+    assert_eq!(
+        log,
+        "fixup! Spell out three\nfixup! Spell out ten\nSpell out ten\n"
+    );
+    let show = |rev: &str| repo.git(&["show", "--format=", "--name-only", rev]);
+    assert_eq!(show("HEAD"), "src/a.txt\n");
+    assert_eq!(show("HEAD~1"), "src/b.txt\n");
+
+    // The new file stays staged and the unstaged edit untouched
+    assert_eq!(repo.staged_paths(), vec!["src/c.txt"]);
+    assert!(repo.git(&["diff", "--name-only"]).contains("src/b.txt"));
+    // The fixups were built beside the index, which keeps intent-to-add
+    assert_eq!(entries(), before);
+    assert_eq!(
+        repo.git(&["status", "--porcelain", "src/d.txt"]),
+        " A src/d.txt\n"
+    );
+}
+
+#[test]
+fn test_fixup_blames_a_staged_rename_at_its_old_path() {
+    let repo = TestRepo::new("fixup-rename");
+    let lines: Vec<String> = (1..=20).map(|i| format!("l{}", i)).collect();
+    repo.write("a.txt", &format!("{}\n", lines.join("\n")));
+    repo.commit_all("init");
+    let mut changed = lines.clone();
+    changed[2] = "three".to_string();
+    repo.write("a.txt", &format!("{}\n", changed.join("\n")));
+    repo.commit_all("Spell out three");
+
+    repo.git(&["mv", "a.txt", "b.txt"]);
+    changed[2] = "Three".to_string();
+    repo.write("b.txt", &format!("{}\n", changed.join("\n")));
+    repo.git(&["add", "-A"]);
+
+    let mut app = open_app(&repo, None);
+    press(&mut app, "j F");
+    assert_eq!(app.focus, Focus::Staged);
+    assert_eq!(app.error_message, None);
+    let frame = render(&app);
+    assert!(frame.contains("Fixups: 1 commits for 1 hunks"), "{}", frame);
+    assert!(frame.contains("fixup! Spell out three"), "{}", frame);
+
+    press(&mut app, "enter");
+    assert_eq!(app.error_message, None);
+    assert_eq!(
+        repo.git(&["log", "--format=%s", "-2"]),
+        "fixup! Spell out three\nSpell out three\n"
+    );
+    // The rename goes into the fixup along with its hunk
+    let status = repo.git(&["show", "--format=", "--name-status", "-M", "HEAD"]);
+    assert!(status.starts_with('R'), "{}", status);
+    assert!(status.ends_with("\ta.txt\tb.txt\n"), "{}", status);
+    assert!(repo.staged_paths().is_empty());
+}
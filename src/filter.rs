@@ -0,0 +1,207 @@
+use crate::app::TreeNode;
+
+/// Criteria used to narrow the file trees.
+///
+/// Parsed from the filter bar, where the query is a whitespace separated
+/// list of tokens:
+///   - `s:MA` / `status:M` → status characters (any of them matches)
+///   - `ext:rs`            → file extension (any of them matches)
+///   - `is:both`           → only files with both staged and unstaged changes
+///   - anything else       → path glob (all of them must match)
+///
+/// A glob containing `/` is matched against the full path, a glob ending in
+/// `/` matches everything below that directory, and any other glob is
+/// matched against the file name only.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TreeFilter {
+    pub statuses: Vec<char>,
+    pub extensions: Vec<String>,
+    pub globs: Vec<String>,
+    pub both_only: bool,
+}
+
+impl TreeFilter {
+    pub fn parse(query: &str) -> Self {
+        let mut filter = TreeFilter::default();
+
+        for token in query.split_whitespace() {
+            if let Some(chars) = token
+                .strip_prefix("s:")
+                .or_else(|| token.strip_prefix("status:"))
+            {
+                filter
+                    .statuses
+                    .extend(chars.chars().map(|c| c.to_ascii_uppercase()));
+            } else if let Some(ext) = token.strip_prefix("ext:") {
+                filter
+                    .extensions
+                    .push(ext.trim_start_matches('.').to_string());
+            } else if token == "is:both" {
+                filter.both_only = true;
+            } else {
+                filter.globs.push(token.to_string());
+            }
+        }
+
+        filter
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.statuses.is_empty()
+            || !self.extensions.is_empty()
+            || !self.globs.is_empty()
+            || self.both_only
+    }
+
+    /// Whether a file node passes the filter. `status` is the status
+    /// character shown for the node in its pane.
+    pub fn matches(&self, node: &TreeNode, status: char) -> bool {
+        if !self.statuses.is_empty() {
+            let unmerged = node.is_unmerged() && self.statuses.contains(&'U');
+            if !unmerged && !self.statuses.contains(&status) {
+                return false;
+            }
+        }
+
+        if self.both_only && !(is_change(node.staged) && is_change(node.unstaged)) {
+            return false;
+        }
+
+        let path = node.path.to_string_lossy();
+
+        if !self.extensions.is_empty() {
+            let ext = node
+                .path
+                .extension()
+                .map(|e| e.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if !self.extensions.contains(&ext) {
+                return false;
+            }
+        }
+
+        self.globs.iter().all(|g| path_matches(g, &path))
+    }
+}
+
+fn is_change(status: char) -> bool {
+    status != ' ' && status != '?'
+}
+
+/// Match a filter glob against a repo-relative path (see [`TreeFilter`]).
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    if let Some(dir) = pattern.strip_suffix('/') {
+        return path.starts_with(&format!("{}/", dir)) || glob_match(&format!("{}/**", dir), path);
+    }
+    if pattern.contains('/') {
+        glob_match(pattern, path)
+    } else {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        glob_match(pattern, name)
+    }
+}
+
+/// Minimal glob matcher: `*` matches within a path segment, `**` matches
+/// across segments and `?` matches a single non-separator character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    glob_match_from(&p, &t)
+}
+
+fn glob_match_from(p: &[char], t: &[char]) -> bool {
+    match p.first() {
+        None => t.is_empty(),
+        Some('*') if p.get(1) == Some(&'*') => {
+            // `**/` also matches zero directories
+            let rest = &p[2..];
+            if rest.first() == Some(&'/') && glob_match_from(&rest[1..], t) {
+                return true;
+            }
+            (0..=t.len()).any(|i| glob_match_from(rest, &t[i..]))
+        }
+        Some('*') => {
+            let rest = &p[1..];
+            for i in 0..=t.len() {
+                if glob_match_from(rest, &t[i..]) {
+                    return true;
+                }
+                if t.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => match t.first() {
+            Some(&c) if c != '/' => glob_match_from(&p[1..], &t[1..]),
+            _ => false,
+        },
+        Some(&c) => t.first() == Some(&c) && glob_match_from(&p[1..], &t[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn file(path: &str, staged: char, unstaged: char) -> TreeNode {
+        TreeNode {
+            path: PathBuf::from(path),
+            name: path.rsplit('/').next().unwrap().to_string(),
+            depth: path.matches('/').count(),
+            is_dir: false,
+            is_group: false,
+            expanded: false,
+            staged,
+            unstaged,
+            parent: None,
+            stat: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", "src/main.rs"));
+        assert!(glob_match("src/**/*.rs", "src/main.rs"));
+        assert!(glob_match("src/**/*.rs", "src/git/diff.rs"));
+        assert!(glob_match("src/?pp.rs", "src/app.rs"));
+        assert!(!glob_match("src/*.rs", "src/git/diff.rs"));
+    }
+
+    #[test]
+    fn test_path_matches_forms() {
+        assert!(path_matches("*.rs", "src/git/diff.rs"));
+        assert!(path_matches("src/", "src/git/diff.rs"));
+        assert!(!path_matches("src/", "tests/src.rs"));
+        assert!(path_matches("src/git/*", "src/git/diff.rs"));
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let f = TreeFilter::parse("s:ma ext:.rs src/ is:both");
+        assert_eq!(f.statuses, vec!['M', 'A']);
+        assert_eq!(f.extensions, vec!["rs".to_string()]);
+        assert_eq!(f.globs, vec!["src/".to_string()]);
+        assert!(f.both_only);
+        assert!(!TreeFilter::parse("  ").is_active());
+    }
+
+    #[test]
+    fn test_matches_status_and_glob() {
+        let f = TreeFilter::parse("s:M src/ *.rs");
+        assert!(f.matches(&file("src/app.rs", ' ', 'M'), 'M'));
+        assert!(!f.matches(&file("src/app.rs", ' ', 'A'), 'A'));
+        assert!(!f.matches(&file("README.md", ' ', 'M'), 'M'));
+
+        let both = TreeFilter::parse("is:both");
+        assert!(both.matches(&file("a.rs", 'M', 'M'), 'M'));
+        assert!(!both.matches(&file("a.rs", ' ', 'M'), 'M'));
+        assert!(!both.matches(&file("a.rs", '?', '?'), '?'));
+
+        let unmerged = TreeFilter::parse("s:U");
+        assert!(unmerged.matches(&file("a.rs", 'U', 'U'), 'U'));
+        assert!(unmerged.matches(&file("a.rs", 'A', 'U'), 'A'));
+    }
+}
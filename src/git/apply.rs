@@ -1,36 +1,25 @@
 use anyhow::Result;
 use std::collections::HashSet;
-use std::path::Path;
 
+use super::backend::{ApplyOptions, GitBackend};
 use super::diff::{DiffLine, Hunk};
 
-// ─── File-level operations ─────────────────────────────────────────────────
-
-pub fn stage_file(path: &str, repo_root: &Path) -> Result<()> {
-    super::run_git(&["add", path], repo_root)?;
-    Ok(())
-}
-
-/// Unstage a file from the index (restore --staged)
-pub fn unstage_file(path: &str, repo_root: &Path) -> Result<()> {
-    super::run_git(&["restore", "--staged", path], repo_root)?;
-    Ok(())
-}
-
 // ─── Hunk-level operations ─────────────────────────────────────────────────
 
 #[allow(dead_code)]
-pub fn stage_hunk(file_path: &str, hunk: &Hunk, repo_root: &Path) -> Result<()> {
+pub fn stage_hunk(file_path: &str, hunk: &Hunk, git: &dyn GitBackend) -> Result<()> {
     let patch = build_hunk_patch(file_path, hunk);
-    super::run_git_with_stdin(&["apply", "--cached"], &patch, repo_root)?;
-    Ok(())
+    git.apply(&patch, ApplyOptions::cached())
 }
 
 #[allow(dead_code)]
-pub fn unstage_hunk(file_path: &str, hunk: &Hunk, repo_root: &Path) -> Result<()> {
+pub fn unstage_hunk(file_path: &str, hunk: &Hunk, git: &dyn GitBackend) -> Result<()> {
     let patch = build_hunk_patch(file_path, hunk);
-    super::run_git_with_stdin(&["apply", "--cached", "--reverse"], &patch, repo_root)?;
-    Ok(())
+    let options = ApplyOptions {
+        reverse: true,
+        ..ApplyOptions::cached()
+    };
+    git.apply(&patch, options)
 }
 
 // ─── Line-level operations ─────────────────────────────────────────────────
@@ -41,11 +30,10 @@ pub fn stage_lines(
     file_path: &str,
     hunk: &Hunk,
     selected: &HashSet<usize>,
-    repo_root: &Path,
+    git: &dyn GitBackend,
 ) -> Result<()> {
     let patch = build_partial_patch(file_path, hunk, selected);
-    super::run_git_with_stdin(&["apply", "--cached"], &patch, repo_root)?;
-    Ok(())
+    git.apply(&patch, ApplyOptions::cached())
 }
 
 /// Unstage selected lines within a hunk.
@@ -57,11 +45,10 @@ pub fn unstage_lines(
     file_path: &str,
     hunk: &Hunk,
     selected: &HashSet<usize>,
-    repo_root: &Path,
+    git: &dyn GitBackend,
 ) -> Result<()> {
     let patch = build_reverse_partial_patch(file_path, hunk, selected);
-    super::run_git_with_stdin(&["apply", "--cached"], &patch, repo_root)?;
-    Ok(())
+    git.apply(&patch, ApplyOptions::cached())
 }
 
 // ─── Patch builders ────────────────────────────────────────────────────────
@@ -70,54 +57,65 @@ fn build_hunk_patch(file_path: &str, hunk: &Hunk) -> String {
     let mut patch = String::new();
     patch.push_str(&format!("--- a/{}\n", file_path));
     patch.push_str(&format!("+++ b/{}\n", file_path));
-    patch.push_str(&format!(
+    patch.push_str(&hunk_text(hunk));
+    patch
+}
+
+/// The `@@` line and body of a hunk
+fn hunk_text(hunk: &Hunk) -> String {
+    let mut text = format!(
         "@@ -{},{} +{},{} @@\n",
         hunk.old_start, hunk.old_count, hunk.new_start, hunk.new_count
-    ));
+    );
     for line in &hunk.lines {
         match line {
-            DiffLine::Context(s) => patch.push_str(&format!(" {}\n", s)),
-            DiffLine::Added(s) => patch.push_str(&format!("+{}\n", s)),
-            DiffLine::Removed(s) => patch.push_str(&format!("-{}\n", s)),
+            DiffLine::Context(s) => text.push_str(&format!(" {}\n", s)),
+            DiffLine::Added(s) => text.push_str(&format!("+{}\n", s)),
+            DiffLine::Removed(s) => text.push_str(&format!("-{}\n", s)),
         }
     }
-    patch
+    text
 }
 
 /// Build a partial patch for staging (forward direction).
 ///
+/// Applied with: `git apply --cached`
+fn build_partial_patch(file_path: &str, hunk: &Hunk, selected: &HashSet<usize>) -> String {
+    build_hunk_patch(file_path, &partial_hunk(hunk, selected))
+}
+
+/// `hunk` reduced to the selected lines.
+///
 /// Rules (matching `git add -e` semantics):
 ///   - Selected   `+` lines → kept as `+`
 ///   - Unselected `+` lines → omitted entirely (not staged)
 ///   - Selected   `-` lines → kept as `-`
 ///   - Unselected `-` lines → converted to context ` ` (file unchanged there)
 ///   - Context lines → always kept as ` `
-///
-/// Applied with: `git apply --cached`
-fn build_partial_patch(file_path: &str, hunk: &Hunk, selected: &HashSet<usize>) -> String {
-    let mut body_lines: Vec<String> = Vec::new();
+fn partial_hunk(hunk: &Hunk, selected: &HashSet<usize>) -> Hunk {
+    let mut lines: Vec<DiffLine> = Vec::new();
     let mut old_count: u32 = 0;
     let mut new_count: u32 = 0;
 
     for (i, line) in hunk.lines.iter().enumerate() {
         match line {
             DiffLine::Context(s) => {
-                body_lines.push(format!(" {}", s));
+                lines.push(DiffLine::Context(s.clone()));
                 old_count += 1;
                 new_count += 1;
             }
             DiffLine::Added(s) => {
                 if selected.contains(&i) {
-                    body_lines.push(format!("+{}", s));
+                    lines.push(DiffLine::Added(s.clone()));
                     new_count += 1;
                 }
             }
             DiffLine::Removed(s) => {
                 if selected.contains(&i) {
-                    body_lines.push(format!("-{}", s));
+                    lines.push(DiffLine::Removed(s.clone()));
                     old_count += 1;
                 } else {
-                    body_lines.push(format!(" {}", s));
+                    lines.push(DiffLine::Context(s.clone()));
                     old_count += 1;
                     new_count += 1;
                 }
@@ -125,16 +123,44 @@ fn build_partial_patch(file_path: &str, hunk: &Hunk, selected: &HashSet<usize>)
         }
     }
 
-    let mut patch = String::new();
-    patch.push_str(&format!("--- a/{}\n", file_path));
-    patch.push_str(&format!("+++ b/{}\n", file_path));
-    patch.push_str(&format!(
-        "@@ -{},{} +{},{} @@\n",
-        hunk.old_start, old_count, hunk.new_start, new_count
-    ));
-    for line in &body_lines {
-        patch.push_str(line);
-        patch.push('\n');
+    Hunk {
+        header: String::new(),
+        old_start: hunk.old_start,
+        old_count,
+        new_start: hunk.new_start,
+        new_count,
+        lines,
+    }
+}
+
+/// A standalone patch of one file for sharing: the `diff --git` header of
+/// `raw_diff` followed by the given hunks, each whole or reduced to its
+/// selected lines. Hunks must be in file order; new-side line numbers
+/// account for changes left out. The `index` line is dropped because the
+/// result no longer matches those blobs.
+pub fn export_patch(raw_diff: &str, parts: &[(&Hunk, Option<&HashSet<usize>>)]) -> String {
+    let header_end = raw_diff
+        .find("\n@@")
+        .map(|i| i + 1)
+        .unwrap_or(raw_diff.len());
+    let mut patch: String = raw_diff[..header_end]
+        .lines()
+        .filter(|line| !line.starts_with("index "))
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    let mut offset: i64 = 0;
+    for (hunk, selected) in parts {
+        let mut hunk = match selected {
+            Some(selected) => partial_hunk(hunk, selected),
+            None => (*hunk).clone(),
+        };
+        // An empty range names the line before it, so a pure insertion
+        // starts one line after `old_start`
+        let first_new = i64::from(hunk.old_start) + i64::from(hunk.old_count == 0) + offset;
+        hunk.new_start = (first_new - i64::from(hunk.new_count == 0)).max(0) as u32;
+        offset += i64::from(hunk.new_count) - i64::from(hunk.old_count);
+        patch.push_str(&hunk_text(&hunk));
     }
     patch
 }
@@ -295,4 +321,200 @@ mod tests {
         // new=desired: ctx_before + (+old_line) + (space)new_line + ctx_after = 4 lines
         assert!(patch.contains("@@ -1,3 +1,4 @@"));
     }
+
+    #[test]
+    fn test_stage_then_unstage_lines_restores_index() {
+        use crate::git::backend::memory::MemoryBackend;
+        use crate::git::diff::parse_diff;
+
+        let git = MemoryBackend::with_files(&[("f", "a\nb\nc\n")]);
+        git.write("f", "a\nB\nc\nd\n");
+
+        // Stage only the added "d"
+        let unstaged = parse_diff(&git.diff("f", false).unwrap());
+        let hunk = &unstaged.hunks[0];
+        let d = hunk
+            .lines
+            .iter()
+            .position(|l| matches!(l, DiffLine::Added(s) if s == "d"))
+            .unwrap();
+        stage_lines("f", hunk, &HashSet::from([d]), &git).unwrap();
+        assert_eq!(git.index_content("f").unwrap(), "a\nb\nc\nd\n");
+
+        let staged = parse_diff(&git.diff("f", true).unwrap());
+        let hunk = &staged.hunks[0];
+        let all: HashSet<usize> = (0..hunk.lines.len()).collect();
+        unstage_lines("f", hunk, &all, &git).unwrap();
+        assert_eq!(git.index_content("f").unwrap(), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn test_export_patch_keeps_header_and_shifts_hunks() {
+        use crate::git::diff::parse_diff;
+
+        let raw = concat!(
+            "diff --git a/f b/f\n",
+            "index 1111111..2222222 100644\n",
+            "--- a/f\n",
+            "+++ b/f\n",
+            "@@ -1,2 +1,3 @@\n a\n+new\n b\n",
+            "@@ -10,3 +11,3 @@\n j\n-k\n+K\n l\n",
+        );
+        let file = parse_diff(raw);
+        let (first, second) = (&file.hunks[0], &file.hunks[1]);
+
+        // Without the first hunk's insertion, the second starts at line 10
+        let patch = export_patch(raw, &[(second, None)]);
+        assert_eq!(
+            patch,
+            "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -10,3 +10,3 @@\n j\n-k\n+K\n l\n"
+        );
+
+        // Only "-k" of the second hunk, after the whole first one
+        let patch = export_patch(raw, &[(first, None), (second, Some(&HashSet::from([1])))]);
+        assert!(patch.ends_with("@@ -1,2 +1,3 @@\n a\n+new\n b\n@@ -10,3 +11,2 @@\n j\n-k\n l\n"));
+    }
+
+    // ─── Properties, checked against a real index ──────────────────────
+
+    use crate::git::backend::ProcessBackend;
+    use crate::git::diff::{get_raw_diff, parse_diff};
+    use crate::testing::TestRepo;
+    use proptest::prelude::*;
+
+    /// Lines from a small alphabet, so diffs interleave context and changes
+    fn content() -> impl Strategy<Value = Vec<String>> {
+        let line = prop::sample::select(vec!["a", "b", "c", "d", "x", "y"]).prop_map(String::from);
+        prop::collection::vec(line, 0..24)
+    }
+
+    fn join(lines: &[String]) -> String {
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    /// Per hunk, the changed lines whose bit is set (bits are consumed in
+    /// order across all hunks)
+    fn select(hunks: &[Hunk], bits: &[bool]) -> Vec<HashSet<usize>> {
+        let mut bits = bits.iter().cycle();
+        hunks
+            .iter()
+            .map(|hunk| {
+                hunk.lines
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, line)| !matches!(line, DiffLine::Context(_)))
+                    .filter(|_| *bits.next().unwrap())
+                    .map(|(i, _)| i)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The changed lines of each hunk that are not in `selected`
+    fn complement(hunks: &[Hunk], selected: &[HashSet<usize>]) -> Vec<HashSet<usize>> {
+        let all = select(hunks, &[true]);
+        all.into_iter()
+            .zip(selected)
+            .map(|(all, selected)| all.difference(selected).copied().collect())
+            .collect()
+    }
+
+    /// `base` with only the selected changes of `hunks` applied: selected
+    /// `+` lines inserted, selected `-` lines dropped
+    fn merge(base: &[String], hunks: &[Hunk], selected: &[HashSet<usize>]) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut pos = 0;
+        for (hunk, selected) in hunks.iter().zip(selected) {
+            let start = if hunk.old_count == 0 {
+                hunk.old_start
+            } else {
+                hunk.old_start - 1
+            } as usize;
+            out.extend_from_slice(&base[pos..start]);
+            pos = start;
+            for (i, line) in hunk.lines.iter().enumerate() {
+                match line {
+                    DiffLine::Context(s) => {
+                        out.push(s.clone());
+                        pos += 1;
+                    }
+                    DiffLine::Removed(s) => {
+                        if !selected.contains(&i) {
+                            out.push(s.clone());
+                        }
+                        pos += 1;
+                    }
+                    DiffLine::Added(s) => {
+                        if selected.contains(&i) {
+                            out.push(s.clone());
+                        }
+                    }
+                }
+            }
+        }
+        out.extend_from_slice(&base[pos..]);
+        out
+    }
+
+    /// Apply per-hunk selections bottom-up, as the app does
+    fn apply_selection(
+        git: &ProcessBackend,
+        hunks: &[Hunk],
+        selected: &[HashSet<usize>],
+        staged: bool,
+    ) -> Result<()> {
+        for (hunk, selected) in hunks.iter().zip(selected).rev() {
+            if selected.is_empty() {
+                continue;
+            }
+            if staged {
+                unstage_lines("f", hunk, selected, git)?;
+            } else {
+                stage_lines("f", hunk, selected, git)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn prop_stage_merges_selection_and_unstage_inverts_it() {
+        let repo = TestRepo::new("prop-partial");
+        let git = ProcessBackend::new(&repo.root);
+        let config = ProptestConfig {
+            cases: 48,
+            failure_persistence: None,
+            ..ProptestConfig::default()
+        };
+
+        proptest!(config, |(
+            old in content(),
+            new in content(),
+            stage_bits in prop::collection::vec(any::<bool>(), 1..32),
+            unstage_bits in prop::collection::vec(any::<bool>(), 1..32),
+        )| {
+            repo.write("f", &join(&old));
+            repo.git(&["add", "f"]);
+            repo.git(&["commit", "-q", "--allow-empty", "-m", "case"]);
+            repo.write("f", &join(&new));
+
+            // Stage a random subset of the worktree changes
+            let unstaged = parse_diff(&get_raw_diff("f", false, &repo.root).unwrap()).hunks;
+            let selected = select(&unstaged, &stage_bits);
+            apply_selection(&git, &unstaged, &selected, false).unwrap();
+            prop_assert_eq!(repo.index("f"), join(&merge(&old, &unstaged, &selected)));
+
+            // Unstage a random subset of what is staged now
+            let staged = parse_diff(&get_raw_diff("f", true, &repo.root).unwrap()).hunks;
+            let unselected = select(&staged, &unstage_bits);
+            apply_selection(&git, &staged, &unselected, true).unwrap();
+            let kept = complement(&staged, &unselected);
+            prop_assert_eq!(repo.index("f"), join(&merge(&old, &staged, &kept)));
+
+            // Unstaging everything that is left restores HEAD exactly
+            let staged = parse_diff(&get_raw_diff("f", true, &repo.root).unwrap()).hunks;
+            let all = select(&staged, &[true]);
+            apply_selection(&git, &staged, &all, true).unwrap();
+            prop_assert_eq!(repo.index("f"), join(&old));
+        });
+    }
 }
@@ -0,0 +1,353 @@
+//! Reads status, diffs and their line counts and blob IDs in-process with
+//! gitoxide. Patches are still applied and committed by `git`, as gix does
+//! not implement `apply`, and so are whole-file staging and the file list
+//! of a commit.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::unified::{unified_diff, Side};
+use super::{changed_paths, ApplyOptions, BlobIds, GitBackend, ProcessBackend, NULL_ID};
+use crate::git::diff::DiffTarget;
+use crate::git::status::GitFile;
+
+#[derive(Debug)]
+pub struct GixBackend {
+    repo: gix::ThreadSafeRepository,
+    repo_root: PathBuf,
+    process: ProcessBackend,
+}
+
+/// A blob as read from HEAD, the index or the worktree
+struct Blob {
+    content: Vec<u8>,
+    mode: u32,
+    id: String,
+}
+
+impl Blob {
+    fn side(&self) -> Side<'_> {
+        Side {
+            content: &self.content,
+            mode: self.mode,
+            id: &self.id,
+        }
+    }
+}
+
+impl GixBackend {
+    pub fn open(repo_root: &Path) -> Result<Self> {
+        let repo = gix::open(repo_root)
+            .with_context(|| format!("Failed to open {} with gix", repo_root.display()))?;
+        Ok(Self {
+            repo: repo.into_sync(),
+            repo_root: repo_root.to_path_buf(),
+            process: ProcessBackend::new(repo_root),
+        })
+    }
+
+    fn tree_blob(
+        &self,
+        repo: &gix::Repository,
+        tree: gix::ObjectId,
+        path: &str,
+    ) -> Result<Option<Blob>> {
+        let tree = repo.find_tree(tree)?;
+        let Some(entry) = tree.lookup_entry_by_path(path)? else {
+            return Ok(None);
+        };
+        let mode = entry.mode();
+        if mode.is_tree() {
+            return Ok(None);
+        }
+        let id = entry.object_id();
+        Ok(Some(Blob {
+            content: repo.find_object(id)?.detach().data,
+            mode: u32::from(mode.value()),
+            id: id.to_string(),
+        }))
+    }
+
+    fn head_blob(&self, repo: &gix::Repository, path: &str) -> Result<Option<Blob>> {
+        let tree = repo.head_tree_id_or_empty()?.detach();
+        self.tree_blob(repo, tree, path)
+    }
+
+    fn index_blob(&self, repo: &gix::Repository, path: &str) -> Result<Option<Blob>> {
+        let index = repo.index_or_empty()?;
+        let Some(entry) = index.entry_by_path(path.into()) else {
+            return Ok(None);
+        };
+        Ok(Some(Blob {
+            content: repo.find_object(entry.id)?.detach().data,
+            mode: entry.mode.bits(),
+            id: entry.id.to_string(),
+        }))
+    }
+
+    fn worktree_blob(&self, repo: &gix::Repository, path: &str) -> Result<Option<Blob>> {
+        let full = self.repo_root.join(path);
+        let Ok(meta) = std::fs::symlink_metadata(&full) else {
+            return Ok(None);
+        };
+        let (content, mode) = if meta.file_type().is_symlink() {
+            let target = std::fs::read_link(&full)?;
+            (target.to_string_lossy().into_owned().into_bytes(), 0o120000)
+        } else {
+            (std::fs::read(&full)?, file_mode(&meta))
+        };
+        let id = gix::objs::compute_hash(repo.object_hash(), gix::objs::Kind::Blob, &content)?;
+        Ok(Some(Blob {
+            content,
+            mode,
+            id: id.to_string(),
+        }))
+    }
+}
+
+#[cfg(unix)]
+fn file_mode(meta: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    if meta.permissions().mode() & 0o111 != 0 {
+        0o100755
+    } else {
+        0o100644
+    }
+}
+
+#[cfg(not(unix))]
+fn file_mode(_meta: &std::fs::Metadata) -> u32 {
+    0o100644
+}
+
+fn render(path: &str, old: Option<Blob>, new: Option<Blob>) -> String {
+    unified_diff(
+        path,
+        old.as_ref().map(Blob::side),
+        new.as_ref().map(Blob::side),
+    )
+}
+
+impl GitBackend for GixBackend {
+    fn status(&self) -> Result<Vec<GitFile>> {
+        use gix::status::index_worktree::iter::Summary;
+        use gix::status::Item;
+
+        let repo = self.repo.to_thread_local();
+        let items = repo
+            .status(gix::progress::Discard)?
+            .untracked_files(gix::status::UntrackedFiles::Collapsed)
+            .index_worktree_rewrites(None)
+            .into_iter(None)?;
+
+        // Path → (index column, worktree column), as in `git status --porcelain`
+        let mut columns: BTreeMap<String, (char, char)> = BTreeMap::new();
+        let mut sources: BTreeMap<String, String> = BTreeMap::new();
+        for item in items {
+            match item? {
+                Item::TreeIndex(change) => {
+                    use gix::diff::index::ChangeRef;
+                    let status = match &change {
+                        ChangeRef::Addition { .. } => 'A',
+                        ChangeRef::Deletion { .. } => 'D',
+                        ChangeRef::Modification { .. } => 'M',
+                        ChangeRef::Rewrite { copy: true, .. } => 'C',
+                        ChangeRef::Rewrite { .. } => 'R',
+                    };
+                    let path = change.location().to_string();
+                    if let ChangeRef::Rewrite {
+                        source_location, ..
+                    } = &change
+                    {
+                        sources.insert(path.clone(), source_location.to_string());
+                    }
+                    columns.entry(path).or_insert((' ', ' ')).0 = status;
+                }
+                Item::IndexWorktree(item) => {
+                    let status = match item.summary() {
+                        Some(Summary::Added) => {
+                            let mut path = item.rela_path().to_string();
+                            if let gix::status::index_worktree::Item::DirectoryContents {
+                                entry,
+                                ..
+                            } = &item
+                            {
+                                if entry.disk_kind.is_some_and(|k| k.is_dir()) {
+                                    path.push('/');
+                                }
+                            }
+                            columns.insert(path, ('?', '?'));
+                            continue;
+                        }
+                        Some(Summary::Conflict) => {
+                            columns.insert(item.rela_path().to_string(), ('U', 'U'));
+                            continue;
+                        }
+                        Some(Summary::Removed) => 'D',
+                        Some(Summary::IntentToAdd) => 'A',
+                        Some(_) => 'M',
+                        None => continue,
+                    };
+                    let path = item.rela_path().to_string();
+                    columns.entry(path).or_insert((' ', ' ')).1 = status;
+                }
+            }
+        }
+
+        Ok(columns
+            .into_iter()
+            .map(|(path, (staged, unstaged))| GitFile {
+                orig_path: sources.remove(&path),
+                path,
+                staged,
+                unstaged,
+            })
+            .collect())
+    }
+
+    fn diff(&self, path: &str, staged: bool) -> Result<String> {
+        let repo = self.repo.to_thread_local();
+        if staged {
+            let old = self.head_blob(&repo, path)?;
+            let new = self.index_blob(&repo, path)?;
+            Ok(render(path, old, new))
+        } else {
+            // Untracked files have no worktree diff
+            let Some(old) = self.index_blob(&repo, path)? else {
+                return Ok(String::new());
+            };
+            let new = self.worktree_blob(&repo, path)?;
+            Ok(render(path, Some(old), new))
+        }
+    }
+
+    fn show(&self, revision: &str, path: &str) -> Result<String> {
+        let repo = self.repo.to_thread_local();
+        let commit = repo
+            .rev_parse_single(revision)?
+            .object()?
+            .peel_to_commit()?;
+        let tree = commit.tree_id()?.detach();
+        let old = match commit.parent_ids().next() {
+            Some(parent) => {
+                let parent_tree = parent.object()?.peel_to_commit()?.tree_id()?.detach();
+                self.tree_blob(&repo, parent_tree, path)?
+            }
+            None => None,
+        };
+        let new = self.tree_blob(&repo, tree, path)?;
+        Ok(render(path, old, new))
+    }
+
+    /// Full IDs, unlike the abbreviated ones of the rendered diffs, so
+    /// they match `git hash-object` for viewed marks
+    fn blob_ids(&self, target: &DiffTarget) -> Result<BlobIds> {
+        let DiffTarget::Worktree { staged } = *target else {
+            return self.process.blob_ids(target);
+        };
+        let repo = self.repo.to_thread_local();
+        let id = |blob: Option<Blob>| blob.map_or(NULL_ID.to_string(), |b| b.id);
+        changed_paths(self, target)?
+            .into_iter()
+            .map(|path| {
+                let ids = if staged {
+                    let old = self.head_blob(&repo, &path)?;
+                    (id(old), id(self.index_blob(&repo, &path)?))
+                } else {
+                    (id(self.index_blob(&repo, &path)?), NULL_ID.to_string())
+                };
+                Ok((path, ids))
+            })
+            .collect()
+    }
+
+    fn commit_files(&self, revision: &str) -> Result<Vec<GitFile>> {
+        self.process.commit_files(revision)
+    }
+
+    fn stage_file(&self, path: &str) -> Result<()> {
+        self.process.stage_file(path)
+    }
+
+    fn unstage_file(&self, path: &str) -> Result<()> {
+        self.process.unstage_file(path)
+    }
+
+    fn staged_rename_diff(&self, orig_path: &str, path: &str) -> Result<String> {
+        self.process.staged_rename_diff(orig_path, path)
+    }
+
+    fn apply(&self, patch: &str, options: ApplyOptions) -> Result<()> {
+        self.process.apply(patch, options)
+    }
+
+    fn commit_patch(&self, patch: &str, message: &str) -> Result<String> {
+        self.process.commit_patch(patch, message)
+    }
+
+    fn rev_parse(&self, revision: &str) -> Result<String> {
+        let repo = self.repo.to_thread_local();
+        let id = repo
+            .rev_parse_single(revision)
+            .with_context(|| format!("Unknown revision {}", revision))?;
+        Ok(id.detach().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRepo;
+
+    #[test]
+    fn test_matches_process_backend() {
+        let repo = TestRepo::new("gix");
+        repo.write("a.txt", "1\n2\n3\n4\n5\n6\n7\n8\n9\n");
+        repo.write("b.txt", "old\n");
+        repo.commit_all("init");
+
+        repo.write("a.txt", "1\n2\nthree\n4\n5\n6\n7\n8\n9\nten");
+        repo.write("b.txt", "new\n");
+        repo.git(&["add", "b.txt"]);
+        repo.write("c.txt", "untracked\n");
+
+        let process = ProcessBackend::new(&repo.root);
+        let gix = GixBackend::open(&repo.root).unwrap();
+        let columns = |files: Vec<GitFile>| -> Vec<(String, char, char)> {
+            files
+                .into_iter()
+                .map(|f| (f.path, f.staged, f.unstaged))
+                .collect()
+        };
+        assert_eq!(
+            columns(gix.status().unwrap()),
+            columns(process.status().unwrap())
+        );
+        for (path, staged) in [("a.txt", false), ("b.txt", true), ("c.txt", false)] {
+            assert_eq!(
+                gix.diff(path, staged).unwrap(),
+                process.diff(path, staged).unwrap()
+            );
+        }
+        assert_eq!(
+            gix.rev_parse("HEAD").unwrap(),
+            process.rev_parse("HEAD").unwrap()
+        );
+        assert_eq!(
+            gix.show("HEAD", "a.txt").unwrap(),
+            process.show("HEAD", "a.txt").unwrap()
+        );
+        for staged in [false, true] {
+            let target = DiffTarget::Worktree { staged };
+            assert_eq!(
+                gix.numstat(&target).unwrap(),
+                process.numstat(&target).unwrap()
+            );
+            assert_eq!(
+                gix.blob_ids(&target).unwrap(),
+                process.blob_ids(&target).unwrap()
+            );
+        }
+    }
+}
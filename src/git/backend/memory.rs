@@ -0,0 +1,342 @@
+//! An in-memory repository for tests: HEAD, index and worktree are plain
+//! path → content maps, and patches are applied without touching disk.
+
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+use super::unified::{unified_diff, Side};
+use super::{ApplyOptions, GitBackend};
+use crate::git::diff::{parse_diff, parse_diffs, DiffLine, FileDiff};
+use crate::git::status::GitFile;
+
+type Files = BTreeMap<String, String>;
+
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct State {
+    /// Commit ID → (parent files, files), oldest first
+    commits: Vec<(String, Files, Files)>,
+    index: Files,
+    worktree: Files,
+}
+
+impl MemoryBackend {
+    /// A repository whose HEAD, index and worktree all contain `files`
+    pub fn with_files(files: &[(&str, &str)]) -> Self {
+        let backend = Self::default();
+        for (path, content) in files {
+            backend.write(path, content);
+        }
+        backend.stage_all();
+        backend.commit();
+        backend
+    }
+
+    pub fn write(&self, path: &str, content: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.worktree.insert(path.to_string(), content.to_string());
+    }
+
+    pub fn index_content(&self, path: &str) -> Option<String> {
+        self.state.lock().unwrap().index.get(path).cloned()
+    }
+
+    pub fn stage_all(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.index = state.worktree.clone();
+    }
+
+    /// Record the index as a new commit and return its ID
+    pub fn commit(&self) -> String {
+        let mut state = self.state.lock().unwrap();
+        let id = format!("{:040x}", state.commits.len() + 1);
+        let parent = head(&state).clone();
+        let files = state.index.clone();
+        state.commits.push((id.clone(), parent, files));
+        id
+    }
+}
+
+fn head(state: &State) -> &Files {
+    static EMPTY: Files = BTreeMap::new();
+    state.commits.last().map_or(&EMPTY, |(_, _, files)| files)
+}
+
+fn side<'a>(files: &'a Files, path: &str, id: &'a str) -> Option<Side<'a>> {
+    files.get(path).map(|content| Side {
+        content: content.as_bytes(),
+        mode: 0o100644,
+        id,
+    })
+}
+
+impl GitBackend for MemoryBackend {
+    fn status(&self) -> Result<Vec<GitFile>> {
+        let state = self.state.lock().unwrap();
+        let head = head(&state);
+        let paths: BTreeSet<&String> = head
+            .keys()
+            .chain(state.index.keys())
+            .chain(state.worktree.keys())
+            .collect();
+
+        let column = |old: Option<&String>, new: Option<&String>| match (old, new) {
+            (None, Some(_)) => 'A',
+            (Some(_), None) => 'D',
+            (Some(a), Some(b)) if a != b => 'M',
+            _ => ' ',
+        };
+        Ok(paths
+            .into_iter()
+            .filter_map(|path| {
+                let (h, i, w) = (
+                    head.get(path),
+                    state.index.get(path),
+                    state.worktree.get(path),
+                );
+                let (staged, unstaged) = match (h, i) {
+                    (None, None) => ('?', '?'),
+                    // A path missing from the index has no worktree column
+                    (_, None) => (column(h, i), ' '),
+                    _ => (column(h, i), column(i, w)),
+                };
+                (staged != ' ' || unstaged != ' ').then(|| GitFile {
+                    path: path.clone(),
+                    staged,
+                    unstaged,
+                    orig_path: None,
+                })
+            })
+            .collect())
+    }
+
+    fn diff(&self, path: &str, staged: bool) -> Result<String> {
+        let state = self.state.lock().unwrap();
+        let (old, new) = if staged {
+            (head(&state), &state.index)
+        } else if state.index.contains_key(path) {
+            (&state.index, &state.worktree)
+        } else {
+            // Untracked files have no worktree diff
+            return Ok(String::new());
+        };
+        Ok(unified_diff(
+            path,
+            side(old, path, "1111111"),
+            side(new, path, "2222222"),
+        ))
+    }
+
+    fn show(&self, revision: &str, path: &str) -> Result<String> {
+        let id = self.rev_parse(revision)?;
+        let state = self.state.lock().unwrap();
+        let (_, parent, files) = state
+            .commits
+            .iter()
+            .find(|(commit, _, _)| *commit == id)
+            .context("unknown commit")?;
+        Ok(unified_diff(
+            path,
+            side(parent, path, "1111111"),
+            side(files, path, "2222222"),
+        ))
+    }
+
+    fn commit_files(&self, revision: &str) -> Result<Vec<GitFile>> {
+        let id = self.rev_parse(revision)?;
+        let state = self.state.lock().unwrap();
+        let (_, parent, files) = state
+            .commits
+            .iter()
+            .find(|(commit, _, _)| *commit == id)
+            .context("unknown commit")?;
+        let paths: BTreeSet<&String> = parent.keys().chain(files.keys()).collect();
+        Ok(paths
+            .into_iter()
+            .filter_map(|path| {
+                let status = match (parent.get(path), files.get(path)) {
+                    (None, Some(_)) => 'A',
+                    (Some(_), None) => 'D',
+                    (Some(a), Some(b)) if a != b => 'M',
+                    _ => return None,
+                };
+                Some(GitFile {
+                    path: path.clone(),
+                    staged: status,
+                    unstaged: status,
+                    orig_path: None,
+                })
+            })
+            .collect())
+    }
+
+    fn stage_file(&self, path: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.worktree.get(path).cloned() {
+            Some(content) => state.index.insert(path.to_string(), content),
+            None => state.index.remove(path),
+        };
+        Ok(())
+    }
+
+    fn unstage_file(&self, path: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match head(&state).get(path).cloned() {
+            Some(content) => state.index.insert(path.to_string(), content),
+            None => state.index.remove(path),
+        };
+        Ok(())
+    }
+
+    fn apply(&self, patch: &str, options: ApplyOptions) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let target = if options.cached {
+            &mut state.index
+        } else {
+            &mut state.worktree
+        };
+        apply_to(target, &parse_diff(patch), options)
+    }
+
+    fn rev_parse(&self, revision: &str) -> Result<String> {
+        let state = self.state.lock().unwrap();
+        let revision = revision.trim_end_matches("^{commit}");
+        let found = if revision == "HEAD" {
+            state.commits.last()
+        } else {
+            state
+                .commits
+                .iter()
+                .find(|(id, _, _)| id.starts_with(revision))
+        };
+        found
+            .map(|(id, _, _)| id.clone())
+            .with_context(|| format!("unknown revision {}", revision))
+    }
+
+    fn commit_patch(&self, patch: &str, _message: &str) -> Result<String> {
+        let mut state = self.state.lock().unwrap();
+        let parent = head(&state).clone();
+        let mut files = parent.clone();
+        for file in parse_diffs(patch) {
+            apply_to(&mut files, &file.diff, ApplyOptions::default())?;
+        }
+        let id = format!("{:040x}", state.commits.len() + 1);
+        state.commits.push((id.clone(), parent, files));
+        Ok(id)
+    }
+}
+
+/// Apply one file's diff to `target`, as `git apply` with `options`
+fn apply_to(target: &mut Files, file: &FileDiff, options: ApplyOptions) -> Result<()> {
+    let content = target
+        .get(&file.path)
+        .with_context(|| format!("{}: does not exist", file.path))?
+        .clone();
+
+    let mut lines: Vec<&str> = content.lines().collect();
+    let mut offset: i64 = 0;
+    for hunk in &file.hunks {
+        let (mut old, mut new) = (Vec::new(), Vec::new());
+        for line in &hunk.lines {
+            let (removed, added) = match line {
+                DiffLine::Context(s) => (Some(s), Some(s)),
+                DiffLine::Removed(s) => (Some(s), None),
+                DiffLine::Added(s) => (None, Some(s)),
+            };
+            let (removed, added) = if options.reverse {
+                (added, removed)
+            } else {
+                (removed, added)
+            };
+            old.extend(removed.map(String::as_str));
+            new.extend(added.map(String::as_str));
+        }
+
+        let start = if options.reverse {
+            hunk.new_start
+        } else {
+            hunk.old_start
+        } as i64;
+        let start = if old.is_empty() { start } else { start - 1 } + offset;
+        let start = usize::try_from(start).context("patch does not apply")?;
+        if lines.get(start..start + old.len()) != Some(&old[..]) {
+            bail!("{}: patch does not apply", file.path);
+        }
+        offset += new.len() as i64 - old.len() as i64;
+        lines.splice(start..start + old.len(), new);
+    }
+
+    if !options.check {
+        let content: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        target.insert(file.path.clone(), content);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_columns() {
+        let git = MemoryBackend::with_files(&[("a", "1\n"), ("b", "1\n")]);
+        git.write("a", "2\n");
+        git.write("new", "x\n");
+        git.stage_all();
+        git.write("b", "2\n");
+
+        let status: Vec<(String, char, char)> = git
+            .status()
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.path, f.staged, f.unstaged))
+            .collect();
+        assert_eq!(
+            status,
+            vec![
+                ("a".to_string(), 'M', ' '),
+                ("b".to_string(), ' ', 'M'),
+                ("new".to_string(), 'A', ' '),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_and_reverse() {
+        let git = MemoryBackend::with_files(&[("f", "a\nb\nc\n")]);
+        git.write("f", "a\nB\nc\nd\n");
+        let patch = git.diff("f", false).unwrap();
+
+        git.apply(&patch, ApplyOptions::cached()).unwrap();
+        assert_eq!(git.index_content("f").unwrap(), "a\nB\nc\nd\n");
+        assert!(git.apply(&patch, ApplyOptions::cached()).is_err());
+
+        let reverse = ApplyOptions {
+            reverse: true,
+            ..ApplyOptions::cached()
+        };
+        git.apply(&patch, reverse).unwrap();
+        assert_eq!(git.index_content("f").unwrap(), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn test_stage_and_unstage_files() {
+        let git = MemoryBackend::with_files(&[("a", "1\n")]);
+        git.write("a", "2\n");
+        git.write("new", "x\n");
+        git.stage_file("a").unwrap();
+        git.stage_file("new").unwrap();
+        assert_eq!(git.index_content("a").unwrap(), "2\n");
+
+        git.unstage_file("a").unwrap();
+        git.unstage_file("new").unwrap();
+        assert_eq!(git.index_content("a").unwrap(), "1\n");
+        assert_eq!(git.index_content("new"), None);
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use super::diff::{parse_diff, DiffStat, DiffTarget};
+use super::status::GitFile;
+
+#[cfg(feature = "gix")]
+mod gix;
+#[cfg(test)]
+pub mod memory;
+mod process;
+#[cfg(any(test, feature = "gix"))]
+mod unified;
+
+#[cfg(feature = "gix")]
+pub use self::gix::GixBackend;
+pub use process::ProcessBackend;
+
+/// How `apply` writes a patch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ApplyOptions {
+    /// Apply to the index instead of the worktree
+    pub cached: bool,
+    pub reverse: bool,
+    /// Only check that the patch applies
+    pub check: bool,
+}
+
+impl ApplyOptions {
+    pub fn cached() -> Self {
+        Self {
+            cached: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// A raw diff being loaded; dropping it cancels the load
+pub type DiffFuture = Pin<Box<dyn Future<Output = Result<String>> + Send>>;
+
+/// The blob ID git reports for a missing or unhashed side
+pub(super) const NULL_ID: &str = "0000000000000000000000000000000000000000";
+
+/// Old and new blob IDs per changed file
+pub type BlobIds = HashMap<String, (String, String)>;
+
+/// The repository operations diffview needs.
+///
+/// The default implementation runs `git`; the `gix` feature adds one that
+/// reads status and diffs in-process, and tests use an in-memory repository.
+/// Diffs are git's unified format, so [`super::diff::parse_diff`] and the
+/// patch builders work the same on every backend.
+pub trait GitBackend: Send + Sync + std::fmt::Debug + 'static {
+    /// Porcelain status of the worktree, one entry per changed path
+    fn status(&self) -> Result<Vec<GitFile>>;
+
+    /// Diff of one file: worktree against the index, or the index against
+    /// HEAD when `staged`
+    fn diff(&self, path: &str, staged: bool) -> Result<String>;
+
+    /// Staged diff of `path` as renamed or copied from `orig_path`, with
+    /// the lines numbered as in the source. By default the plain staged
+    /// diff of `path`, as a new file.
+    fn staged_rename_diff(&self, _orig_path: &str, path: &str) -> Result<String> {
+        self.diff(path, true)
+    }
+
+    /// Diff of one file in `revision` against its first parent
+    fn show(&self, revision: &str, path: &str) -> Result<String>;
+
+    /// Files changed by `revision` against its first parent, with the
+    /// change in both status columns
+    fn commit_files(&self, revision: &str) -> Result<Vec<GitFile>>;
+
+    /// Stage a whole file, as `git add`
+    fn stage_file(&self, path: &str) -> Result<()>;
+
+    /// Unstage a whole file, as `git restore --staged`
+    fn unstage_file(&self, path: &str) -> Result<()>;
+
+    fn apply(&self, patch: &str, options: ApplyOptions) -> Result<()>;
+
+    /// Full object ID of a revision expression (`HEAD~2`, `main^{commit}`)
+    fn rev_parse(&self, revision: &str) -> Result<String>;
+
+    /// Commit `patch` on top of HEAD with `message` and move HEAD to the
+    /// new commit, leaving the index and worktree as they are. Returns the
+    /// commit's ID.
+    fn commit_patch(&self, patch: &str, message: &str) -> Result<String>;
+
+    fn diff_target(&self, target: &DiffTarget, path: &str) -> Result<String> {
+        match target {
+            DiffTarget::Worktree { staged } => self.diff(path, *staged),
+            DiffTarget::Commit(rev) => self.show(rev, path),
+        }
+    }
+
+    /// `diff_target` for the background loader. By default it runs on a
+    /// blocking task, whose result is discarded when the load is cancelled.
+    fn diff_target_async(self: Arc<Self>, target: DiffTarget, path: String) -> DiffFuture {
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || self.diff_target(&target, &path)).await?
+        })
+    }
+
+    /// Line counts per changed file of a target; by default counted from
+    /// each file's diff
+    fn numstat(&self, target: &DiffTarget) -> Result<HashMap<String, DiffStat>> {
+        changed_paths(self, target)?
+            .into_iter()
+            .map(|path| {
+                let stat = parse_diff(&self.diff_target(target, &path)?).stat();
+                Ok((path, stat))
+            })
+            .collect()
+    }
+
+    /// Old and new blob IDs per changed file of a target, by default from
+    /// the `index` lines of the diffs, which may abbreviate them. The
+    /// worktree side is all zeros, as it is not hashed.
+    fn blob_ids(&self, target: &DiffTarget) -> Result<BlobIds> {
+        let mut ids = BlobIds::new();
+        for path in changed_paths(self, target)? {
+            let diff = parse_diff(&self.diff_target(target, &path)?);
+            let (Some(old), Some(new)) = (diff.old_id, diff.new_id) else {
+                continue;
+            };
+            let new = match target {
+                DiffTarget::Worktree { staged: false } => NULL_ID.to_string(),
+                _ => new,
+            };
+            ids.insert(path, (old, new));
+        }
+        Ok(ids)
+    }
+}
+
+/// Paths with a diff for `target`; untracked files have none
+pub(super) fn changed_paths<B: GitBackend + ?Sized>(
+    git: &B,
+    target: &DiffTarget,
+) -> Result<Vec<String>> {
+    let files = match target {
+        DiffTarget::Worktree { staged } => git
+            .status()?
+            .into_iter()
+            .filter(|f| {
+                let column = if *staged { f.staged } else { f.unstaged };
+                column != ' ' && !f.is_untracked()
+            })
+            .collect(),
+        DiffTarget::Commit(rev) => git.commit_files(rev)?,
+    };
+    Ok(files.into_iter().map(|f| f.path).collect())
+}
+
+/// Names accepted by `git.backend`
+pub const BACKENDS: &[&str] = &["process", "gix"];
+
+/// Open the backend configured as `git.backend`
+pub fn open(name: &str, repo_root: &Path) -> Result<Arc<dyn GitBackend>> {
+    match name {
+        "process" => Ok(Arc::new(ProcessBackend::new(repo_root))),
+        #[cfg(feature = "gix")]
+        "gix" => Ok(Arc::new(GixBackend::open(repo_root)?)),
+        #[cfg(not(feature = "gix"))]
+        "gix" => Err(anyhow::anyhow!(
+            "git.backend = \"gix\" needs diffview built with `--features gix`"
+        )),
+        other => Err(anyhow::anyhow!(
+            "unknown git.backend {:?} (expected one of: {})",
+            other,
+            BACKENDS.join(", ")
+        )),
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::git::{run_git, run_git_with_env};
+
+use super::{ApplyOptions, BlobIds, DiffFuture, GitBackend};
+use crate::git::diff::{self, DiffStat, DiffTarget};
+use crate::git::status::{self, GitFile};
+
+/// Runs the `git` executable for every operation
+#[derive(Debug, Clone)]
+pub struct ProcessBackend {
+    repo_root: PathBuf,
+}
+
+impl ProcessBackend {
+    pub fn new(repo_root: &Path) -> Self {
+        Self {
+            repo_root: repo_root.to_path_buf(),
+        }
+    }
+}
+
+impl GitBackend for ProcessBackend {
+    fn status(&self) -> Result<Vec<GitFile>> {
+        status::get_status(&self.repo_root)
+    }
+
+    fn diff(&self, path: &str, staged: bool) -> Result<String> {
+        diff::get_raw_diff(path, staged, &self.repo_root)
+    }
+
+    fn staged_rename_diff(&self, orig_path: &str, path: &str) -> Result<String> {
+        run_git(
+            &["diff", "--cached", "--find-renames", "--", orig_path, path],
+            &self.repo_root,
+        )
+    }
+
+    fn show(&self, revision: &str, path: &str) -> Result<String> {
+        diff::get_raw_commit_diff(revision, path, &self.repo_root)
+    }
+
+    fn commit_files(&self, revision: &str) -> Result<Vec<GitFile>> {
+        status::get_commit_files(revision, &self.repo_root)
+    }
+
+    fn stage_file(&self, path: &str) -> Result<()> {
+        run_git(&["add", path], &self.repo_root)?;
+        Ok(())
+    }
+
+    fn unstage_file(&self, path: &str) -> Result<()> {
+        run_git(&["restore", "--staged", path], &self.repo_root)?;
+        Ok(())
+    }
+
+    /// Awaits `git` itself, which is killed when the load is cancelled
+    fn diff_target_async(self: Arc<Self>, target: DiffTarget, path: String) -> DiffFuture {
+        Box::pin(async move {
+            let args = target.git_args(&path, false);
+            diff::run_async("git", &args, None, &[], &self.repo_root).await
+        })
+    }
+
+    fn numstat(&self, target: &DiffTarget) -> Result<HashMap<String, DiffStat>> {
+        match target {
+            DiffTarget::Worktree { staged } => diff::get_numstat(*staged, &self.repo_root),
+            DiffTarget::Commit(rev) => diff::get_commit_numstat(rev, &self.repo_root),
+        }
+    }
+
+    fn blob_ids(&self, target: &DiffTarget) -> Result<BlobIds> {
+        match target {
+            DiffTarget::Worktree { staged } => diff::get_blob_ids(*staged, &self.repo_root),
+            DiffTarget::Commit(rev) => diff::get_commit_blob_ids(rev, &self.repo_root),
+        }
+    }
+
+    fn apply(&self, patch: &str, options: ApplyOptions) -> Result<()> {
+        let mut args = vec!["apply"];
+        if options.cached {
+            args.push("--cached");
+        }
+        if options.reverse {
+            args.push("--reverse");
+        }
+        if options.check {
+            args.push("--check");
+        }
+        crate::git::run_git_with_stdin(&args, patch, &self.repo_root)?;
+        Ok(())
+    }
+
+    fn rev_parse(&self, revision: &str) -> Result<String> {
+        let output = run_git(&["rev-parse", "--verify", revision], &self.repo_root)?;
+        Ok(output.trim().to_string())
+    }
+
+    /// Builds the tree in a temporary index, so the real one keeps its
+    /// stat cache, intent-to-add and skip-worktree entries, and a failure
+    /// leaves nothing half done
+    fn commit_patch(&self, patch: &str, message: &str) -> Result<String> {
+        let head = self.rev_parse("HEAD")?;
+        let index = run_git(
+            &["rev-parse", "--git-path", "index.diffview-commit"],
+            &self.repo_root,
+        )?;
+        let index = self.repo_root.join(index.trim());
+        let env = [("GIT_INDEX_FILE", index.as_path())];
+        let tree = run_git_with_env(&["read-tree", &head], &env, "", &self.repo_root)
+            .and_then(|_| run_git_with_env(&["apply", "--cached"], &env, patch, &self.repo_root))
+            .and_then(|_| run_git_with_env(&["write-tree"], &env, "", &self.repo_root));
+        let _ = std::fs::remove_file(&index);
+
+        let tree = tree?;
+        let commit = run_git(
+            &["commit-tree", tree.trim(), "-p", &head, "-m", message],
+            &self.repo_root,
+        )?;
+        let commit = commit.trim().to_string();
+        // Only moves HEAD if nothing else moved it meanwhile
+        run_git(
+            &["update-ref", "-m", message, "HEAD", &commit, &head],
+            &self.repo_root,
+        )?;
+        Ok(commit)
+    }
+}
@@ -0,0 +1,174 @@
+//! Git-style unified diffs for backends that compare blobs themselves.
+
+use similar::{Algorithm, ChangeTag, TextDiff};
+
+/// Lines of context around each change, as `git diff` uses by default
+const CONTEXT: usize = 3;
+
+/// Git treats content with a NUL in its first 8000 bytes as binary
+const BINARY_PROBE: usize = 8000;
+
+/// One side of a file diff; `None` sides are missing (added/deleted files)
+#[derive(Debug, Clone, Copy)]
+pub struct Side<'a> {
+    pub content: &'a [u8],
+    /// Octal mode as git prints it, e.g. `0o100644`
+    pub mode: u32,
+    pub id: &'a str,
+}
+
+/// Render the diff of `path` between two versions the way `git diff`
+/// does: `diff --git` header, mode and index lines, then the hunks.
+/// Identical sides produce an empty string.
+pub fn unified_diff(path: &str, old: Option<Side>, new: Option<Side>) -> String {
+    let same_content = match (&old, &new) {
+        (Some(o), Some(n)) => o.content == n.content,
+        (None, None) => true,
+        _ => false,
+    };
+    let same_mode = match (&old, &new) {
+        (Some(o), Some(n)) => o.mode == n.mode,
+        _ => true,
+    };
+    if same_content && same_mode {
+        return String::new();
+    }
+
+    let mut out = format!("diff --git a/{} b/{}\n", path, path);
+    match (&old, &new) {
+        (None, Some(n)) => out.push_str(&format!("new file mode {:o}\n", n.mode)),
+        (Some(o), None) => out.push_str(&format!("deleted file mode {:o}\n", o.mode)),
+        (Some(o), Some(n)) if !same_mode => {
+            out.push_str(&format!("old mode {:o}\nnew mode {:o}\n", o.mode, n.mode));
+        }
+        _ => {}
+    }
+    if same_content {
+        return out;
+    }
+
+    let old_id = old.map(|s| abbrev(s.id)).unwrap_or("0000000");
+    let new_id = new.map(|s| abbrev(s.id)).unwrap_or("0000000");
+    match (&old, &new) {
+        (Some(o), Some(_)) if same_mode => {
+            out.push_str(&format!("index {}..{} {:o}\n", old_id, new_id, o.mode));
+        }
+        _ => out.push_str(&format!("index {}..{}\n", old_id, new_id)),
+    }
+
+    let old_content = old.map(|s| s.content).unwrap_or_default();
+    let new_content = new.map(|s| s.content).unwrap_or_default();
+    let old_name = old.map_or("/dev/null".to_string(), |_| format!("a/{}", path));
+    let new_name = new.map_or("/dev/null".to_string(), |_| format!("b/{}", path));
+
+    if is_binary(old_content) || is_binary(new_content) {
+        out.push_str(&format!(
+            "Binary files {} and {} differ\n",
+            old_name, new_name
+        ));
+        return out;
+    }
+
+    out.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+    let old_text = String::from_utf8_lossy(old_content);
+    let new_text = String::from_utf8_lossy(new_content);
+    let diff = TextDiff::configure()
+        .algorithm(Algorithm::Myers)
+        .diff_lines(&*old_text, &*new_text);
+
+    for group in diff.grouped_ops(CONTEXT) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_start = first.old_range().start;
+        let new_start = first.new_range().start;
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, last.old_range().end - old_start),
+            hunk_range(new_start, last.new_range().end - new_start)
+        ));
+
+        for op in &group {
+            for change in diff.iter_changes(op) {
+                out.push(match change.tag() {
+                    ChangeTag::Equal => ' ',
+                    ChangeTag::Delete => '-',
+                    ChangeTag::Insert => '+',
+                });
+                out.push_str(change.value());
+                if change.missing_newline() {
+                    out.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+    }
+    out
+}
+
+/// `start,count` with git's conventions: a count of one is omitted and an
+/// empty range names the line before it
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+fn abbrev(id: &str) -> &str {
+    &id[..id.len().min(7)]
+}
+
+fn is_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_PROBE)].contains(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn side(content: &str) -> Side<'_> {
+        Side {
+            content: content.as_bytes(),
+            mode: 0o100644,
+            id: "0123456789abcdef",
+        }
+    }
+
+    #[test]
+    fn test_matches_git_output() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nb\nc\nd\ne\nF\ng\nh\ni\nj\nk";
+        let diff = unified_diff("src/x.rs", Some(side(old)), Some(side(new)));
+        assert_eq!(
+            diff,
+            concat!(
+                "diff --git a/src/x.rs b/src/x.rs\n",
+                "index 0123456..0123456 100644\n",
+                "--- a/src/x.rs\n",
+                "+++ b/src/x.rs\n",
+                "@@ -3,8 +3,9 @@\n",
+                " c\n d\n e\n-f\n+F\n g\n h\n i\n j\n",
+                "+k\n\\ No newline at end of file\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_new_and_deleted_files() {
+        let added = unified_diff("n.txt", None, Some(side("one\n")));
+        assert!(added.contains("new file mode 100644\nindex 0000000..0123456\n"));
+        assert!(added.contains("--- /dev/null\n+++ b/n.txt\n@@ -0,0 +1 @@\n+one\n"));
+
+        let deleted = unified_diff("d.txt", Some(side("x\ny\n")), None);
+        assert!(deleted.contains("deleted file mode 100644\n"));
+        assert!(deleted.contains("+++ /dev/null\n@@ -1,2 +0,0 @@\n-x\n-y\n"));
+    }
+
+    #[test]
+    fn test_binary_and_unchanged() {
+        let bin = unified_diff("b.bin", Some(side("a\0")), Some(side("b\0")));
+        assert!(bin.ends_with("Binary files a/b.bin and b/b.bin differ\n"));
+        assert!(unified_diff("same", Some(side("x\n")), Some(side("x\n"))).is_empty());
+    }
+}
@@ -1,17 +1,23 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::path::Path;
 use std::process::{Command, Stdio};
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+use super::backend::GitBackend;
+use super::patch::PatchFile;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "text", rename_all = "snake_case")]
 pub enum DiffLine {
     Context(String),
     Added(String),
     Removed(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Hunk {
-    #[allow(dead_code)]
     pub header: String,
     pub old_start: u32,
     pub old_count: u32,
@@ -20,14 +26,316 @@ pub struct Hunk {
     pub lines: Vec<DiffLine>,
 }
 
-#[derive(Debug, Clone, Default)]
+/// How a file changed, from the extended header of its diff
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FileChange {
+    #[default]
+    Modified,
+    Added,
+    Deleted,
+    /// `similarity` is git's percentage from `similarity index`
+    Renamed {
+        similarity: u8,
+    },
+    Copied {
+        similarity: u8,
+    },
+}
+
+impl FileChange {
+    /// The letter `git status` uses
+    pub fn status(self) -> char {
+        match self {
+            FileChange::Modified => 'M',
+            FileChange::Added => 'A',
+            FileChange::Deleted => 'D',
+            FileChange::Renamed { .. } => 'R',
+            FileChange::Copied { .. } => 'C',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct FileDiff {
-    #[allow(dead_code)]
+    /// The new path; for deletions the old one
     pub path: String,
+    /// The path before the change; differs from `path` for renames and copies
+    pub old_path: String,
+    pub change: FileChange,
+    /// File modes as octal strings (`100644`); the same on both sides
+    /// unless the mode changed
+    pub old_mode: Option<String>,
+    pub new_mode: Option<String>,
+    /// Abbreviated blob IDs from the `index` line
+    pub old_id: Option<String>,
+    pub new_id: Option<String>,
     pub is_binary: bool,
     pub hunks: Vec<Hunk>,
 }
 
+impl FileDiff {
+    /// Old and new mode when the diff changes the mode of a kept file
+    pub fn mode_change(&self) -> Option<(&str, &str)> {
+        match (self.old_mode.as_deref(), self.new_mode.as_deref()) {
+            (Some(old), Some(new)) if old != new => Some((old, new)),
+            _ => None,
+        }
+    }
+
+    /// Added and removed lines, as `git diff --numstat` counts them
+    pub fn stat(&self) -> DiffStat {
+        let mut stat = DiffStat {
+            binary: self.is_binary,
+            ..DiffStat::default()
+        };
+        for line in self.hunks.iter().flat_map(|h| &h.lines) {
+            match line {
+                DiffLine::Added(_) => stat.added += 1,
+                DiffLine::Removed(_) => stat.removed += 1,
+                DiffLine::Context(_) => {}
+            }
+        }
+        stat
+    }
+}
+
+/// Added/removed line counts of one file (from `git diff --numstat`).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiffStat {
+    pub added: u32,
+    pub removed: u32,
+    pub binary: bool,
+}
+
+impl DiffStat {
+    pub fn total(&self) -> u32 {
+        self.added + self.removed
+    }
+
+    pub fn accumulate(&mut self, other: &DiffStat) {
+        self.added += other.added;
+        self.removed += other.removed;
+        self.binary |= other.binary;
+    }
+}
+
+impl Hunk {
+    /// Identifies the hunk by its path and changed lines, so the ID stays
+    /// the same when edits elsewhere in the file shift its line numbers.
+    /// Use [`hunk_ids`] to keep repeated identical changes apart.
+    pub fn id(&self, path: &str) -> String {
+        // FNV-1a: unlike `DefaultHasher`, stable across Rust releases
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        feed(path.as_bytes());
+        for line in &self.lines {
+            match line {
+                DiffLine::Added(s) => {
+                    feed(b"\n+");
+                    feed(s.as_bytes());
+                }
+                DiffLine::Removed(s) => {
+                    feed(b"\n-");
+                    feed(s.as_bytes());
+                }
+                DiffLine::Context(_) => {}
+            }
+        }
+        format!("{:012x}", hash >> 16)
+    }
+}
+
+/// IDs of all hunks of a file; the n-th repeat of an identical change
+/// gets a `-n` suffix.
+pub fn hunk_ids(path: &str, hunks: &[Hunk]) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    hunks
+        .iter()
+        .map(|hunk| {
+            let id = hunk.id(path);
+            let count = seen.entry(id.clone()).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                id
+            } else {
+                format!("{}-{}", id, count)
+            }
+        })
+        .collect()
+}
+
+/// Per-file line counts.
+/// staged=true  → `git diff --cached --numstat` (index vs HEAD)
+/// staged=false → `git diff --numstat` (working tree vs index)
+pub fn get_numstat(staged: bool, repo_root: &Path) -> Result<HashMap<String, DiffStat>> {
+    let args: Vec<&str> = if staged {
+        vec!["diff", "--cached", "--numstat", "-z"]
+    } else {
+        vec!["diff", "--numstat", "-z"]
+    };
+    Ok(parse_numstat(&super::run_git(&args, repo_root)?))
+}
+
+/// Per-file line counts of a specific commit.
+pub fn get_commit_numstat(revision: &str, repo_root: &Path) -> Result<HashMap<String, DiffStat>> {
+    let output = super::run_git(
+        &[
+            "show",
+            "--format=",
+            "--numstat",
+            "-z",
+            "--find-renames",
+            revision,
+        ],
+        repo_root,
+    )?;
+    Ok(parse_numstat(&output))
+}
+
+/// Parse `--numstat -z` output. Renames are reported as
+/// `added\tremoved\t\0old\0new\0` and keyed by the new path.
+pub fn parse_numstat(output: &str) -> HashMap<String, DiffStat> {
+    let mut stats = HashMap::new();
+    let mut records = output.split('\0');
+
+    while let Some(record) = records.next() {
+        let record = record.trim_start_matches('\n');
+        let mut parts = record.splitn(3, '\t');
+        let (added, removed, path) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(a), Some(r), Some(p)) => (a, r, p),
+            _ => continue,
+        };
+
+        let path = if path.is_empty() {
+            // Rename/copy: skip the old path, keep the new one
+            records.next();
+            match records.next() {
+                Some(new) => new,
+                None => break,
+            }
+        } else {
+            path
+        };
+
+        let binary = added == "-" && removed == "-";
+        stats.insert(
+            path.to_string(),
+            DiffStat {
+                added: added.parse().unwrap_or(0),
+                removed: removed.parse().unwrap_or(0),
+                binary,
+            },
+        );
+    }
+
+    stats
+}
+
+/// Old and new blob IDs per changed file.
+/// staged=true  → HEAD and index blobs
+/// staged=false → index blob and all zeros (the worktree side is not hashed)
+pub fn get_blob_ids(staged: bool, repo_root: &Path) -> Result<HashMap<String, (String, String)>> {
+    let args: Vec<&str> = if staged {
+        vec!["diff", "--cached", "--raw", "-z", "--no-abbrev"]
+    } else {
+        vec!["diff", "--raw", "-z", "--no-abbrev"]
+    };
+    Ok(parse_raw_blob_ids(&super::run_git(&args, repo_root)?))
+}
+
+/// Old and new blob IDs per file changed by a commit
+pub fn get_commit_blob_ids(
+    revision: &str,
+    repo_root: &Path,
+) -> Result<HashMap<String, (String, String)>> {
+    let output = super::run_git(
+        &[
+            "show",
+            "--format=",
+            "--raw",
+            "-z",
+            "--no-abbrev",
+            "--find-renames",
+            revision,
+        ],
+        repo_root,
+    )?;
+    Ok(parse_raw_blob_ids(&output))
+}
+
+/// Blob IDs git would store for these worktree files, by path
+pub fn hash_worktree_files(paths: &[String], repo_root: &Path) -> Result<HashMap<String, String>> {
+    if paths.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mut args = vec!["hash-object", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    let output = super::run_git(&args, repo_root)?;
+    Ok(paths
+        .iter()
+        .cloned()
+        .zip(output.lines().map(String::from))
+        .collect())
+}
+
+/// Parse `--raw -z` output: `:oldmode newmode oldsha newsha status\0path\0`,
+/// with a second path for renames and copies (keyed by the new path).
+pub fn parse_raw_blob_ids(output: &str) -> HashMap<String, (String, String)> {
+    let mut ids = HashMap::new();
+    let mut records = output.split('\0');
+
+    while let Some(meta) = records.next() {
+        let fields: Vec<&str> = meta.trim_start_matches('\n').split(' ').collect();
+        let (old, new, status) = match fields.as_slice() {
+            [_, _, old, new, status] => (*old, *new, *status),
+            _ => continue,
+        };
+        let Some(mut path) = records.next() else {
+            break;
+        };
+        if status.starts_with('R') || status.starts_with('C') {
+            match records.next() {
+                Some(new_path) => path = new_path,
+                None => break,
+            }
+        }
+        ids.insert(path.to_string(), (old.to_string(), new.to_string()));
+    }
+
+    ids
+}
+
+/// Line count of an untracked file, reported as all-added.
+pub fn untracked_stat(path: &str, repo_root: &Path) -> Option<DiffStat> {
+    const MAX_BYTES: u64 = 4 * 1024 * 1024;
+
+    let full = repo_root.join(path);
+    if std::fs::metadata(&full).ok()?.len() > MAX_BYTES {
+        return None;
+    }
+    let bytes = std::fs::read(&full).ok()?;
+    if bytes.contains(&0) {
+        return Some(DiffStat {
+            binary: true,
+            ..DiffStat::default()
+        });
+    }
+    let mut lines = bytes.iter().filter(|&&b| b == b'\n').count() as u32;
+    if bytes.last().is_some_and(|&b| b != b'\n') {
+        lines += 1;
+    }
+    Some(DiffStat {
+        added: lines,
+        ..DiffStat::default()
+    })
+}
+
 /// Raw git diff output (used for operations).
 /// staged=true  → `git diff --cached -- <path>` (index vs HEAD)
 /// staged=false → `git diff -- <path>` (working tree vs index)
@@ -48,149 +356,343 @@ pub fn get_raw_commit_diff(revision: &str, path: &str, repo_root: &Path) -> Resu
     )
 }
 
-/// Display diff (may be colored by delta/difftastic)
+/// A commit as a mail-style patch (`git format-patch -1 --stdout`), with
+/// the file name `format-patch` would give it
+pub fn get_format_patch(revision: &str, repo_root: &Path) -> Result<(String, String)> {
+    let subject = super::run_git(&["log", "-1", "--format=%f", revision], repo_root)?;
+    let patch = super::run_git(&["format-patch", "-1", "--stdout", revision], repo_root)?;
+    Ok((format!("0001-{}.patch", subject.trim()), patch))
+}
+
+/// Display diff of one file from its raw diff (may be colored by
+/// delta/difftastic). Falls back to the raw diff when the tool fails.
 pub fn get_display_diff(
+    target: &DiffTarget,
     path: &str,
-    staged: bool,
+    raw: &str,
     tool: &str,
     pane_width: u16,
     repo_root: &Path,
-) -> Result<String> {
-    match tool {
-        "delta" => get_delta_diff(path, staged, pane_width, repo_root),
-        "difftastic" => get_difftastic_diff(path, staged, repo_root),
-        _ => get_raw_diff(path, staged, repo_root),
+) -> String {
+    match DisplayCommand::new(tool, target, path, pane_width) {
+        Some(command) => command
+            .run(raw, repo_root)
+            .unwrap_or_else(|_| raw.to_string()),
+        None => raw.to_string(),
     }
 }
 
-/// Display diff for a specific commit and path (may be colored by delta/difftastic).
-pub fn get_display_commit_diff(
-    revision: &str,
-    path: &str,
-    tool: &str,
-    pane_width: u16,
-    repo_root: &Path,
-) -> Result<String> {
-    match tool {
-        "delta" => get_delta_commit_diff(revision, path, pane_width, repo_root),
-        "difftastic" => get_difftastic_commit_diff(revision, path, repo_root),
-        _ => get_raw_commit_diff(revision, path, repo_root),
-    }
+/// How a display tool renders a file diff: delta reads the raw diff on
+/// stdin, difftastic runs as git's external diff
+struct DisplayCommand {
+    program: &'static str,
+    args: Vec<String>,
+    envs: Vec<(&'static str, String)>,
+    pipe_raw: bool,
 }
 
-fn get_delta_diff(path: &str, staged: bool, pane_width: u16, repo_root: &Path) -> Result<String> {
-    let diff_args: Vec<&str> = if staged {
-        vec!["diff", "--cached", "--", path]
-    } else {
-        vec!["diff", "--", path]
-    };
+impl DisplayCommand {
+    fn new(tool: &str, target: &DiffTarget, path: &str, pane_width: u16) -> Option<Self> {
+        match tool {
+            "delta" => {
+                let width = pane_width.to_string();
+                Some(Self {
+                    program: "delta",
+                    args: vec![
+                        "--width".into(),
+                        width.clone(),
+                        "--paging".into(),
+                        "never".into(),
+                    ],
+                    envs: vec![("COLUMNS", width)],
+                    pipe_raw: true,
+                })
+            }
+            "difftastic" => Some(Self {
+                program: "git",
+                args: target.git_args(path, true),
+                envs: vec![("GIT_EXTERNAL_DIFF", "difft".into())],
+                pipe_raw: false,
+            }),
+            _ => None,
+        }
+    }
 
-    let width_str = pane_width.to_string();
+    fn stdin(&self, raw: &str) -> Option<String> {
+        self.pipe_raw.then(|| raw.to_string())
+    }
 
-    let git_proc = Command::new("git")
-        .args(&diff_args)
-        .current_dir(repo_root)
-        .stdout(Stdio::piped())
-        .spawn()?;
+    fn run(&self, raw: &str, cwd: &Path) -> Result<String> {
+        use std::io::Write;
+
+        let mut child = Command::new(self.program)
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(k, v)| (k, v)))
+            .current_dir(cwd)
+            .stdin(if self.pipe_raw {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to spawn: {}", self.program))?;
+
+        // Feed stdin from a thread so a full stdout pipe cannot deadlock us
+        let feeder = match (self.stdin(raw), child.stdin.take()) {
+            (Some(data), Some(mut pipe)) => Some(std::thread::spawn(move || {
+                let _ = pipe.write_all(data.as_bytes());
+            })),
+            _ => None,
+        };
+        let output = child.wait_with_output()?;
+        if let Some(feeder) = feeder {
+            let _ = feeder.join();
+        }
+        command_output(self.program, &self.args, output)
+    }
 
-    let output = Command::new("delta")
-        .args(["--width", &width_str, "--paging", "never"])
-        .env("COLUMNS", &width_str)
-        .stdin(git_proc.stdout.unwrap())
-        .output()?;
+    async fn run_async(&self, raw: &str, cwd: &Path) -> Result<String> {
+        let envs: Vec<(&str, &str)> = self.envs.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        run_async(
+            self.program,
+            &self.args,
+            self.stdin(raw).as_deref(),
+            &envs,
+            cwd,
+        )
+        .await
+    }
+}
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+/// What a file diff compares: worktree vs index, index vs HEAD, or a commit
+/// against its parent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DiffTarget {
+    Worktree { staged: bool },
+    Commit(String),
 }
 
-fn get_delta_commit_diff(
-    revision: &str,
+impl DiffTarget {
+    pub(in crate::git) fn git_args(&self, path: &str, ext_diff: bool) -> Vec<String> {
+        let mut args: Vec<String> = match self {
+            DiffTarget::Worktree { staged: false } => vec!["diff".into()],
+            DiffTarget::Worktree { staged: true } => vec!["diff".into(), "--cached".into()],
+            DiffTarget::Commit(rev) => vec![
+                "show".into(),
+                "--format=".into(),
+                "--patch".into(),
+                rev.clone(),
+            ],
+        };
+        if ext_diff {
+            args.push("--ext-diff".into());
+        }
+        args.push("--".into());
+        args.push(path.to_string());
+        args
+    }
+}
+
+/// Raw and display diff of one file, for loading in the background.
+///
+/// Child processes are killed when the returned future is dropped, so
+/// aborting the task that awaits it cancels the load.
+///
+/// The backend loads the raw diff; see [`GitBackend::diff_target_async`].
+pub async fn load_diff_async(
+    git: Arc<dyn GitBackend>,
+    target: &DiffTarget,
     path: &str,
+    tool: &str,
     pane_width: u16,
     repo_root: &Path,
-) -> Result<String> {
-    let diff_args: Vec<&str> = vec!["show", "--format=", "--patch", revision, "--", path];
-    let width_str = pane_width.to_string();
+) -> Result<(String, String)> {
+    let raw = git
+        .diff_target_async(target.clone(), path.to_string())
+        .await?;
+
+    let display = match DisplayCommand::new(tool, target, path, pane_width) {
+        Some(command) => command
+            .run_async(&raw, repo_root)
+            .await
+            .unwrap_or_else(|_| raw.clone()),
+        None => raw.clone(),
+    };
 
-    let git_proc = Command::new("git")
-        .args(&diff_args)
-        .current_dir(repo_root)
-        .stdout(Stdio::piped())
-        .spawn()?;
+    Ok((raw, display))
+}
 
-    let output = Command::new("delta")
-        .args(["--width", &width_str, "--paging", "never"])
-        .env("COLUMNS", &width_str)
-        .stdin(git_proc.stdout.unwrap())
-        .output()?;
+pub(in crate::git) async fn run_async(
+    program: &str,
+    args: &[String],
+    stdin_data: Option<&str>,
+    envs: &[(&str, &str)],
+    cwd: &Path,
+) -> Result<String> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut child = tokio::process::Command::new(program)
+        .args(args)
+        .envs(envs.iter().copied())
+        .current_dir(cwd)
+        .stdin(if stdin_data.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to spawn: {} {}", program, args.join(" ")))?;
+
+    if let (Some(data), Some(mut pipe)) = (stdin_data, child.stdin.take()) {
+        // Feed stdin concurrently so a full stdout pipe cannot deadlock us
+        let data = data.to_string();
+        tokio::spawn(async move {
+            let _ = pipe.write_all(data.as_bytes()).await;
+        });
+    }
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    let output = child.wait_with_output().await?;
+    command_output(program, args, output)
 }
 
-fn get_difftastic_diff(path: &str, staged: bool, repo_root: &Path) -> Result<String> {
-    let diff_args: Vec<&str> = if staged {
-        vec!["diff", "--cached", "--ext-diff", "--", path]
+fn command_output(program: &str, args: &[String], output: std::process::Output) -> Result<String> {
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
     } else {
-        vec!["diff", "--ext-diff", "--", path]
-    };
-
-    let output = Command::new("git")
-        .args(&diff_args)
-        .env("GIT_EXTERNAL_DIFF", "difft")
-        .current_dir(repo_root)
-        .output()?;
-
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        Err(anyhow::anyhow!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            stderr.trim()
+        ))
+    }
 }
 
-fn get_difftastic_commit_diff(revision: &str, path: &str, repo_root: &Path) -> Result<String> {
-    let output = Command::new("git")
-        .args([
-            "show",
-            "--format=",
-            "--patch",
-            "--ext-diff",
-            revision,
-            "--",
-            path,
-        ])
-        .env("GIT_EXTERNAL_DIFF", "difft")
-        .current_dir(repo_root)
-        .output()?;
-
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+/// Parse a diff of several files, such as `git diff` or `git log -p`
+/// output, into one [`PatchFile`] per file: its parsed diff and its text.
+/// Text between the diffs (commit messages, mail headers) is skipped.
+pub fn parse_diffs(text: &str) -> Vec<PatchFile> {
+    super::patch::split_patch(text)
 }
 
+/// Parse the diff of one file. Hunks of several diffs of the same file (a
+/// path changed by two commits of `git log -p`) are collected in order; the
+/// header fields come from the first.
 pub fn parse_diff(diff_text: &str) -> FileDiff {
-    if diff_text.contains("Binary files") {
-        return FileDiff {
-            path: String::new(),
-            is_binary: true,
-            hunks: vec![],
-        };
-    }
-
+    let mut file = FileDiff::default();
     let mut hunks: Vec<Hunk> = Vec::new();
     let mut current_hunk: Option<Hunk> = None;
-    let mut path = String::new();
+    // Old/new lines left in the current hunk
+    let mut remaining = (0u32, 0u32);
+    let mut diff_git_line: Option<&str> = None;
+    let mut old_name: Option<String> = None;
+    let mut new_name: Option<String> = None;
 
     for line in diff_text.lines() {
-        if line.starts_with("+++ b/") {
-            path = line[6..].to_string();
-        } else if line.starts_with("@@") {
+        if let Some(ref mut hunk) = current_hunk {
+            if remaining != (0, 0) {
+                let (old, new) = remaining;
+                remaining = match line.as_bytes().first() {
+                    Some(b'+') => {
+                        hunk.lines.push(DiffLine::Added(line[1..].to_string()));
+                        (old, new.saturating_sub(1))
+                    }
+                    Some(b'-') => {
+                        hunk.lines.push(DiffLine::Removed(line[1..].to_string()));
+                        (old.saturating_sub(1), new)
+                    }
+                    Some(b'\\') => (old, new),
+                    // Mailers may strip the space of empty context lines
+                    _ => {
+                        let text = line.strip_prefix(' ').unwrap_or(line);
+                        hunk.lines.push(DiffLine::Context(text.to_string()));
+                        (old.saturating_sub(1), new.saturating_sub(1))
+                    }
+                };
+                continue;
+            }
+        }
+
+        if line.starts_with("@@") {
             if let Some(hunk) = current_hunk.take() {
                 hunks.push(hunk);
             }
             if let Some(hunk) = parse_hunk_header(line) {
+                remaining = (hunk.old_count, hunk.new_count);
                 current_hunk = Some(hunk);
             }
-        } else if let Some(ref mut hunk) = current_hunk {
-            if line.starts_with('+') {
-                hunk.lines.push(DiffLine::Added(line[1..].to_string()));
-            } else if line.starts_with('-') {
-                hunk.lines.push(DiffLine::Removed(line[1..].to_string()));
-            } else if line.starts_with(' ') {
-                hunk.lines.push(DiffLine::Context(line[1..].to_string()));
+            continue;
+        }
+        // Headers after the first hunk belong to a later diff of the file
+        if current_hunk.is_some() || !hunks.is_empty() {
+            continue;
+        }
+
+        if line.starts_with("diff --git ") {
+            diff_git_line = Some(line);
+        } else if let Some(mode) = line.strip_prefix("old mode ") {
+            file.old_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            file.new_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("new file mode ") {
+            file.change = FileChange::Added;
+            file.new_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+            file.change = FileChange::Deleted;
+            file.old_mode = Some(mode.to_string());
+        } else if let Some(percent) = line.strip_prefix("similarity index ") {
+            let similarity = percent.trim_end_matches('%').parse().unwrap_or(0);
+            file.change = match file.change {
+                FileChange::Copied { .. } => FileChange::Copied { similarity },
+                _ => FileChange::Renamed { similarity },
+            };
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            old_name = Some(path.to_string());
+        } else if let Some(path) = line.strip_prefix("rename to ") {
+            new_name = Some(path.to_string());
+            file.change = FileChange::Renamed {
+                similarity: similarity(file.change),
+            };
+        } else if let Some(path) = line.strip_prefix("copy from ") {
+            old_name = Some(path.to_string());
+        } else if let Some(path) = line.strip_prefix("copy to ") {
+            new_name = Some(path.to_string());
+            file.change = FileChange::Copied {
+                similarity: similarity(file.change),
+            };
+        } else if let Some(index) = line.strip_prefix("index ") {
+            // `index abc..def 100644`; the mode is left out when it changed
+            let (ids, mode) = index.split_once(' ').unwrap_or((index, ""));
+            if let Some((old, new)) = ids.split_once("..") {
+                file.old_id = Some(old.to_string());
+                file.new_id = Some(new.to_string());
+            }
+            if !mode.is_empty() {
+                file.old_mode.get_or_insert_with(|| mode.to_string());
+                file.new_mode.get_or_insert_with(|| mode.to_string());
+            }
+        } else if let Some(name) = line.strip_prefix("--- ") {
+            match diff_file_name(name) {
+                Some(name) => {
+                    old_name.get_or_insert(name);
+                }
+                None => file.change = FileChange::Added,
             }
+        } else if let Some(name) = line.strip_prefix("+++ ") {
+            match diff_file_name(name) {
+                Some(name) => {
+                    new_name.get_or_insert(name);
+                }
+                None => file.change = FileChange::Deleted,
+            }
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            file.is_binary = true;
         }
     }
 
@@ -198,11 +700,43 @@ pub fn parse_diff(diff_text: &str) -> FileDiff {
         hunks.push(hunk);
     }
 
-    FileDiff {
-        path,
-        is_binary: false,
-        hunks,
+    // `diff --git a/x b/x` names the paths when there is no `---`/`+++`
+    // (binary files, mode-only changes)
+    if let Some((old, new)) = diff_git_line
+        .and_then(|line| line.strip_prefix("diff --git a/"))
+        .and_then(|names| names.split_once(" b/"))
+    {
+        old_name.get_or_insert_with(|| old.to_string());
+        new_name.get_or_insert_with(|| new.to_string());
+    }
+    file.path = new_name
+        .clone()
+        .or_else(|| old_name.clone())
+        .unwrap_or_default();
+    file.old_path = old_name.unwrap_or_else(|| file.path.clone());
+    file.hunks = hunks;
+    file
+}
+
+fn similarity(change: FileChange) -> u8 {
+    match change {
+        FileChange::Renamed { similarity } | FileChange::Copied { similarity } => similarity,
+        _ => 0,
+    }
+}
+
+/// The path in a `---`/`+++` line: without the `a/`/`b/` prefix and the
+/// timestamp `diff -u` appends after a tab; `None` for `/dev/null`
+fn diff_file_name(value: &str) -> Option<String> {
+    let value = value.split('\t').next().unwrap_or(value);
+    if value == "/dev/null" {
+        return None;
     }
+    let name = value
+        .strip_prefix("a/")
+        .or_else(|| value.strip_prefix("b/"))
+        .unwrap_or(value);
+    Some(name.to_string())
 }
 
 fn parse_hunk_header(line: &str) -> Option<Hunk> {
@@ -268,10 +802,130 @@ index abc..def 100644
         assert!(matches!(hunk.lines[4], DiffLine::Context(_)));
     }
 
+    #[test]
+    fn test_parse_raw_blob_ids() {
+        let output = concat!(
+            ":100644 100644 aaaa bbbb R100\0old.rs\0new.rs\0",
+            ":000000 100644 0000 cccc A\0added.rs\0",
+        );
+        let ids = parse_raw_blob_ids(output);
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids["new.rs"], ("aaaa".to_string(), "bbbb".to_string()));
+        assert_eq!(ids["added.rs"], ("0000".to_string(), "cccc".to_string()));
+    }
+
+    #[test]
+    fn test_parse_numstat() {
+        let input = concat!(
+            "3\t1\tsrc/main.rs\0",
+            "-\t-\timg.png\0",
+            "10\t0\t\0old.rs\0new.rs\0"
+        );
+        let stats = parse_numstat(input);
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats["src/main.rs"].added, 3);
+        assert_eq!(stats["src/main.rs"].removed, 1);
+        assert!(stats["img.png"].binary);
+        assert_eq!(stats["new.rs"].added, 10);
+        assert!(!stats.contains_key("old.rs"));
+    }
+
     #[test]
     fn test_binary_detection() {
         let fd = parse_diff("Binary files a/img.png and b/img.png differ\n");
         assert!(fd.is_binary);
         assert!(fd.hunks.is_empty());
     }
+
+    #[test]
+    fn test_parse_diffs_reads_each_file_header() {
+        let text = concat!(
+            "commit 1111111111111111111111111111111111111111\n",
+            "Author: Someone <someone@example.com>\n",
+            "\n",
+            "    Rework\n",
+            "\n",
+            "diff --git a/old.rs b/new.rs\n",
+            "similarity index 92%\n",
+            "rename from old.rs\n",
+            "rename to new.rs\n",
+            "index 1111111..2222222 100644\n",
+            "--- a/old.rs\n",
+            "+++ b/new.rs\n",
+            "@@ -1,2 +1,2 @@\n",
+            " Binary files are not special here\n",
+            "-a\n",
+            "+b\n",
+            "diff --git a/run.sh b/run.sh\n",
+            "old mode 100644\n",
+            "new mode 100755\n",
+            "diff --git a/img.png b/img.png\n",
+            "new file mode 100644\n",
+            "index 0000000..3333333\n",
+            "Binary files /dev/null and b/img.png differ\n",
+            "diff --git a/gone.txt b/gone.txt\n",
+            "deleted file mode 100644\n",
+            "index 4444444..0000000\n",
+            "--- a/gone.txt\n",
+            "+++ /dev/null\n",
+            "@@ -1 +0,0 @@\n",
+            "-bye\n",
+        );
+        let files: Vec<FileDiff> = parse_diffs(text).into_iter().map(|f| f.diff).collect();
+        assert_eq!(files.len(), 4);
+
+        let renamed = &files[0];
+        assert_eq!(
+            (renamed.old_path.as_str(), renamed.path.as_str()),
+            ("old.rs", "new.rs")
+        );
+        assert_eq!(renamed.change, FileChange::Renamed { similarity: 92 });
+        assert_eq!(renamed.old_id.as_deref(), Some("1111111"));
+        assert_eq!(renamed.new_id.as_deref(), Some("2222222"));
+        assert_eq!(renamed.mode_change(), None);
+        assert!(!renamed.is_binary);
+        assert_eq!(renamed.hunks[0].lines.len(), 3);
+
+        assert_eq!(files[1].path, "run.sh");
+        assert_eq!(files[1].change, FileChange::Modified);
+        assert_eq!(files[1].mode_change(), Some(("100644", "100755")));
+
+        assert_eq!(files[2].path, "img.png");
+        assert_eq!(files[2].change, FileChange::Added);
+        assert!(files[2].is_binary);
+
+        assert_eq!(files[3].path, "gone.txt");
+        assert_eq!(files[3].change, FileChange::Deleted);
+        assert_eq!(files[3].old_mode.as_deref(), Some("100644"));
+        assert_eq!(files[3].hunks.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_diff_collects_hunks_of_repeated_diffs() {
+        let text = concat!(
+            "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1 +1 @@\n-b\n+c\n",
+            "diff --git a/f b/f\nnew file mode 100644\n--- /dev/null\n+++ b/f\n@@ -0,0 +1 @@\n+b\n",
+        );
+        let file = parse_diff(text);
+        assert_eq!(file.change, FileChange::Modified);
+        assert_eq!(file.hunks.len(), 2);
+        assert_eq!(file.hunks[1].new_count, 1);
+    }
+
+    #[test]
+    fn test_hunk_ids_survive_line_shifts() {
+        let before = parse_diff("@@ -10,3 +10,3 @@\n a\n-b\n+B\n c\n@@ -40 +40 @@\n-b\n+B\n");
+        let after = parse_diff("@@ -12,3 +12,3 @@\n a\n-b\n+B\n c\n");
+        let ids = hunk_ids("f.rs", &before.hunks);
+        assert_eq!(ids[0], hunk_ids("f.rs", &after.hunks)[0]);
+        assert_eq!(ids[1], format!("{}-2", ids[0]));
+        assert_ne!(ids[0], hunk_ids("g.rs", &after.hunks)[0]);
+        assert_eq!(ids[0].len(), 12);
+    }
+
+    #[test]
+    fn test_diff_line_json() {
+        let line = serde_json::to_value(DiffLine::Added("x".to_string())).unwrap();
+        assert_eq!(line, serde_json::json!({"kind": "added", "text": "x"}));
+    }
 }
@@ -0,0 +1,282 @@
+//! `fixup!` commits for staged hunks, in the manner of git-absorb: each
+//! hunk goes to the most recent commit of the branch that touched the lines
+//! it changes, found by blaming them in HEAD.
+//!
+//! The history is read with `git log` and `git blame` directly rather than
+//! through [`GitBackend`], which covers the index, the worktree and single
+//! commits: gix has no blame, and the in-memory backend no history to walk.
+//! The commits themselves are made through the backend.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::apply::export_patch;
+use super::backend::GitBackend;
+use super::diff::{parse_diff, DiffLine, Hunk};
+
+/// How far back from HEAD a hunk can go, as git-absorb's stack limit
+const MAX_COMMITS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub id: String,
+    pub subject: String,
+}
+
+impl Commit {
+    pub fn short_id(&self) -> &str {
+        &self.id[..self.id.len().min(8)]
+    }
+}
+
+/// A staged hunk and the diff of the file it belongs to
+#[derive(Debug, Clone)]
+pub struct StagedHunk {
+    pub path: String,
+    /// The file's `git diff --cached`, whose header patches are built from
+    pub raw: String,
+    pub hunk: Hunk,
+    /// 1-based index of the hunk in its file, for display
+    pub number: usize,
+}
+
+/// Hunks grouped by the commit they fix up, in branch order (newest first),
+/// and the hunks no commit of the branch touched
+#[derive(Debug, Clone, Default)]
+pub struct FixupPlan {
+    pub fixups: Vec<(Commit, Vec<StagedHunk>)>,
+    pub unassigned: Vec<StagedHunk>,
+}
+
+impl FixupPlan {
+    pub fn hunk_count(&self) -> usize {
+        self.fixups.iter().map(|(_, hunks)| hunks.len()).sum()
+    }
+}
+
+/// The commits a fixup can target: HEAD back to the upstream, the first
+/// merge or `MAX_COMMITS`, newest first
+pub fn branch_commits(repo_root: &Path) -> Result<Vec<Commit>> {
+    let limit = format!("-{}", MAX_COMMITS);
+    let format = "--format=%H%x00%P%x00%s";
+    let log = super::run_git(
+        &["log", &limit, format, "HEAD", "--not", "@{upstream}"],
+        repo_root,
+    )
+    .or_else(|_| super::run_git(&["log", &limit, format, "HEAD"], repo_root))?;
+    Ok(parse_log(&log))
+}
+
+fn parse_log(log: &str) -> Vec<Commit> {
+    log.lines()
+        .map(|line| line.splitn(3, '\0').collect::<Vec<_>>())
+        .take_while(|fields| fields.len() == 3 && fields[1].split(' ').count() <= 1)
+        .map(|fields| Commit {
+            id: fields[0].to_string(),
+            subject: fields[2].to_string(),
+        })
+        .collect()
+}
+
+/// Old-side lines the hunk depends on: its removed lines, or for a pure
+/// addition its context lines
+fn blame_lines(hunk: &Hunk) -> Vec<u32> {
+    let mut removed = Vec::new();
+    let mut context = Vec::new();
+    let mut old = hunk.old_start;
+    for line in &hunk.lines {
+        match line {
+            DiffLine::Removed(_) => {
+                removed.push(old);
+                old += 1;
+            }
+            DiffLine::Context(_) => {
+                context.push(old);
+                old += 1;
+            }
+            DiffLine::Added(_) => {}
+        }
+    }
+    if removed.is_empty() {
+        context
+    } else {
+        removed
+    }
+}
+
+/// `-L` arguments for runs of consecutive line numbers
+fn line_ranges(lines: &[u32]) -> Vec<String> {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &line in lines {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == line => *end = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(from, to)| format!("-L{},{}", from, to))
+        .collect()
+}
+
+/// Commit IDs in `git blame --porcelain` output
+fn parse_blame(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|line| !line.starts_with('\t'))
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let id = fields.next()?;
+            let is_header =
+                id.len() >= 40 && id.bytes().all(|b| b.is_ascii_hexdigit()) && fields.count() >= 2;
+            is_header.then(|| id.to_string())
+        })
+        .collect()
+}
+
+/// Assign every hunk to the newest branch commit that blame names for its lines
+pub fn plan(hunks: Vec<StagedHunk>, repo_root: &Path) -> Result<FixupPlan> {
+    let commits = branch_commits(repo_root)?;
+    let rank: HashMap<&str, usize> = commits
+        .iter()
+        .enumerate()
+        .map(|(idx, commit)| (commit.id.as_str(), idx))
+        .collect();
+
+    let mut assigned: Vec<Vec<StagedHunk>> = vec![Vec::new(); commits.len()];
+    let mut plan = FixupPlan::default();
+    for hunk in hunks {
+        let lines = blame_lines(&hunk.hunk);
+        let target = if lines.is_empty() {
+            None
+        } else {
+            let mut args = vec!["blame".to_string(), "--porcelain".to_string()];
+            args.extend(line_ranges(&lines));
+            // A renamed file's lines are found under its old path
+            let old_path = parse_diff(&hunk.raw).old_path;
+            args.extend(["HEAD".to_string(), "--".to_string(), old_path]);
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            // A file new to the index has no history to blame
+            super::run_git(&args, repo_root).ok().and_then(|output| {
+                parse_blame(&output)
+                    .iter()
+                    .filter_map(|id| rank.get(id.as_str()).copied())
+                    .min()
+            })
+        };
+        match target {
+            Some(idx) => assigned[idx].push(hunk),
+            None => plan.unassigned.push(hunk),
+        }
+    }
+
+    plan.fixups = commits
+        .into_iter()
+        .zip(assigned)
+        .filter(|(_, hunks)| !hunks.is_empty())
+        .collect();
+    Ok(plan)
+}
+
+/// Commit each group of the plan as `fixup! <subject>`. The index is left
+/// alone: the fixed-up hunks are in HEAD afterwards, so they no longer show
+/// as staged, and staged changes outside the plan stay staged. Returns the
+/// number of commits.
+pub fn create_fixups(plan: &FixupPlan, git: &dyn GitBackend) -> Result<usize> {
+    for (commit, hunks) in &plan.fixups {
+        let message = format!("fixup! {}", commit.subject);
+        git.commit_patch(&fixup_patch(hunks), &message)?;
+    }
+    Ok(plan.fixups.len())
+}
+
+/// One patch with the hunks of every file, each file's hunks in file order
+fn fixup_patch(hunks: &[StagedHunk]) -> String {
+    let mut by_file: Vec<(&str, Vec<&StagedHunk>)> = Vec::new();
+    for hunk in hunks {
+        match by_file.iter_mut().find(|(path, _)| *path == hunk.path) {
+            Some((_, file_hunks)) => file_hunks.push(hunk),
+            None => by_file.push((&hunk.path, vec![hunk])),
+        }
+    }
+    by_file
+        .into_iter()
+        .map(|(_, mut file_hunks)| {
+            file_hunks.sort_by_key(|h| h.hunk.old_start);
+            let parts: Vec<_> = file_hunks.iter().map(|h| (&h.hunk, None)).collect();
+            export_patch(&file_hunks[0].raw, &parts)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::backend::memory::MemoryBackend;
+
+    #[test]
+    fn test_parse_log_stops_at_merges() {
+        let log = "aaa\0p1\0Add parser\nbbb\0p2\0Fix typo\nccc\0p3 p4\0Merge main\nddd\0p5\0Old\n";
+        let commits = parse_log(log);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[1].subject, "Fix typo");
+    }
+
+    #[test]
+    fn test_blame_removed_lines_or_else_context() {
+        let raw = "diff --git a/f b/f\n--- a/f\n+++ b/f\n\
+            @@ -3,4 +3,3 @@\n a\n-b\n-c\n+C\n d\n\
+            @@ -20,2 +20,3 @@\n x\n+new\n y\n";
+        let hunks = parse_diff(raw).hunks;
+        assert_eq!(blame_lines(&hunks[0]), vec![4, 5]);
+        assert_eq!(blame_lines(&hunks[1]), vec![20, 21]);
+        assert_eq!(line_ranges(&[4, 5, 9]), vec!["-L4,5", "-L9,9"]);
+    }
+
+    #[test]
+    fn test_parse_blame_porcelain() {
+        let id = "0123456789abcdef0123456789abcdef01234567";
+        let output =
+            format!("{id} 4 4 2\nauthor A\nsummary Add b\nfilename f\n\tb\n{id} 5 5\n\tc\n");
+        assert_eq!(parse_blame(&output), vec![id.to_string(), id.to_string()]);
+    }
+
+    #[test]
+    fn test_fixups_leave_the_index_alone() {
+        let lines: Vec<String> = (1..=12).map(|n| n.to_string()).collect();
+        let git = MemoryBackend::with_files(&[("f", &format!("{}\n", lines.join("\n")))]);
+        let mut changed = lines.clone();
+        changed[0] = "one".to_string();
+        changed[11] = "twelve".to_string();
+        let staged = format!("{}\n", changed.join("\n"));
+        git.write("f", &staged);
+        git.stage_all();
+
+        let raw = git.diff("f", true).unwrap();
+        let hunks = parse_diff(&raw).hunks;
+        assert_eq!(hunks.len(), 2);
+        let commit = Commit {
+            id: git.rev_parse("HEAD").unwrap(),
+            subject: "Add f".to_string(),
+        };
+        let staged_hunk = StagedHunk {
+            path: "f".to_string(),
+            raw,
+            hunk: hunks[0].clone(),
+            number: 1,
+        };
+        let plan = FixupPlan {
+            fixups: vec![(commit.clone(), vec![staged_hunk])],
+            unassigned: Vec::new(),
+        };
+
+        assert_eq!(create_fixups(&plan, &git).unwrap(), 1);
+        assert_ne!(git.rev_parse("HEAD").unwrap(), commit.id);
+        assert_eq!(git.index_content("f").unwrap(), staged);
+        // Only the hunk left out of the fixup is still staged
+        let left = parse_diff(&git.diff("f", true).unwrap()).hunks;
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].new_start, hunks[1].new_start);
+    }
+}
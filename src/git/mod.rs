@@ -1,9 +1,13 @@
 use anyhow::{Context, Result};
+use std::collections::HashSet;
 use std::path::Path;
 use std::process::Command;
 
 pub mod apply;
+pub mod backend;
 pub mod diff;
+pub mod fixup;
+pub mod patch;
 pub mod status;
 
 pub fn run_git(args: &[&str], cwd: &Path) -> Result<String> {
@@ -26,11 +30,22 @@ pub fn run_git(args: &[&str], cwd: &Path) -> Result<String> {
 }
 
 pub fn run_git_with_stdin(args: &[&str], stdin_data: &str, cwd: &Path) -> Result<String> {
+    run_git_with_env(args, &[], stdin_data, cwd)
+}
+
+/// `run_git_with_stdin` with extra environment, such as `GIT_INDEX_FILE`
+pub fn run_git_with_env(
+    args: &[&str],
+    envs: &[(&str, &Path)],
+    stdin_data: &str,
+    cwd: &Path,
+) -> Result<String> {
     use std::io::Write;
     use std::process::Stdio;
 
     let mut child = Command::new("git")
         .args(args)
+        .envs(envs.iter().copied())
         .current_dir(cwd)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
@@ -72,8 +87,106 @@ pub fn get_repo_root() -> Result<std::path::PathBuf> {
     }
 }
 
-pub fn resolve_commit(revision: &str, repo_root: &Path) -> Result<String> {
-    let rev_expr = format!("{}^{{commit}}", revision);
-    let output = run_git(&["rev-parse", "--verify", &rev_expr], repo_root)?;
-    Ok(output.trim().to_string())
+/// `git config` entries (all scopes) named `<prefix>.*`, in git's order
+/// so later entries override earlier ones.
+pub fn get_config_entries(prefix: &str, repo_root: &Path) -> Result<Vec<(String, String)>> {
+    let pattern = format!("^{}\\.", prefix);
+    let output = Command::new("git")
+        .args(["config", "-z", "--get-regexp", &pattern])
+        .current_dir(repo_root)
+        .output()
+        .context("Failed to run git config")?;
+
+    // Exit code 1 just means no entry matched
+    if !output.status.success() {
+        if output.status.code() == Some(1) {
+            return Ok(Vec::new());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(anyhow::anyhow!("git config failed: {}", stderr.trim()));
+    }
+
+    Ok(parse_config_entries(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Parse `git config -z` output: `name\nvalue\0` records. A name without a
+/// value is a boolean set to true.
+fn parse_config_entries(output: &str) -> Vec<(String, String)> {
+    output
+        .split('\0')
+        .filter(|record| !record.is_empty())
+        .map(|record| match record.split_once('\n') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => (record.to_string(), "true".to_string()),
+        })
+        .collect()
+}
+
+/// The subset of `paths` (repo-relative, as given) excluded by `.gitignore`,
+/// `.git/info/exclude` or `core.excludesFile`.
+pub fn check_ignore(paths: &[String], repo_root: &Path) -> Result<HashSet<String>> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new("git")
+        .args(["check-ignore", "-z", "--stdin"])
+        .current_dir(repo_root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to spawn: git check-ignore")?;
+
+    if let Some(mut pipe) = child.stdin.take() {
+        for path in paths {
+            pipe.write_all(path.as_bytes())?;
+            pipe.write_all(b"\0")?;
+        }
+    }
+
+    let output = child.wait_with_output()?;
+
+    // Exit code 1 just means none of the paths is ignored
+    if !output.status.success() && output.status.code() != Some(1) {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(anyhow::anyhow!(
+            "git check-ignore failed: {}",
+            stderr.trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect())
+}
+
+/// Untracked directories excluded by the ignore rules, repo-relative
+/// without the trailing `/` (e.g. `target`, `web/node_modules`).
+pub fn ignored_dirs(repo_root: &Path) -> Result<Vec<String>> {
+    let output = run_git(
+        &[
+            "ls-files",
+            "-z",
+            "--others",
+            "--ignored",
+            "--exclude-standard",
+            "--directory",
+        ],
+        repo_root,
+    )?;
+    Ok(output
+        .split('\0')
+        .filter_map(|entry| entry.strip_suffix('/'))
+        .map(|dir| dir.to_string())
+        .collect())
+}
+
+/// Absolute path of the repository's `.git` directory (per worktree).
+pub fn get_git_dir(repo_root: &Path) -> Result<std::path::PathBuf> {
+    let output = run_git(&["rev-parse", "--absolute-git-dir"], repo_root)?;
+    Ok(std::path::PathBuf::from(output.trim()))
 }
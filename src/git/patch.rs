@@ -0,0 +1,295 @@
+//! Patches from outside the repository: `git diff` output, a mail from
+//! `git format-patch`, or an mbox of several.
+
+use super::diff::{parse_diff, FileDiff};
+
+/// One file's part of a patch
+#[derive(Debug, Clone)]
+pub struct PatchFile {
+    pub path: String,
+    /// `A`, `D`, `R`, `C` or `M`, as in `git status`
+    pub status: char,
+    /// The file's diff, from its `diff --git` (or `---`) line through its
+    /// last hunk
+    pub raw: String,
+    /// `raw`, parsed
+    pub diff: FileDiff,
+    /// Short hash and subject of the commit (`git log -p`) or mail the diff
+    /// came from
+    pub commit: Option<String>,
+}
+
+/// Split a patch into per-file diffs, for [`super::diff::parse_diffs`].
+/// Mail headers, commit messages and signatures between them are skipped;
+/// hunks end where their line counts say, so a `-- ` signature is not
+/// mistaken for a removed line.
+pub(super) fn split_patch(text: &str) -> Vec<PatchFile> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut files: Vec<PatchFile> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut in_header = false;
+    // Inside `GIT binary patch` data, which runs until the next file
+    let mut in_binary = false;
+    // Old/new lines left in the current hunk
+    let mut remaining: Option<(u32, u32)> = None;
+    // Commit of the following diffs, and whether its subject is still to come
+    let mut commit: Option<String> = None;
+    let mut awaiting_subject = false;
+    // Commit of the diff in `current`
+    let mut current_commit: Option<String> = None;
+
+    for (i, &line) in lines.iter().enumerate() {
+        if let Some((old, new)) = remaining {
+            if old > 0 || new > 0 {
+                let (old, new) = match line.as_bytes().first() {
+                    Some(b'-') => (old.saturating_sub(1), new),
+                    Some(b'+') => (old, new.saturating_sub(1)),
+                    Some(b'\\') => (old, new),
+                    // Mailers may strip the space of empty context lines
+                    _ => (old.saturating_sub(1), new.saturating_sub(1)),
+                };
+                current.push(line);
+                remaining = Some((old, new));
+                continue;
+            }
+            remaining = None;
+            if line.starts_with('\\') {
+                current.push(line);
+                continue;
+            }
+        }
+
+        let plain_start = !in_header
+            && line.starts_with("--- ")
+            && lines
+                .get(i + 1)
+                .is_some_and(|next| next.starts_with("+++ "));
+        if line.starts_with("diff --git ") || plain_start {
+            files.extend(finish(&current, &current_commit));
+            current = vec![line];
+            current_commit = commit.clone();
+            in_header = true;
+            in_binary = false;
+        } else if line.starts_with("@@") && !current.is_empty() {
+            current.push(line);
+            in_header = false;
+            remaining = hunk_counts(line);
+        } else if in_header && ((in_binary && line != "-- ") || is_header_line(line)) {
+            in_binary |= line == "GIT binary patch";
+            current.push(line);
+        } else {
+            // Mail text, or a signature after a diff without hunks
+            in_header = false;
+            in_binary = false;
+            if let Some(hash) = commit_hash(line) {
+                commit = Some(hash[..7].to_string());
+                awaiting_subject = true;
+            } else if let Some(subject) = awaiting_subject.then(|| subject(line)).flatten() {
+                commit = commit.map(|hash| format!("{} {}", hash, subject));
+                awaiting_subject = false;
+            }
+        }
+    }
+    files.extend(finish(&current, &current_commit));
+    files
+}
+
+/// Remove terminal escape sequences, such as the colours git adds to
+/// `git diff` output when it runs a pager
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        // CSI: `ESC [`, parameters, then a final byte in `@`..=`~`
+        if chars.clone().next() == Some('[') {
+            chars.next();
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Hash of a `commit <hash>` line of `git log` or a `From <hash> <date>`
+/// mbox separator
+fn commit_hash(line: &str) -> Option<&str> {
+    let rest = line
+        .strip_prefix("commit ")
+        .or_else(|| line.strip_prefix("From "))?;
+    let hash = rest.split(' ').next()?;
+    (hash.len() == 40 && hash.bytes().all(|b| b.is_ascii_hexdigit())).then_some(hash)
+}
+
+/// Subject from the indented first message line of `git log`, or from a
+/// mail's `Subject:` header without its `[PATCH n/m]` tag
+fn subject(line: &str) -> Option<&str> {
+    let subject = match line.strip_prefix("Subject: ") {
+        Some(subject) => match subject.strip_prefix('[') {
+            Some(tagged) => tagged.split_once("] ").map_or(subject, |(_, rest)| rest),
+            None => subject,
+        },
+        None => line.strip_prefix("    ")?,
+    };
+    Some(subject.trim()).filter(|s| !s.is_empty())
+}
+
+/// Extended header lines between `diff --git` and the first hunk
+fn is_header_line(line: &str) -> bool {
+    const PREFIXES: &[&str] = &[
+        "old mode ",
+        "new mode ",
+        "deleted file mode ",
+        "new file mode ",
+        "copy from ",
+        "copy to ",
+        "rename from ",
+        "rename to ",
+        "similarity index ",
+        "dissimilarity index ",
+        "index ",
+        "--- ",
+        "+++ ",
+        "Binary files ",
+        "GIT binary patch",
+    ];
+    PREFIXES.iter().any(|prefix| line.starts_with(prefix))
+}
+
+/// Old and new line counts of a `@@ -a,b +c,d @@` line
+fn hunk_counts(line: &str) -> Option<(u32, u32)> {
+    let mut parts = line.split(' ').skip(1);
+    let count = |range: &str| -> Option<u32> {
+        match range.split_once(',') {
+            Some((_, count)) => count.parse().ok(),
+            None => Some(1),
+        }
+    };
+    let old = count(parts.next()?.strip_prefix('-')?)?;
+    let new = count(parts.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+fn finish(lines: &[&str], commit: &Option<String>) -> Option<PatchFile> {
+    if lines.is_empty() {
+        return None;
+    }
+    let raw: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    let diff = parse_diff(&raw);
+    if diff.path.is_empty() {
+        return None;
+    }
+    Some(PatchFile {
+        path: diff.path.clone(),
+        status: diff.change.status(),
+        raw,
+        diff,
+        commit: commit.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MBOX: &str = concat!(
+        "From 1111111111111111111111111111111111111111 Mon Sep 17 00:00:00 2001\n",
+        "From: Someone <someone@example.com>\n",
+        "Subject: [PATCH 1/2] Change things\n",
+        "\n",
+        "Body text\n",
+        "---\n",
+        " a.txt | 2 +-\n",
+        "\n",
+        "diff --git a/a.txt b/a.txt\n",
+        "index 1111111..2222222 100644\n",
+        "--- a/a.txt\n",
+        "+++ b/a.txt\n",
+        "@@ -1,2 +1,2 @@\n",
+        " one\n",
+        "-two\n",
+        "+TWO\n",
+        "diff --git a/new.txt b/new.txt\n",
+        "new file mode 100644\n",
+        "index 0000000..3333333\n",
+        "--- /dev/null\n",
+        "+++ b/new.txt\n",
+        "@@ -0,0 +1 @@\n",
+        "+fresh\n",
+        "-- \n",
+        "2.43.0\n",
+        "\n",
+        "From 2222222222222222222222222222222222222222 Mon Sep 17 00:00:00 2001\n",
+        "Subject: [PATCH 2/2] Rename\n",
+        "\n",
+        "diff --git a/old.txt b/moved.txt\n",
+        "similarity index 100%\n",
+        "rename from old.txt\n",
+        "rename to moved.txt\n",
+        "-- \n",
+        "2.43.0\n",
+    );
+
+    #[test]
+    fn test_split_mbox() {
+        let files = split_patch(MBOX);
+        let summary: Vec<(&str, char)> =
+            files.iter().map(|f| (f.path.as_str(), f.status)).collect();
+        assert_eq!(
+            summary,
+            vec![("a.txt", 'M'), ("new.txt", 'A'), ("moved.txt", 'R')]
+        );
+        // The signature after the last hunk is not part of the diff
+        assert!(files[1].raw.ends_with("@@ -0,0 +1 @@\n+fresh\n"));
+        assert!(files[2].raw.ends_with("rename to moved.txt\n"));
+        assert_eq!(files[1].commit.as_deref(), Some("1111111 Change things"));
+        assert_eq!(files[2].commit.as_deref(), Some("2222222 Rename"));
+    }
+
+    #[test]
+    fn test_split_log_keeps_commit_subjects() {
+        let text = concat!(
+            "commit 3333333333333333333333333333333333333333 (HEAD -> main)\n",
+            "Author: Someone <someone@example.com>\n",
+            "Date:   Mon Jan 1 00:00:00 2024 +0000\n",
+            "\n",
+            "    Second change\n",
+            "\n",
+            "    Details\n",
+            "\n",
+            "diff --git a/f.txt b/f.txt\n",
+            "--- a/f.txt\n",
+            "+++ b/f.txt\n",
+            "@@ -1 +1 @@\n",
+            "-b\n",
+            "+c\n",
+        );
+        let files = split_patch(text);
+        assert_eq!(files[0].commit.as_deref(), Some("3333333 Second change"));
+        assert_eq!(
+            split_patch("--- f\n+++ f\n@@ -1 +1 @@\n-a\n+b\n")[0].commit,
+            None
+        );
+    }
+
+    #[test]
+    fn test_strip_ansi_colours() {
+        let coloured = "\x1b[1mdiff --git a/f b/f\x1b[m\n\x1b[32m+new\x1b[m\n";
+        assert_eq!(strip_ansi(coloured), "diff --git a/f b/f\n+new\n");
+    }
+
+    #[test]
+    fn test_split_plain_unified_diff() {
+        let text = "--- f.txt\t2024-01-01\n+++ f.txt\t2024-01-02\n@@ -1 +1 @@\n-a\n+b\n";
+        let files = split_patch(text);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "f.txt");
+        assert_eq!(files[0].raw, text);
+    }
+}
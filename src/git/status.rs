@@ -1,15 +1,18 @@
 use anyhow::Result;
+use serde::Serialize;
 use std::path::Path;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct GitFile {
     pub path: String,
     pub staged: char,
     pub unstaged: char,
+    /// The path before a staged (or committed) rename or copy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orig_path: Option<String>,
 }
 
 impl GitFile {
-    #[allow(dead_code)]
     pub fn is_untracked(&self) -> bool {
         self.staged == '?' && self.unstaged == '?'
     }
@@ -38,7 +41,9 @@ impl GitFile {
 }
 
 pub fn get_status(repo_root: &Path) -> Result<Vec<GitFile>> {
-    let output = super::run_git(&["status", "--porcelain"], repo_root)?;
+    // Without optional locks status does not write the index back, which
+    // the watcher would report as a change
+    let output = super::run_git(&["--no-optional-locks", "status", "--porcelain"], repo_root)?;
     Ok(parse_status(&output))
 }
 
@@ -68,21 +73,19 @@ pub fn parse_status(output: &str) -> Vec<GitFile> {
         let unstaged = line.chars().nth(1).unwrap_or(' ');
         let rest = &line[3..];
 
-        // Handle renamed files "old -> new"
-        let path = if rest.contains(" -> ") {
-            rest.split(" -> ").last().unwrap_or(rest).to_string()
-        } else {
-            rest.to_string()
+        // Handle renamed files "old -> new"; git quotes special filenames
+        let unquote = |path: &str| path.trim_matches('"').to_string();
+        let (orig_path, path) = match rest.split_once(" -> ") {
+            Some((old, new)) => (Some(unquote(old)), unquote(new)),
+            None => (None, unquote(rest)),
         };
 
-        // Strip quotes git might add for special filenames
-        let path = path.trim_matches('"').to_string();
-
         if !path.is_empty() {
             files.push(GitFile {
                 path,
                 staged,
                 unstaged,
+                orig_path,
             });
         }
     }
@@ -105,10 +108,10 @@ pub fn parse_commit_name_status(output: &str) -> Vec<GitFile> {
 
         let status_token = parts[0];
         let status = status_token.chars().next().unwrap_or(' ');
-        let path = match status {
+        let (orig_path, path) = match (status, parts.get(2)) {
             // Rename/copy format: R100\told\tnew, C100\told\tnew
-            'R' | 'C' => parts.get(2).copied().unwrap_or(parts[1]),
-            _ => parts[1],
+            ('R' | 'C', Some(new)) => (Some(parts[1].to_string()), *new),
+            _ => (None, parts[1]),
         };
 
         if !path.is_empty() {
@@ -116,6 +119,7 @@ pub fn parse_commit_name_status(output: &str) -> Vec<GitFile> {
                 path: path.to_string(),
                 staged: status,
                 unstaged: status,
+                orig_path,
             });
         }
     }
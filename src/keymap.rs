@@ -0,0 +1,668 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::config::KeySpec;
+
+// ─── Actions ────────────────────────────────────────────────────────────────
+
+/// Where a key press is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyContext {
+    Tree,
+    Diff,
+    Select,
+}
+
+impl KeyContext {
+    fn name(self) -> &'static str {
+        match self {
+            KeyContext::Tree => "tree",
+            KeyContext::Diff => "diff",
+            KeyContext::Select => "select",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Refresh,
+    Help,
+    Down,
+    Up,
+    HalfPageDown,
+    HalfPageUp,
+    Top,
+    Bottom,
+    NextHunk,
+    PrevHunk,
+    Open,
+    Back,
+    ToggleStage,
+    CopyPath,
+    Filter,
+    CycleLayout,
+    LineSelect,
+    RangeSelect,
+    Apply,
+    Cancel,
+    CopyPatch,
+    WritePatch,
+    ApplyToIndex,
+    Search,
+    SearchNext,
+    SearchPrev,
+    NextFile,
+    PrevFile,
+    ToggleContinuous,
+    Annotate,
+    ListNotes,
+    ToggleViewed,
+    ToggleRevert,
+    CherryPick,
+    Fixup,
+}
+
+use KeyContext::{Diff, Select, Tree};
+
+/// Every action with its config name, description and default keys.
+const ACTIONS: &[(Action, &str, &str, &[&str])] = &[
+    (Action::Quit, "quit", "quit", &["q"]),
+    (Action::Refresh, "refresh", "refresh", &["r"]),
+    (Action::Help, "help", "help", &["?"]),
+    (Action::Down, "down", "down", &["j", "down"]),
+    (Action::Up, "up", "up", &["k", "up"]),
+    (
+        Action::HalfPageDown,
+        "half_page_down",
+        "half-page-down",
+        &["ctrl-d"],
+    ),
+    (
+        Action::HalfPageUp,
+        "half_page_up",
+        "half-page-up",
+        &["ctrl-u"],
+    ),
+    (Action::Top, "top", "top", &["g"]),
+    (Action::Bottom, "bottom", "bottom", &["G"]),
+    (Action::NextHunk, "next_hunk", "next-hunk", &["n"]),
+    (Action::PrevHunk, "prev_hunk", "prev-hunk", &["p"]),
+    (Action::Open, "open", "open", &["l", "right"]),
+    (Action::Back, "back", "back", &["h", "left"]),
+    (
+        Action::ToggleStage,
+        "toggle_stage",
+        "stage/unstage",
+        &["enter"],
+    ),
+    (Action::CopyPath, "copy_path", "copy-path", &["c"]),
+    (Action::Filter, "filter", "filter", &["/"]),
+    (Action::CycleLayout, "cycle_layout", "layout", &["t"]),
+    (Action::LineSelect, "line_select", "line-select", &["v"]),
+    (Action::RangeSelect, "range_select", "range", &["V"]),
+    (Action::Apply, "apply", "apply", &["enter"]),
+    (Action::Cancel, "cancel", "cancel", &["esc"]),
+    (Action::CopyPatch, "copy_patch", "copy-patch", &["y"]),
+    (Action::WritePatch, "write_patch", "write-patch", &["w"]),
+    (
+        Action::ApplyToIndex,
+        "apply_to_index",
+        "apply-to-index",
+        &["i"],
+    ),
+    (Action::Search, "search", "search", &["/"]),
+    (Action::SearchNext, "search_next", "next-match", &[";"]),
+    (Action::SearchPrev, "search_prev", "prev-match", &[","]),
+    (Action::NextFile, "next_file", "next-file", &["]"]),
+    (Action::PrevFile, "prev_file", "prev-file", &["["]),
+    (
+        Action::ToggleContinuous,
+        "toggle_continuous",
+        "all-files",
+        &["a"],
+    ),
+    (Action::Annotate, "annotate", "comment", &["c"]),
+    (Action::ListNotes, "notes", "notes", &["C"]),
+    (Action::ToggleViewed, "toggle_viewed", "viewed", &["x"]),
+    (Action::ToggleRevert, "toggle_revert", "revert", &["R"]),
+    (Action::CherryPick, "cherry_pick", "cherry-pick", &["P"]),
+    (Action::Fixup, "fixup", "fixup", &["F"]),
+];
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        Self::entry(self).1
+    }
+
+    fn entry(self) -> &'static (Action, &'static str, &'static str, &'static [&'static str]) {
+        ACTIONS
+            .iter()
+            .find(|(a, ..)| *a == self)
+            .expect("every action has an ACTIONS entry")
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ACTIONS
+            .iter()
+            .find(|(_, n, ..)| *n == name)
+            .map(|(a, ..)| *a)
+    }
+
+    /// Contexts in which the action does something
+    pub fn contexts(self) -> &'static [KeyContext] {
+        match self {
+            Action::Quit | Action::Refresh | Action::Help => &[Tree, Diff, Select],
+            Action::CopyPatch | Action::WritePatch => &[Tree, Diff, Select],
+            Action::Down | Action::Up | Action::Back => &[Tree, Diff, Select],
+            Action::HalfPageDown | Action::HalfPageUp => &[Diff, Select],
+            Action::NextHunk | Action::PrevHunk => &[Diff, Select],
+            Action::Top | Action::Bottom => &[Diff],
+            Action::Open
+            | Action::ToggleStage
+            | Action::CopyPath
+            | Action::Filter
+            | Action::CycleLayout => &[Tree],
+            Action::LineSelect => &[Diff, Select],
+            Action::ApplyToIndex => &[Tree, Diff, Select],
+            Action::Apply => &[Diff, Select],
+            Action::Search | Action::SearchNext | Action::SearchPrev => &[Diff],
+            Action::NextFile | Action::PrevFile => &[Diff, Select],
+            Action::ToggleContinuous => &[Tree, Diff],
+            Action::Annotate => &[Diff, Select],
+            Action::ListNotes => &[Tree, Diff, Select],
+            Action::ToggleViewed => &[Tree, Diff],
+            Action::ToggleRevert | Action::CherryPick => &[Tree, Diff, Select],
+            Action::Fixup => &[Tree, Diff],
+            Action::RangeSelect => &[Select],
+            Action::Cancel => &[Tree, Select],
+        }
+    }
+}
+
+// ─── Key chords ─────────────────────────────────────────────────────────────
+
+/// One key press with its modifiers. Shift is folded into the character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn from_event(event: KeyEvent) -> Self {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        let code = match event.code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            KeyCode::BackTab => {
+                modifiers |= KeyModifiers::SHIFT;
+                KeyCode::Tab
+            }
+            other => other,
+        };
+        Self { code, modifiers }
+    }
+
+    /// Parse `ctrl-d`, `alt-x`, `shift-tab`, `enter`, `G`, `?` ...
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec;
+        loop {
+            let lower = rest.to_ascii_lowercase();
+            if rest.len() > 1 && (lower.starts_with("ctrl-") || lower.starts_with("c-")) {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = &rest[rest.find('-')? + 1..];
+            } else if rest.len() > 1 && (lower.starts_with("alt-") || lower.starts_with("m-")) {
+                modifiers |= KeyModifiers::ALT;
+                rest = &rest[rest.find('-')? + 1..];
+            } else if rest.len() > 1 && lower.starts_with("shift-") {
+                modifiers |= KeyModifiers::SHIFT;
+                rest = &rest[6..];
+            } else {
+                break;
+            }
+        }
+
+        let code = match rest.to_ascii_lowercase().as_str() {
+            "enter" | "return" | "cr" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" | "bs" => KeyCode::Backspace,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "delete" | "del" => KeyCode::Delete,
+            _ => {
+                let mut chars = rest.chars();
+                let c = chars.next()?;
+                if chars.next().is_some() {
+                    return None;
+                }
+                if modifiers.contains(KeyModifiers::SHIFT) && c.is_ascii_alphabetic() {
+                    modifiers -= KeyModifiers::SHIFT;
+                    KeyCode::Char(c.to_ascii_uppercase())
+                } else if modifiers.contains(KeyModifiers::CONTROL) {
+                    KeyCode::Char(c.to_ascii_lowercase())
+                } else {
+                    KeyCode::Char(c)
+                }
+            }
+        };
+
+        // Shift only survives on non-character keys (shift-tab)
+        if matches!(code, KeyCode::Char(_)) {
+            modifiers -= KeyModifiers::SHIFT;
+        }
+        Some(Self { code, modifiers })
+    }
+
+    /// Short label used in the status bar and help
+    pub fn label(&self) -> String {
+        let key = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => "Enter".to_string(),
+            KeyCode::Esc => "Esc".to_string(),
+            KeyCode::Tab => "Tab".to_string(),
+            KeyCode::Backspace => "BS".to_string(),
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::Home => "Home".to_string(),
+            KeyCode::End => "End".to_string(),
+            KeyCode::PageUp => "PgUp".to_string(),
+            KeyCode::PageDown => "PgDn".to_string(),
+            KeyCode::Delete => "Del".to_string(),
+            other => format!("{:?}", other),
+        };
+        let mut prefix = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            prefix.push_str("C-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            prefix.push_str("M-");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            prefix.push_str("S-");
+        }
+        format!("{}{}", prefix, key)
+    }
+}
+
+/// A key sequence such as `g g`.
+pub type KeySequence = Vec<KeyChord>;
+
+fn parse_sequence(spec: &str) -> Option<KeySequence> {
+    let seq: Option<KeySequence> = spec.split_whitespace().map(KeyChord::parse).collect();
+    seq.filter(|s| !s.is_empty())
+}
+
+fn sequence_label(seq: &[KeyChord]) -> String {
+    seq.iter().map(|c| c.label()).collect::<Vec<_>>().join(" ")
+}
+
+// ─── Keymap ─────────────────────────────────────────────────────────────────
+
+pub enum Resolution {
+    Action(Action),
+    /// The keys so far are the prefix of a longer binding
+    Pending,
+    Unbound,
+}
+
+pub struct Keymap {
+    bindings: Vec<(Action, KeySequence)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(&HashMap::new()).0
+    }
+}
+
+impl Keymap {
+    /// Build the keymap from the defaults and the `[keys]` overrides.
+    /// Returns the problems found (unknown actions, bad keys, conflicts).
+    pub fn new(overrides: &HashMap<String, KeySpec>) -> (Self, Vec<String>) {
+        let mut problems = Vec::new();
+        let mut bindings = Vec::new();
+
+        for name in overrides.keys() {
+            if Action::from_name(name).is_none() {
+                problems.push(format!("unknown action '{}'", name));
+            }
+        }
+
+        for (action, name, _, defaults) in ACTIONS {
+            let specs: Vec<String> = match overrides.get(*name) {
+                Some(spec) => spec.keys(),
+                None => defaults.iter().map(|s| s.to_string()).collect(),
+            };
+            for spec in specs {
+                match parse_sequence(&spec) {
+                    Some(seq) => bindings.push((*action, seq)),
+                    None => problems.push(format!("{}: cannot parse key '{}'", name, spec)),
+                }
+            }
+        }
+
+        let keymap = Self { bindings };
+        problems.extend(keymap.conflicts());
+        (keymap, problems)
+    }
+
+    /// Bindings that shadow each other within a context: identical
+    /// sequences, or one sequence being a prefix of another.
+    fn conflicts(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (i, (a, seq_a)) in self.bindings.iter().enumerate() {
+            for (b, seq_b) in self.bindings.iter().skip(i + 1) {
+                if a == b {
+                    continue;
+                }
+                let shared: Vec<&str> = a
+                    .contexts()
+                    .iter()
+                    .filter(|c| b.contexts().contains(c))
+                    .map(|c| c.name())
+                    .collect();
+                if shared.is_empty() {
+                    continue;
+                }
+                let (short, long) = if seq_a.len() <= seq_b.len() {
+                    (seq_a, seq_b)
+                } else {
+                    (seq_b, seq_a)
+                };
+                if long.starts_with(short) {
+                    problems.push(format!(
+                        "'{}' ({}) conflicts with '{}' ({}) in {}",
+                        sequence_label(seq_a),
+                        a.name(),
+                        sequence_label(seq_b),
+                        b.name(),
+                        shared.join("/")
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    pub fn resolve(&self, context: KeyContext, keys: &[KeyChord]) -> Resolution {
+        let mut pending = false;
+        for (action, seq) in &self.bindings {
+            if !action.contexts().contains(&context) {
+                continue;
+            }
+            if seq.as_slice() == keys {
+                return Resolution::Action(*action);
+            }
+            if seq.starts_with(keys) {
+                pending = true;
+            }
+        }
+        if pending {
+            Resolution::Pending
+        } else {
+            Resolution::Unbound
+        }
+    }
+
+    /// Label of the first key bound to an action, e.g. `j`
+    pub fn key(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, seq)| sequence_label(seq))
+            .unwrap_or_else(|| "-".to_string())
+    }
+
+    /// Labels of all keys bound to an action, e.g. `l/→`
+    pub fn keys(&self, action: Action) -> String {
+        let labels: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(a, _)| *a == action)
+            .map(|(_, seq)| sequence_label(seq))
+            .collect();
+        if labels.is_empty() {
+            "-".to_string()
+        } else {
+            labels.join("/")
+        }
+    }
+
+    /// `[key]label` hints for the status bar; several actions sharing a
+    /// label are shown as `[j/k]move`
+    pub fn hints(&self, items: &[(&[Action], &str)]) -> String {
+        items
+            .iter()
+            .map(|(actions, label)| {
+                let keys: Vec<String> = actions.iter().map(|a| self.key(*a)).collect();
+                format!(" [{}]{}", keys.join("/"), label)
+            })
+            .collect()
+    }
+
+    /// `keys:description` list of every action available in a context.
+    /// `describe` relabels an action for the current mode, or hides it
+    /// with `None`.
+    pub fn help(
+        &self,
+        context: KeyContext,
+        describe: impl Fn(Action, &'static str) -> Option<&'static str>,
+    ) -> String {
+        ACTIONS
+            .iter()
+            .filter(|(action, ..)| action.contexts().contains(&context))
+            .filter_map(|(action, _, description, _)| {
+                describe(*action, description)
+                    .map(|description| format!("{}:{}", self.keys(*action), description))
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
+
+/// How long a key sequence waits for its next key, like vim's `timeoutlen`
+pub const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The keys typed so far of a multi-key binding
+#[derive(Debug, Default)]
+pub struct PendingKeys {
+    keys: Vec<KeyChord>,
+    since: Option<Instant>,
+}
+
+impl PendingKeys {
+    /// Add a key pressed at `now` and resolve the sequence. When the keys
+    /// before it lead nowhere, they are dropped and the key is resolved on
+    /// its own, so `z j` with only `z n` bound still moves down.
+    pub fn feed(
+        &mut self,
+        keymap: &Keymap,
+        context: KeyContext,
+        chord: KeyChord,
+        now: Instant,
+    ) -> Resolution {
+        if self.expired(now) {
+            self.clear();
+        }
+        self.keys.push(chord);
+        let mut resolution = keymap.resolve(context, &self.keys);
+        if matches!(resolution, Resolution::Unbound) && self.keys.len() > 1 {
+            self.keys = vec![chord];
+            resolution = keymap.resolve(context, &self.keys);
+        }
+        match resolution {
+            Resolution::Pending => self.since = Some(now),
+            _ => self.clear(),
+        }
+        resolution
+    }
+
+    /// Whether the sequence waited longer than [`SEQUENCE_TIMEOUT`]
+    pub fn expired(&self, now: Instant) -> bool {
+        self.since
+            .is_some_and(|since| now.duration_since(since) >= SEQUENCE_TIMEOUT)
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.since = None;
+    }
+
+    /// The keys typed so far, e.g. `z …`
+    pub fn label(&self) -> String {
+        let typed: Vec<String> = self.keys.iter().map(|k| k.label()).collect();
+        format!("{} …", typed.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(spec: &str) -> KeyChord {
+        KeyChord::parse(spec).unwrap()
+    }
+
+    fn overrides(pairs: &[(&str, &str)]) -> HashMap<String, KeySpec> {
+        pairs
+            .iter()
+            .map(|(a, k)| (a.to_string(), KeySpec::One(k.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_chords() {
+        assert_eq!(
+            chord("ctrl-d"),
+            KeyChord {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::CONTROL
+            }
+        );
+        assert_eq!(chord("G").code, KeyCode::Char('G'));
+        assert_eq!(chord("shift-g"), chord("G"));
+        assert_eq!(chord("enter").code, KeyCode::Enter);
+        assert_eq!(chord("-").code, KeyCode::Char('-'));
+        assert!(KeyChord::parse("nonsense").is_none());
+    }
+
+    #[test]
+    fn test_event_normalisation() {
+        let event = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(KeyChord::from_event(event), chord("G"));
+        let event = KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(KeyChord::from_event(event), chord("ctrl-d"));
+    }
+
+    #[test]
+    fn test_defaults_have_no_conflicts() {
+        let (_, problems) = Keymap::new(&HashMap::new());
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+
+    #[test]
+    fn test_sequences_resolve() {
+        let (keymap, problems) = Keymap::new(&overrides(&[("next_hunk", "z h")]));
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert!(matches!(
+            keymap.resolve(KeyContext::Diff, &[chord("z")]),
+            Resolution::Pending
+        ));
+        assert!(matches!(
+            keymap.resolve(KeyContext::Diff, &[chord("z"), chord("h")]),
+            Resolution::Action(Action::NextHunk)
+        ));
+        assert!(matches!(
+            keymap.resolve(KeyContext::Diff, &[chord("n")]),
+            Resolution::Unbound
+        ));
+    }
+
+    #[test]
+    fn test_pending_keys_restart_and_time_out() {
+        let (keymap, _) = Keymap::new(&overrides(&[("next_hunk", "z h")]));
+        let mut pending = PendingKeys::default();
+        let start = Instant::now();
+        let mut feed = |key: &str, after_ms: u64| {
+            let now = start + Duration::from_millis(after_ms);
+            pending.feed(&keymap, KeyContext::Diff, chord(key), now)
+        };
+        assert!(matches!(feed("z", 0), Resolution::Pending));
+        assert!(matches!(
+            feed("h", 100),
+            Resolution::Action(Action::NextHunk)
+        ));
+
+        // An unbound continuation is read as a key of its own
+        assert!(matches!(feed("z", 200), Resolution::Pending));
+        assert!(matches!(feed("j", 300), Resolution::Action(Action::Down)));
+
+        // The sequence is forgotten after the timeout
+        assert!(matches!(feed("z", 400), Resolution::Pending));
+        assert!(!matches!(
+            feed("h", 1500),
+            Resolution::Action(Action::NextHunk)
+        ));
+    }
+
+    #[test]
+    fn test_conflicts_are_reported() {
+        let (_, problems) = Keymap::new(&overrides(&[("next_hunk", "j")]));
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("next_hunk"));
+
+        // Prefix conflicts: `g` would always win over `g g`
+        let (_, problems) = Keymap::new(&overrides(&[("bottom", "g g")]));
+        assert_eq!(problems.len(), 1);
+
+        // Different contexts do not conflict
+        let (_, problems) = Keymap::new(&overrides(&[("cycle_layout", "n")]));
+        assert!(problems.is_empty());
+
+        let (_, problems) = Keymap::new(&overrides(&[("no_such_action", "x")]));
+        assert_eq!(
+            problems,
+            vec!["unknown action 'no_such_action'".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_hints_follow_bindings() {
+        let (keymap, _) = Keymap::new(&overrides(&[("open", "o")]));
+        assert_eq!(keymap.hints(&[(&[Action::Open], "open")]), " [o]open");
+        assert_eq!(
+            keymap.hints(&[(&[Action::Down, Action::Up], "move")]),
+            " [j/k]move"
+        );
+        assert_eq!(keymap.keys(Action::Down), "j/↓");
+    }
+
+    #[test]
+    fn test_help_relabels_and_hides_actions() {
+        let (keymap, _) = Keymap::new(&HashMap::new());
+        let help = keymap.help(KeyContext::Tree, |action, description| match action {
+            Action::ToggleStage => Some("open"),
+            Action::CherryPick => None,
+            _ => Some(description),
+        });
+        assert!(help.contains(&format!("{}:open", keymap.keys(Action::ToggleStage))));
+        assert!(!help.contains("stage/unstage"));
+        assert!(!help.contains("cherry-pick"));
+        assert!(help.contains("R:revert"));
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+use crate::app::{StatusSnapshot, TreePane};
+use crate::cache::DiffKey;
+use crate::git::backend::GitBackend;
+use crate::git::diff::{load_diff_async, DiffTarget};
+
+/// A file diff to load in the background
+#[derive(Debug, Clone)]
+pub struct DiffRequest {
+    /// Cache key the result is stored under
+    pub key: Option<DiffKey>,
+    pub path: String,
+    pub pane: TreePane,
+    pub target: DiffTarget,
+    pub tool: String,
+    pub width: u16,
+    pub repo_root: PathBuf,
+    pub git: Arc<dyn GitBackend>,
+    /// Reload of the shown file: keep scroll and cursor instead of starting at the top
+    pub keep_position: bool,
+}
+
+/// A finished load, delivered by [`Loader::poll`]
+pub enum Loaded {
+    Diff {
+        request: DiffRequest,
+        result: Result<(String, String)>,
+    },
+    /// The diffs of the continuous view, in request order
+    Diffs {
+        results: Vec<(DiffRequest, Result<(String, String)>)>,
+        /// File to scroll to once they are shown
+        scroll_to: Option<String>,
+    },
+    Status(Result<StatusSnapshot>),
+}
+
+/// Runs diff and status loads on the tokio runtime and hands the results
+/// back over a channel, so slow `git`/`delta` calls never block the UI.
+///
+/// Only the newest request of each kind is live: starting a diff load
+/// aborts the previous one (killing its child processes), and results of
+/// superseded requests are dropped.
+///
+/// Without a runtime (unit tests) nothing is spawned and callers load
+/// synchronously.
+pub struct Loader {
+    runtime: Option<Handle>,
+    tx: Sender<(u64, Loaded)>,
+    rx: Receiver<(u64, Loaded)>,
+    next_id: u64,
+    diff_task: Option<Task>,
+    status_task: Option<Task>,
+}
+
+struct Task {
+    id: u64,
+    handle: JoinHandle<()>,
+    started: Instant,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            runtime: Handle::try_current().ok(),
+            tx,
+            rx,
+            next_id: 0,
+            diff_task: None,
+            status_task: None,
+        }
+    }
+
+    /// Start loading a diff, cancelling any diff still in flight.
+    /// Returns false when there is no runtime; the caller loads synchronously.
+    pub fn load_diff(&mut self, request: DiffRequest) -> bool {
+        self.cancel_diff();
+        let Some(runtime) = self.runtime.clone() else {
+            return false;
+        };
+
+        let id = self.next_id();
+        let tx = self.tx.clone();
+        let handle = runtime.spawn(async move {
+            let result = load(&request).await;
+            let _ = tx.send((id, Loaded::Diff { request, result }));
+        });
+        self.diff_task = Some(Task {
+            id,
+            handle,
+            started: Instant::now(),
+        });
+        true
+    }
+
+    /// Start loading several diffs one after another, for the continuous
+    /// view. They share the diff slot: starting this cancels a single diff
+    /// load and the other way round. Returns false when there is no runtime.
+    pub fn load_diffs(&mut self, requests: Vec<DiffRequest>, scroll_to: Option<String>) -> bool {
+        self.cancel_diff();
+        let Some(runtime) = self.runtime.clone() else {
+            return false;
+        };
+
+        let id = self.next_id();
+        let tx = self.tx.clone();
+        let handle = runtime.spawn(async move {
+            let mut results = Vec::with_capacity(requests.len());
+            for request in requests {
+                let result = load(&request).await;
+                results.push((request, result));
+            }
+            let _ = tx.send((id, Loaded::Diffs { results, scroll_to }));
+        });
+        self.diff_task = Some(Task {
+            id,
+            handle,
+            started: Instant::now(),
+        });
+        true
+    }
+
+    /// Run `load` (blocking git calls) on the runtime's blocking pool.
+    /// Returns false when there is no runtime.
+    pub fn load_status<F>(&mut self, load: F) -> bool
+    where
+        F: FnOnce() -> Result<StatusSnapshot> + Send + 'static,
+    {
+        let Some(runtime) = self.runtime.clone() else {
+            return false;
+        };
+        self.cancel_status();
+
+        let id = self.next_id();
+        let tx = self.tx.clone();
+        let handle = runtime.spawn_blocking(move || {
+            let _ = tx.send((id, Loaded::Status(load())));
+        });
+        self.status_task = Some(Task {
+            id,
+            handle,
+            started: Instant::now(),
+        });
+        true
+    }
+
+    /// Abort the in-flight diff load, if any; its result will be ignored
+    pub fn cancel_diff(&mut self) {
+        if let Some(task) = self.diff_task.take() {
+            task.handle.abort();
+        }
+    }
+
+    /// Drop the in-flight status load, if any. A blocking load cannot be
+    /// stopped, but its result will be ignored.
+    pub fn cancel_status(&mut self) {
+        if let Some(task) = self.status_task.take() {
+            task.handle.abort();
+        }
+    }
+
+    /// Finished loads that have not been superseded
+    pub fn poll(&mut self) -> Vec<Loaded> {
+        let mut done = Vec::new();
+        while let Ok((id, loaded)) = self.rx.try_recv() {
+            let task = match loaded {
+                Loaded::Diff { .. } | Loaded::Diffs { .. } => &mut self.diff_task,
+                Loaded::Status(_) => &mut self.status_task,
+            };
+            if task.as_ref().is_some_and(|t| t.id == id) {
+                *task = None;
+                done.push(loaded);
+            }
+        }
+        done
+    }
+
+    /// How long the current diff load has been running
+    pub fn diff_loading_for(&self) -> Option<Duration> {
+        self.diff_task.as_ref().map(|t| t.started.elapsed())
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.diff_task.is_some() || self.status_task.is_some()
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+async fn load(request: &DiffRequest) -> Result<(String, String)> {
+    load_diff_async(
+        request.git.clone(),
+        &request.target,
+        &request.path,
+        &request.tool,
+        request.width,
+        &request.repo_root,
+    )
+    .await
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::backend::ProcessBackend;
+    use crate::testing::TestRepo;
+
+    fn request(repo: &TestRepo, path: &str) -> DiffRequest {
+        DiffRequest {
+            key: None,
+            path: path.to_string(),
+            pane: TreePane::Unstaged,
+            target: DiffTarget::Worktree { staged: false },
+            tool: "raw".to_string(),
+            width: 80,
+            repo_root: repo.root.clone(),
+            git: Arc::new(ProcessBackend::new(&repo.root)),
+            keep_position: false,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_newer_request_supersedes_older() {
+        let repo = TestRepo::new("loader-supersede");
+        repo.write("a.txt", "a\n");
+        repo.write("b.txt", "b\n");
+        repo.commit_all("init");
+        repo.write("a.txt", "a2\n");
+        repo.write("b.txt", "b2\n");
+
+        let mut loader = Loader::new();
+        assert!(loader.load_diff(request(&repo, "a.txt")));
+        assert!(loader.load_diff(request(&repo, "b.txt")));
+        assert!(loader.diff_loading_for().is_some());
+
+        let mut paths = Vec::new();
+        for _ in 0..200 {
+            for loaded in loader.poll() {
+                if let Loaded::Diff { request, result } = loaded {
+                    assert!(result.unwrap().0.contains("+b2"));
+                    paths.push(request.path);
+                }
+            }
+            if !loader.is_busy() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(paths, vec!["b.txt".to_string()]);
+        assert!(loader.diff_loading_for().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_diffs_arrive_together_in_request_order() {
+        let repo = TestRepo::new("loader-diffs");
+        repo.write("a.txt", "a\n");
+        repo.write("b.txt", "b\n");
+        repo.commit_all("init");
+        repo.write("a.txt", "a2\n");
+        repo.write("b.txt", "b2\n");
+
+        let mut loader = Loader::new();
+        let requests = vec![request(&repo, "b.txt"), request(&repo, "a.txt")];
+        assert!(loader.load_diffs(requests, Some("a.txt".to_string())));
+
+        let mut done = Vec::new();
+        for _ in 0..200 {
+            done.extend(loader.poll());
+            if !loader.is_busy() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let [Loaded::Diffs { results, scroll_to }] = done.as_slice() else {
+            panic!("expected one batch");
+        };
+        let paths: Vec<&str> = results.iter().map(|(r, _)| r.path.as_str()).collect();
+        assert_eq!(paths, vec!["b.txt", "a.txt"]);
+        assert!(results[1].1.as_ref().unwrap().0.contains("+a2"));
+        assert_eq!(scroll_to.as_deref(), Some("a.txt"));
+    }
+
+    #[test]
+    fn test_without_runtime_callers_load_synchronously() {
+        let repo = TestRepo::new("loader-sync");
+        let mut loader = Loader::new();
+        assert!(!loader.load_diff(request(&repo, "a.txt")));
+        assert!(!loader.is_busy());
+    }
+}
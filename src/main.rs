@@ -1,11 +1,25 @@
 mod app;
+mod cache;
+mod cli;
 mod clipboard;
 mod config;
+#[cfg(test)]
+mod e2e;
+mod filter;
 mod git;
+mod keymap;
+mod loader;
+mod notes;
+mod review;
+mod state;
+#[cfg(test)]
+mod testing;
+mod theme;
 mod ui;
+mod watcher;
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
@@ -21,18 +35,104 @@ use std::io;
     version
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Diff tool to use (raw | delta | difftastic)
     #[arg(long, value_name = "TOOL")]
     tool: Option<String>,
 
     /// Target commit-ish (e.g. hash, branch, tag). If omitted, working tree mode.
+    /// `-` browses a diff read from stdin (`git diff | diffview -`).
     #[arg(value_name = "REV")]
     revision: Option<String>,
+
+    /// Review a patch or mbox before applying it, without touching the repository
+    #[arg(long, value_name = "FILE", conflicts_with = "revision")]
+    patch: Option<std::path::PathBuf>,
+
+    /// Print JSON instead of text (`status`, `diff` and `notes`)
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+impl Args {
+    /// Parse the command line. `--json` is accepted before or after a
+    /// subcommand but not without one, and the TUI's arguments not with one.
+    fn parse_checked<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let args = Self::try_parse_from(args)?;
+        let tui_args = args.tool.is_some() || args.revision.is_some() || args.patch.is_some();
+        let conflict = match &args.command {
+            None if args.json => "--json needs a subcommand, e.g. `diffview --json status`",
+            Some(_) if tui_args => "REV, --tool and --patch open the TUI and take no subcommand",
+            _ => return Ok(args),
+        };
+        Err(Self::command().error(clap::error::ErrorKind::ArgumentConflict, conflict))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Inspect the configuration
+    Config {
+        /// Print the effective configuration and where each value came from
+        #[arg(long)]
+        show: bool,
+    },
+    /// Print the changed files (a JSON array with --json)
+    Status,
+    /// Print the diff of one file (the parsed hunks with --json)
+    Diff {
+        path: String,
+        /// Diff the index against HEAD instead of the worktree against the index
+        #[arg(long)]
+        staged: bool,
+        /// Diff the file as changed by this commit
+        #[arg(long, value_name = "REV", conflicts_with = "staged")]
+        rev: Option<String>,
+    },
+    /// Print the review notes as markdown, or as JSON lines with --json
+    Notes,
+    /// Stage files, line ranges (`path:FROM-TO`) or hunks
+    Stage {
+        /// `path`, `path:LINE` or `path:FROM-TO` (lines of the worktree file)
+        #[arg(required = true, value_name = "PATH[:LINES]")]
+        targets: Vec<String>,
+        /// Stage only this hunk: its number in the file (from 1) or its ID
+        #[arg(long, value_name = "N|ID")]
+        hunk: Vec<String>,
+    },
+    /// Unstage files, line ranges (`path:FROM-TO`) or hunks
+    Unstage {
+        /// `path`, `path:LINE` or `path:FROM-TO` (lines of the staged file)
+        #[arg(required = true, value_name = "PATH[:LINES]")]
+        targets: Vec<String>,
+        /// Unstage only this hunk: its number in the file (from 1) or its ID
+        #[arg(long, value_name = "N|ID")]
+        hunk: Vec<String>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let args = Args::parse_checked(std::env::args_os()).unwrap_or_else(|err| err.exit());
+
+    if let Some(command) = args.command {
+        return run_command(command, args.json);
+    }
+
+    let piped = if args.revision.as_deref() == Some("-") {
+        match read_pager_input()? {
+            Some(text) => Some(text),
+            None => return Ok(()),
+        }
+    } else {
+        None
+    };
 
     // ── Setup terminal ──────────────────────────────────────────────────
     enable_raw_mode()?;
@@ -43,7 +143,11 @@ async fn main() -> Result<()> {
 
     // ── Create app ──────────────────────────────────────────────────────
     let result = (|| -> Result<()> {
-        let mut app = app::App::new(args.tool, args.revision)?;
+        let mut app = match (args.patch, piped) {
+            (Some(patch), _) => app::App::new_patch(args.tool, &patch)?,
+            (None, Some(text)) => app::App::new_pager(args.tool, &text)?,
+            (None, None) => app::App::new(args.tool, args.revision)?,
+        };
         app.run(&mut terminal)
     })();
 
@@ -63,3 +167,78 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Read the diff for `diffview -`. Keys then come from the terminal, which
+/// crossterm opens as `/dev/tty` when stdin is a pipe. Returns `None` after
+/// passing the input through to stdout, as `less` does, when stdout is not a
+/// terminal or the input has no file diffs (`git log` without `-p`).
+fn read_pager_input() -> Result<Option<String>> {
+    use std::io::{IsTerminal, Read, Write};
+
+    if io::stdin().is_terminal() {
+        anyhow::bail!("`diffview -` reads a diff from stdin, e.g. `git diff | diffview -`");
+    }
+    let mut input = Vec::new();
+    io::stdin().read_to_end(&mut input)?;
+
+    let text = git::patch::strip_ansi(&String::from_utf8_lossy(&input));
+    if !io::stdout().is_terminal() || git::diff::parse_diffs(&text).is_empty() {
+        io::stdout().write_all(&input)?;
+        return Ok(None);
+    }
+    Ok(Some(text))
+}
+
+/// Subcommands run without the TUI
+fn run_command(command: Command, json: bool) -> Result<()> {
+    match command {
+        Command::Status => cli::status(json),
+        Command::Diff { path, staged, rev } => cli::diff(&path, staged, rev.as_deref(), json),
+        Command::Notes => cli::notes(json),
+        Command::Stage { targets, hunk } => cli::stage(&targets, &hunk, false),
+        Command::Unstage { targets, hunk } => cli::stage(&targets, &hunk, true),
+        Command::Config { show } => {
+            let repo_root = git::get_repo_root().ok();
+            if show {
+                let loaded = config::Config::load(repo_root.as_deref())?;
+                print!("{}", loaded.describe());
+                for problem in &loaded.problems {
+                    eprintln!("warning: {}", problem);
+                }
+            } else {
+                println!("user config: {}", config::Config::config_path().display());
+                if let Some(root) = &repo_root {
+                    println!(
+                        "repo config: {}",
+                        config::Config::repo_config_path(root).display()
+                    );
+                }
+                println!("git config:  diffview.<section>.<key>");
+                println!("environment: DIFFVIEW_<SECTION>_<KEY>");
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tui_flags_conflict_with_subcommands() {
+        let parse = |args: &[&str]| Args::parse_checked(args);
+        assert!(parse(&["diffview", "HEAD", "status"]).is_err());
+        assert!(parse(&["diffview", "--patch", "x.patch", "status"]).is_err());
+        assert!(parse(&["diffview", "--json"]).is_err());
+        for line in [
+            &["diffview", "--json", "status"],
+            &["diffview", "status", "--json"],
+        ] {
+            let args = parse(line).unwrap();
+            assert!(args.json && matches!(args.command, Some(Command::Status)));
+        }
+        let args = parse(&["diffview", "--json", "diff", "src/app.rs"]).unwrap();
+        assert!(args.json && matches!(args.command, Some(Command::Diff { .. })));
+    }
+}
@@ -0,0 +1,290 @@
+//! Review notes: comments attached to diff lines during self-review, stored
+//! per repository in `<git-dir>/diffview/notes.toml`. A note is keyed by its
+//! path, side and the line's content, so it follows the line as code around
+//! it moves; the line number is only a hint for telling equal lines apart.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::git::diff::{DiffLine, Hunk};
+use crate::state::{load_toml, save_toml};
+
+/// The version of a file a diff line's number counts in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    /// Context and added lines
+    #[default]
+    New,
+    /// Removed lines
+    Old,
+}
+
+impl Side {
+    fn is_new(&self) -> bool {
+        *self == Side::New
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub path: String,
+    /// Line number on `side` when the note was made
+    pub line: u32,
+    #[serde(default, skip_serializing_if = "Side::is_new")]
+    pub side: Side,
+    /// Commit whose diff the note is on (commit mode); `None` for the
+    /// worktree and index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// The line without its diff prefix
+    pub content: String,
+    pub text: String,
+}
+
+impl Note {
+    /// Whether `other` is on the same line, up to the line number
+    pub fn same_line(&self, other: &Note) -> bool {
+        self.path == other.path
+            && self.side == other.side
+            && self.commit == other.commit
+            && self.content == other.content
+    }
+
+    /// The note's current line in the worktree file: the occurrence of its
+    /// content nearest to the stored line, or the stored line if it is gone.
+    /// Removed lines and lines of a commit are not in the worktree; they
+    /// keep their stored line.
+    pub fn resolve_line(&self, repo_root: &Path) -> u32 {
+        if self.side == Side::Old || self.commit.is_some() {
+            return self.line;
+        }
+        let Ok(text) = std::fs::read_to_string(repo_root.join(&self.path)) else {
+            return self.line;
+        };
+        text.lines()
+            .zip(1u32..)
+            .filter(|(line, _)| *line == self.content)
+            .map(|(_, no)| no)
+            .min_by_key(|no| no.abs_diff(self.line))
+            .unwrap_or(self.line)
+    }
+
+    /// What `line` counts in, when not the worktree's new side
+    fn qualifier(&self) -> String {
+        let short = |commit: &str| commit.chars().take(7).collect::<String>();
+        match (self.side, &self.commit) {
+            (Side::New, None) => String::new(),
+            (Side::Old, None) => " (removed)".to_string(),
+            (Side::New, Some(commit)) => format!(" (in {})", short(commit)),
+            (Side::Old, Some(commit)) => format!(" (removed in {})", short(commit)),
+        }
+    }
+
+    /// `path:line`, with the side and commit when they matter
+    pub fn location(&self) -> String {
+        format!("{}:{}{}", self.path, self.line, self.qualifier())
+    }
+}
+
+/// Side and line number of every line of `hunk`, in one pass
+pub fn line_numbers(hunk: &Hunk) -> Vec<(Side, u32)> {
+    let (mut old, mut new) = (hunk.old_start, hunk.new_start);
+    hunk.lines
+        .iter()
+        .map(|line| match line {
+            DiffLine::Context(_) => {
+                old += 1;
+                new += 1;
+                (Side::New, new - 1)
+            }
+            DiffLine::Added(_) => {
+                new += 1;
+                (Side::New, new - 1)
+            }
+            DiffLine::Removed(_) => {
+                old += 1;
+                (Side::Old, old - 1)
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Notes {
+    #[serde(default, rename = "note")]
+    pub notes: Vec<Note>,
+}
+
+impl Notes {
+    pub fn load(git_dir: &Path) -> Result<Self> {
+        load_toml(&Self::notes_path(git_dir))
+    }
+
+    pub fn save(&self, git_dir: &Path) -> Result<()> {
+        save_toml(&Self::notes_path(git_dir), self)
+    }
+
+    pub fn notes_path(git_dir: &Path) -> PathBuf {
+        git_dir.join("diffview").join("notes.toml")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    /// The note on `anchor`'s line, if any
+    pub fn find(&self, anchor: &Note) -> Option<usize> {
+        self.notes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.same_line(anchor))
+            .min_by_key(|(_, n)| n.line.abs_diff(anchor.line))
+            .map(|(idx, _)| idx)
+    }
+
+    /// Add or replace the note on `note`'s line; empty text removes it
+    pub fn set(&mut self, note: Note) {
+        let existing = self
+            .notes
+            .iter()
+            .position(|n| n.same_line(&note) && n.line == note.line);
+        match (existing, note.text.trim().is_empty()) {
+            (Some(idx), true) => {
+                self.notes.remove(idx);
+            }
+            (Some(idx), false) => self.notes[idx] = note,
+            (None, true) => {}
+            (None, false) => self.notes.push(note),
+        }
+        self.notes
+            .sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    }
+
+    pub fn remove(&mut self, idx: usize) -> Option<Note> {
+        (idx < self.notes.len()).then(|| self.notes.remove(idx))
+    }
+
+    /// Notes at their current lines, for export
+    pub fn resolved(&self, repo_root: &Path) -> Vec<Note> {
+        self.notes
+            .iter()
+            .map(|note| Note {
+                line: note.resolve_line(repo_root),
+                ..note.clone()
+            })
+            .collect()
+    }
+}
+
+/// A markdown list with `path:line` references, for PR descriptions
+pub fn to_markdown(notes: &[Note]) -> String {
+    notes
+        .iter()
+        .map(|note| {
+            format!(
+                "- `{}:{}`{} {}\n  > {}\n",
+                note.path,
+                note.line,
+                note.qualifier(),
+                note.text.trim(),
+                note.content.trim()
+            )
+        })
+        .collect()
+}
+
+/// One JSON object per note and line, for review tools
+pub fn to_json_lines(notes: &[Note]) -> Result<String> {
+    let mut out = String::new();
+    for note in notes {
+        out.push_str(&serde_json::to_string(note)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(path: &str, line: u32, content: &str, text: &str) -> Note {
+        Note {
+            path: path.into(),
+            line,
+            side: Side::New,
+            commit: None,
+            content: content.into(),
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn test_set_replaces_and_removes_notes() {
+        let mut notes = Notes::default();
+        notes.set(note("b.rs", 3, "x", "later"));
+        notes.set(note("a.rs", 9, "}", "one"));
+        notes.set(note("a.rs", 2, "}", "two"));
+        assert_eq!(notes.len(), 3);
+        assert_eq!(notes.notes[0].text, "two");
+        // Equal lines are told apart by the nearest line number
+        assert_eq!(notes.find(&note("a.rs", 8, "}", "")), Some(1));
+
+        notes.set(note("a.rs", 9, "}", "edited"));
+        assert_eq!(notes.notes[1].text, "edited");
+        notes.set(note("a.rs", 9, "}", " "));
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes.find(&note("a.rs", 8, "}", "")), Some(0));
+        // A removed line is another line than an added one with its text
+        let removed = Note {
+            side: Side::Old,
+            ..note("a.rs", 2, "}", "")
+        };
+        assert_eq!(notes.find(&removed), None);
+    }
+
+    #[test]
+    fn test_exports_reference_current_lines() {
+        let dir = std::env::temp_dir().join(format!("diffview-notes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("f.rs"), "fn a() {}\nnew\nfn b() {}\n").unwrap();
+        let mut notes = Notes::default();
+        notes.set(note("f.rs", 2, "fn b() {}", "TODO: rename"));
+        notes.set(note("gone.rs", 7, "x", "ask about this"));
+        // Only new-side worktree lines are looked up in the worktree
+        notes.set(Note {
+            side: Side::Old,
+            ..note("f.rs", 1, "fn a() {}", "why?")
+        });
+        notes.set(Note {
+            commit: Some("0123456789abcdef".into()),
+            ..note("f.rs", 5, "fn b() {}", "old")
+        });
+
+        let resolved = notes.resolved(&dir);
+        assert_eq!(
+            to_markdown(&resolved),
+            "- `f.rs:1` (removed) why?\n  > fn a() {}\n\
+             - `f.rs:3` TODO: rename\n  > fn b() {}\n\
+             - `f.rs:5` (in 0123456) old\n  > fn b() {}\n\
+             - `gone.rs:7` ask about this\n  > x\n"
+        );
+        let json = to_json_lines(&resolved).unwrap();
+        assert_eq!(json.lines().count(), 4);
+        assert!(json.starts_with(r#"{"path":"f.rs","line":1,"side":"old","#));
+
+        notes.save(&dir).unwrap();
+        assert_eq!(Notes::load(&dir).unwrap().notes, notes.notes);
+
+        // A file that does not parse is reported and not overwritten
+        std::fs::write(Notes::notes_path(&dir), "note = 3").unwrap();
+        assert!(Notes::load(&dir).is_err());
+        assert!(notes.save(&dir).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,202 @@
+//! Reviewing a patch from outside the repository (`diffview --patch`).
+//! The patch is only read; nothing changes until the user applies files,
+//! hunks or lines from it. Pager mode (`diffview -`) shows a diff read from
+//! stdin the same way, without checks or applying.
+
+use std::collections::HashMap;
+
+use crate::app::SectionFile;
+use crate::git::backend::{ApplyOptions, GitBackend};
+use crate::git::diff::{parse_diffs, DiffStat};
+use crate::git::patch::PatchFile;
+
+/// Whether a file of the patch applies to the worktree
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchCheck {
+    Applies,
+    /// Only the reverse applies: the change is already there
+    Applied,
+    /// git's reason, e.g. `patch failed: src/app.rs:120`
+    Fails(String),
+}
+
+impl PatchCheck {
+    /// Status column shown in the tree
+    fn status(&self, file: &PatchFile) -> char {
+        match self {
+            PatchCheck::Applies => file.status,
+            PatchCheck::Applied => '=',
+            PatchCheck::Fails(_) => '!',
+        }
+    }
+}
+
+pub struct PatchReview {
+    /// File name of the patch, for titles
+    pub name: String,
+    pub files: Vec<PatchFile>,
+    pub checks: HashMap<String, PatchCheck>,
+    /// Pager mode: only browsed, never checked or applied
+    pub read_only: bool,
+}
+
+impl PatchReview {
+    /// Parse `text`. Diffs of the same path (several mails of an mbox)
+    /// are joined, which `git apply` takes in order.
+    pub fn new(name: &str, text: &str) -> Self {
+        let mut files: Vec<PatchFile> = Vec::new();
+        for file in parse_diffs(text) {
+            join_file(&mut files, file);
+        }
+        Self {
+            name: name.to_string(),
+            files,
+            checks: HashMap::new(),
+            read_only: false,
+        }
+    }
+
+    /// A diff to browse only, as piped in from `git diff` or `git log -p`.
+    /// The files of each commit are kept apart, listed under a directory
+    /// named after the commit and numbered in the order they came.
+    pub fn read_only(name: &str, text: &str) -> Self {
+        let parsed = parse_diffs(text);
+        let mut commits: Vec<String> = Vec::new();
+        for commit in parsed.iter().filter_map(|f| f.commit.as_ref()) {
+            if !commits.contains(commit) {
+                commits.push(commit.clone());
+            }
+        }
+        let width = commits.len().to_string().len();
+        let mut files: Vec<PatchFile> = Vec::new();
+        for mut file in parsed {
+            if let Some(commit) = &file.commit {
+                let n = commits.iter().position(|c| c == commit).unwrap_or(0) + 1;
+                // A slash in the subject would nest it as directories
+                let label = commit.replace('/', "\u{2215}");
+                file.path = format!("{:0width$} {}/{}", n, label, file.path);
+            }
+            join_file(&mut files, file);
+        }
+        Self {
+            name: name.to_string(),
+            files,
+            checks: HashMap::new(),
+            read_only: true,
+        }
+    }
+
+    pub fn file(&self, path: &str) -> Option<&PatchFile> {
+        self.files.iter().find(|f| f.path == path)
+    }
+
+    /// Run `git apply --check` on every file, and in reverse on the ones
+    /// that do not apply
+    pub fn check(&mut self, git: &dyn GitBackend) {
+        let check = ApplyOptions {
+            check: true,
+            ..ApplyOptions::default()
+        };
+        let reverse = ApplyOptions {
+            reverse: true,
+            ..check
+        };
+        self.checks = self
+            .files
+            .iter()
+            .map(|file| {
+                let result = match git.apply(&file.raw, check) {
+                    Ok(()) => PatchCheck::Applies,
+                    Err(_) if git.apply(&file.raw, reverse).is_ok() => PatchCheck::Applied,
+                    Err(err) => PatchCheck::Fails(apply_error(&err)),
+                };
+                (file.path.clone(), result)
+            })
+            .collect();
+    }
+
+    /// The files as the tree shows them, with line counts when `diffstat`:
+    /// the change kind when the file applies, `=` when already applied and
+    /// `!` when it does not apply
+    pub fn tree_files(&self, diffstat: bool) -> (Vec<SectionFile>, HashMap<String, DiffStat>) {
+        let unstaged = self
+            .files
+            .iter()
+            .map(|file| {
+                let status = self
+                    .checks
+                    .get(&file.path)
+                    .map_or(file.status, |check| check.status(file));
+                (file.path.clone(), ' ', status)
+            })
+            .collect();
+        let unstaged_stats = if diffstat {
+            self.files
+                .iter()
+                .map(|file| (file.path.clone(), file.diff.stat()))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        (unstaged, unstaged_stats)
+    }
+}
+
+/// Add `file` to `files`, appending it to an earlier diff of the same path
+fn join_file(files: &mut Vec<PatchFile>, file: PatchFile) {
+    match files.iter_mut().find(|f| f.path == file.path) {
+        Some(existing) => {
+            existing.raw.push_str(&file.raw);
+            existing.diff.hunks.extend(file.diff.hunks);
+        }
+        None => files.push(file),
+    }
+}
+
+/// The first line of git's complaint, without the command and `error:`
+pub fn apply_error(err: &anyhow::Error) -> String {
+    let text = format!("{:#}", err);
+    let text = text
+        .split_once("failed: ")
+        .map_or(text.as_str(), |(_, rest)| rest);
+    let first = text.lines().next().unwrap_or_default();
+    first.trim_start_matches("error: ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::backend::ProcessBackend;
+    use crate::testing::TestRepo;
+
+    #[test]
+    fn test_check_marks_each_file() {
+        let repo = TestRepo::new("review-check");
+        repo.write("ok.txt", "a\n");
+        repo.write("done.txt", "x\n");
+        repo.write("bad.txt", "1\n");
+        repo.commit_all("init");
+
+        let patch = concat!(
+            "diff --git a/ok.txt b/ok.txt\n--- a/ok.txt\n+++ b/ok.txt\n@@ -1 +1 @@\n-a\n+A\n",
+            "diff --git a/done.txt b/done.txt\n--- a/done.txt\n+++ b/done.txt\n@@ -1 +1 @@\n-w\n+x\n",
+            "diff --git a/bad.txt b/bad.txt\n--- a/bad.txt\n+++ b/bad.txt\n@@ -1 +1 @@\n-9\n+2\n",
+        );
+        let mut review = PatchReview::new("x.patch", patch);
+        review.check(&ProcessBackend::new(&repo.root));
+
+        let (files, stats) = review.tree_files(true);
+        let statuses: Vec<char> = files.iter().map(|f| f.2).collect();
+        assert_eq!(statuses, vec!['M', '=', '!']);
+        assert_eq!(
+            review.checks["bad.txt"],
+            PatchCheck::Fails("patch failed: bad.txt:1".to_string())
+        );
+        assert_eq!(stats["ok.txt"].added, 1);
+        // Checking left the worktree alone
+        assert_eq!(
+            std::fs::read_to_string(repo.root.join("ok.txt")).unwrap(),
+            "a\n"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::app::TreeLayout;
+
+/// Read a TOML file under `<git-dir>/diffview`. A missing file is the
+/// default; one that does not parse is an error rather than silently empty.
+pub fn load_toml<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => {
+            toml::from_str(&content).with_context(|| format!("Cannot parse {}", path.display()))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(err) => Err(err).with_context(|| format!("Cannot read {}", path.display())),
+    }
+}
+
+/// Write a TOML file under `<git-dir>/diffview`, unless the file there does
+/// not parse: it failed to load, so saving would replace the user's data.
+pub fn save_toml<T: Serialize + DeserializeOwned>(path: &Path, value: &T) -> Result<()> {
+    if let Ok(content) = std::fs::read_to_string(path) {
+        if toml::from_str::<T>(&content).is_err() {
+            bail!("Not overwriting {}, which does not parse", path.display());
+        }
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, toml::to_string(value)?)?;
+    Ok(())
+}
+
+/// UI state remembered per repository, stored in `<git-dir>/diffview/state.toml`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoState {
+    #[serde(default)]
+    pub tree_layout: TreeLayout,
+}
+
+impl RepoState {
+    pub fn load(git_dir: &Path) -> Result<Self> {
+        load_toml(&Self::state_path(git_dir))
+    }
+
+    pub fn save(&self, git_dir: &Path) -> Result<()> {
+        save_toml(&Self::state_path(git_dir), self)
+    }
+
+    pub fn state_path(git_dir: &Path) -> PathBuf {
+        git_dir.join("diffview").join("state.toml")
+    }
+}
+
+/// Files marked as viewed, stored in `<git-dir>/diffview/viewed.toml`. A
+/// mark holds the blob ID of the version that was viewed, so it no longer
+/// applies once the file changes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ViewedFiles {
+    /// Blob IDs viewed, by path
+    #[serde(default)]
+    pub files: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ViewedFiles {
+    pub fn load(git_dir: &Path) -> Result<Self> {
+        load_toml(&Self::viewed_path(git_dir))
+    }
+
+    pub fn save(&self, git_dir: &Path) -> Result<()> {
+        save_toml(&Self::viewed_path(git_dir), self)
+    }
+
+    pub fn viewed_path(git_dir: &Path) -> PathBuf {
+        git_dir.join("diffview").join("viewed.toml")
+    }
+
+    pub fn contains(&self, path: &str, blob: &str) -> bool {
+        self.files
+            .get(path)
+            .is_some_and(|blobs| blobs.contains(blob))
+    }
+
+    /// Whether any version of `path` was marked
+    pub fn has_path(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    pub fn set(&mut self, path: &str, blob: &str, viewed: bool) {
+        if viewed {
+            self.files
+                .entry(path.to_string())
+                .or_default()
+                .insert(blob.to_string());
+        } else if let Some(blobs) = self.files.get_mut(path) {
+            blobs.remove(blob);
+            if blobs.is_empty() {
+                self.files.remove(path);
+            }
+        }
+    }
+
+    /// Forget the marks of versions no longer shown: for each path of
+    /// `shown`, only the blobs it lists are kept. Other paths are left
+    /// alone. Returns whether anything was dropped.
+    pub fn prune(&mut self, shown: &HashMap<String, HashSet<String>>) -> bool {
+        let before = self.files.values().map(BTreeSet::len).sum::<usize>();
+        self.files.retain(|path, blobs| match shown.get(path) {
+            Some(current) => {
+                blobs.retain(|blob| current.contains(blob));
+                !blobs.is_empty()
+            }
+            None => true,
+        });
+        before != self.files.values().map(BTreeSet::len).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unparsable_state_is_reported_and_kept() {
+        let dir = std::env::temp_dir().join(format!("diffview-state-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(
+            RepoState::load(&dir).unwrap().tree_layout,
+            TreeLayout::Nested
+        );
+
+        let path = RepoState::state_path(&dir);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "tree_layout = \"sideways\"\n").unwrap();
+        let err = RepoState::load(&dir).unwrap_err();
+        assert!(format!("{:#}", err).contains("state.toml"), "{:#}", err);
+        assert!(RepoState::default().save(&dir).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "tree_layout = \"sideways\"\n"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prune_keeps_shown_versions_only() {
+        let mut viewed = ViewedFiles::default();
+        viewed.set("a.txt", "1", true);
+        viewed.set("a.txt", "2", true);
+        viewed.set("b.txt", "3", true);
+        viewed.set("c.txt", "4", true);
+        let shown = HashMap::from([
+            ("a.txt".to_string(), HashSet::from(["2".to_string()])),
+            ("b.txt".to_string(), HashSet::from(["5".to_string()])),
+        ]);
+        assert!(viewed.prune(&shown));
+        assert!(viewed.contains("a.txt", "2") && !viewed.contains("a.txt", "1"));
+        assert!(!viewed.has_path("b.txt"));
+        assert!(viewed.contains("c.txt", "4"));
+        assert!(!viewed.prune(&shown));
+    }
+}
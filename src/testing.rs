@@ -0,0 +1,76 @@
+//! Shared helpers for tests that need a real git repository.
+
+use std::path::PathBuf;
+use std::sync::Once;
+
+use crate::git::run_git;
+
+/// A throwaway git repository, removed on drop
+pub struct TestRepo {
+    pub root: PathBuf,
+}
+
+impl TestRepo {
+    pub fn new(name: &str) -> Self {
+        isolate_env();
+        let root =
+            std::env::temp_dir().join(format!("diffview-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let repo = Self { root };
+        repo.git(&["init", "-q"]);
+        repo.git(&["config", "user.name", "diffview"]);
+        repo.git(&["config", "user.email", "diffview@example.com"]);
+        repo.git(&["config", "commit.gpgsign", "false"]);
+        repo
+    }
+
+    pub fn git(&self, args: &[&str]) -> String {
+        run_git(args, &self.root).unwrap()
+    }
+
+    pub fn write(&self, path: &str, content: &str) {
+        let full = self.root.join(path);
+        if let Some(parent) = full.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(full, content).unwrap();
+    }
+
+    pub fn commit_all(&self, message: &str) {
+        self.git(&["add", "-A"]);
+        self.git(&["commit", "-qm", message]);
+    }
+
+    /// Content of `path` in the index
+    pub fn index(&self, path: &str) -> String {
+        self.git(&["show", &format!(":{}", path)])
+    }
+
+    pub fn staged_paths(&self) -> Vec<String> {
+        self.git(&["diff", "--cached", "--name-only"])
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// Point `HOME` at an empty directory and turn off global and system git
+/// config, so neither git nor diffview sees the developer's settings
+fn isolate_env() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        let home = std::env::temp_dir().join(format!("diffview-test-home-{}", std::process::id()));
+        std::fs::create_dir_all(&home).unwrap();
+        std::env::set_var("GIT_CONFIG_GLOBAL", home.join(".gitconfig"));
+        std::env::set_var("GIT_CONFIG_NOSYSTEM", "1");
+        std::env::set_var("XDG_CONFIG_HOME", home.join(".config"));
+        std::env::set_var("HOME", home);
+    });
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
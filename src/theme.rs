@@ -0,0 +1,437 @@
+use ratatui::style::{Color, Modifier, Style};
+use std::str::FromStr;
+
+use crate::config::ThemeConfig;
+
+// ─── Colour depth ───────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    NoColor,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// `NO_COLOR` wins, then the config setting, then `COLORTERM`/`TERM`.
+    pub fn detect(setting: Option<&str>) -> Self {
+        if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return ColorDepth::NoColor;
+        }
+        match setting.map(|s| s.to_lowercase()).as_deref() {
+            Some("none") => return ColorDepth::NoColor,
+            Some("16") => return ColorDepth::Ansi16,
+            Some("256") => return ColorDepth::Ansi256,
+            Some("truecolor") | Some("24bit") => return ColorDepth::TrueColor,
+            _ => {}
+        }
+
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+
+    /// Map a colour to the closest one the terminal can show
+    pub fn fit(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorDepth::NoColor, _) => Color::Reset,
+            (ColorDepth::TrueColor, c) => c,
+            (ColorDepth::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(rgb_to_256(r, g, b)),
+            (ColorDepth::Ansi16, Color::Rgb(r, g, b)) => nearest_ansi16(r, g, b),
+            (ColorDepth::Ansi16, Color::Indexed(i)) if i >= 16 => {
+                let (r, g, b) = indexed_to_rgb(i);
+                nearest_ansi16(r, g, b)
+            }
+            (_, c) => c,
+        }
+    }
+}
+
+/// The 16 ANSI colours with typical xterm RGB values
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+fn nearest_ansi16(r: u8, g: u8, b: u8) -> Color {
+    let dist = |(cr, cg, cb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, cr) + d(g, cg) + d(b, cb)
+    };
+    ANSI16
+        .iter()
+        .min_by_key(|(_, rgb)| dist(*rgb))
+        .map(|(c, _)| *c)
+        .unwrap_or(Color::Reset)
+}
+
+fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| {
+        if v < 48 {
+            0
+        } else {
+            ((v as u16 - 35) / 40).min(5) as u8
+        }
+    };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+fn indexed_to_rgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => ANSI16[i as usize].1,
+        16..=231 => {
+            let i = i - 16;
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            (level(i / 36), level((i / 6) % 6), level(i % 6))
+        }
+        _ => {
+            let v = 8 + (i - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
+
+// ─── Theme ──────────────────────────────────────────────────────────────────
+
+/// Every style the UI draws with.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub modified: Style,
+    pub added: Style,
+    pub deleted: Style,
+    pub renamed: Style,
+    pub untracked: Style,
+    pub unmerged: Style,
+    pub directory: Style,
+
+    pub diff_added: Style,
+    pub diff_removed: Style,
+    pub hunk_header: Style,
+    pub file_header: Style,
+
+    pub cursor: Style,
+    pub selection: Style,
+    pub border: Style,
+    pub border_focused: Style,
+    pub dim: Style,
+    pub heavy: Style,
+
+    pub statusbar: Style,
+    pub badge: Style,
+    pub prompt: Style,
+    pub message: Style,
+    pub error: Style,
+
+    /// Colours from external tools (delta, difftastic) are dropped too
+    pub no_color: bool,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            modified: fg(Color::Yellow),
+            added: fg(Color::Green),
+            deleted: fg(Color::Red),
+            renamed: fg(Color::Cyan),
+            untracked: fg(Color::DarkGray),
+            unmerged: fg(Color::Red).add_modifier(Modifier::BOLD),
+            directory: fg(Color::Blue).add_modifier(Modifier::BOLD),
+            diff_added: fg(Color::Green),
+            diff_removed: fg(Color::Red),
+            hunk_header: fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            file_header: fg(Color::Yellow),
+            cursor: Style::default().bg(Color::DarkGray),
+            selection: Style::default().bg(Color::Blue),
+            border: fg(Color::DarkGray),
+            border_focused: fg(Color::Cyan),
+            dim: fg(Color::DarkGray),
+            heavy: fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            statusbar: fg(Color::White).bg(Color::DarkGray),
+            badge: fg(Color::Black)
+                .bg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+            prompt: fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            message: fg(Color::Yellow),
+            error: fg(Color::Red).add_modifier(Modifier::BOLD),
+            no_color: false,
+        }
+    }
+
+    /// For light terminal backgrounds: avoids the bright-black (`DarkGray`)
+    /// and yellow foregrounds that fade into light palettes.
+    pub fn light() -> Self {
+        Self {
+            modified: fg(Color::Magenta),
+            added: fg(Color::Green),
+            deleted: fg(Color::Red),
+            renamed: fg(Color::Blue),
+            untracked: fg(Color::Black).add_modifier(Modifier::ITALIC),
+            unmerged: fg(Color::Red).add_modifier(Modifier::BOLD),
+            directory: fg(Color::Blue).add_modifier(Modifier::BOLD),
+            diff_added: fg(Color::Green),
+            diff_removed: fg(Color::Red),
+            hunk_header: fg(Color::Blue).add_modifier(Modifier::BOLD),
+            file_header: fg(Color::Magenta),
+            cursor: Style::default().bg(Color::Gray),
+            selection: fg(Color::Black).bg(Color::LightCyan),
+            border: fg(Color::Black),
+            border_focused: fg(Color::Blue).add_modifier(Modifier::BOLD),
+            dim: fg(Color::Blue),
+            heavy: fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            statusbar: fg(Color::Black).bg(Color::Gray),
+            badge: fg(Color::White)
+                .bg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+            prompt: fg(Color::White)
+                .bg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+            message: fg(Color::Blue),
+            error: fg(Color::Red).add_modifier(Modifier::BOLD),
+            no_color: false,
+        }
+    }
+
+    /// `NO_COLOR`: attributes only
+    pub fn plain() -> Self {
+        let none = Style::default();
+        let bold = none.add_modifier(Modifier::BOLD);
+        Self {
+            modified: none,
+            added: none,
+            deleted: none,
+            renamed: none,
+            untracked: none.add_modifier(Modifier::DIM),
+            unmerged: bold,
+            directory: bold,
+            diff_added: none,
+            diff_removed: none,
+            hunk_header: bold,
+            file_header: bold,
+            cursor: none.add_modifier(Modifier::REVERSED),
+            selection: none.add_modifier(Modifier::UNDERLINED),
+            border: none,
+            border_focused: bold,
+            dim: none.add_modifier(Modifier::DIM),
+            heavy: bold,
+            statusbar: none.add_modifier(Modifier::REVERSED),
+            badge: bold,
+            prompt: bold,
+            message: none,
+            error: bold,
+            no_color: true,
+        }
+    }
+
+    /// Build the theme from `[theme]` for a terminal of `depth` (see
+    /// [`ColorDepth::detect`]). Returns the problems found (unknown preset,
+    /// unparsable colours).
+    pub fn from_config(config: &ThemeConfig, depth: ColorDepth) -> (Self, Vec<String>) {
+        if depth == ColorDepth::NoColor {
+            return (Self::plain(), Vec::new());
+        }
+
+        let mut problems = Vec::new();
+        let mut theme = match config.preset.to_lowercase().as_str() {
+            "dark" => Self::dark(),
+            "light" => Self::light(),
+            other => {
+                problems.push(format!("unknown theme preset '{}'", other));
+                Self::dark()
+            }
+        };
+
+        let mut set = |name: &str, value: &Option<String>, apply: &mut dyn FnMut(Color)| {
+            if let Some(value) = value {
+                match Color::from_str(value) {
+                    Ok(color) => apply(color),
+                    Err(_) => problems.push(format!("{}: cannot parse colour '{}'", name, value)),
+                }
+            }
+        };
+
+        set("modified", &config.modified, &mut |c| {
+            theme.modified.fg = Some(c)
+        });
+        set("added", &config.added, &mut |c| theme.added.fg = Some(c));
+        set("deleted", &config.deleted, &mut |c| {
+            theme.deleted.fg = Some(c)
+        });
+        set("renamed", &config.renamed, &mut |c| {
+            theme.renamed.fg = Some(c)
+        });
+        set("untracked", &config.untracked, &mut |c| {
+            theme.untracked.fg = Some(c)
+        });
+        set("unmerged", &config.unmerged, &mut |c| {
+            theme.unmerged.fg = Some(c)
+        });
+        set("directory", &config.directory, &mut |c| {
+            theme.directory.fg = Some(c)
+        });
+        set("diff_added_fg", &config.diff_added_fg, &mut |c| {
+            theme.diff_added.fg = Some(c)
+        });
+        set("diff_added_bg", &config.diff_added_bg, &mut |c| {
+            theme.diff_added.bg = Some(c)
+        });
+        set("diff_removed_fg", &config.diff_removed_fg, &mut |c| {
+            theme.diff_removed.fg = Some(c)
+        });
+        set("diff_removed_bg", &config.diff_removed_bg, &mut |c| {
+            theme.diff_removed.bg = Some(c)
+        });
+        set("hunk_header", &config.hunk_header, &mut |c| {
+            theme.hunk_header.fg = Some(c)
+        });
+        set("file_header", &config.file_header, &mut |c| {
+            theme.file_header.fg = Some(c)
+        });
+        set("cursor_fg", &config.cursor_fg, &mut |c| {
+            theme.cursor.fg = Some(c)
+        });
+        set("cursor_bg", &config.cursor_bg, &mut |c| {
+            theme.cursor.bg = Some(c)
+        });
+        set("selection_bg", &config.selection_bg, &mut |c| {
+            theme.selection.bg = Some(c)
+        });
+        set("border", &config.border, &mut |c| theme.border.fg = Some(c));
+        set("border_focused", &config.border_focused, &mut |c| {
+            theme.border_focused.fg = Some(c)
+        });
+        set("dim", &config.dim, &mut |c| theme.dim.fg = Some(c));
+        set("statusbar_fg", &config.statusbar_fg, &mut |c| {
+            theme.statusbar.fg = Some(c)
+        });
+        set("statusbar_bg", &config.statusbar_bg, &mut |c| {
+            theme.statusbar.bg = Some(c)
+        });
+
+        theme.fit_to(depth);
+        (theme, problems)
+    }
+
+    fn fit_to(&mut self, depth: ColorDepth) {
+        for style in [
+            &mut self.modified,
+            &mut self.added,
+            &mut self.deleted,
+            &mut self.renamed,
+            &mut self.untracked,
+            &mut self.unmerged,
+            &mut self.directory,
+            &mut self.diff_added,
+            &mut self.diff_removed,
+            &mut self.hunk_header,
+            &mut self.file_header,
+            &mut self.cursor,
+            &mut self.selection,
+            &mut self.border,
+            &mut self.border_focused,
+            &mut self.dim,
+            &mut self.heavy,
+            &mut self.statusbar,
+            &mut self.badge,
+            &mut self.prompt,
+            &mut self.message,
+            &mut self.error,
+        ] {
+            style.fg = style.fg.map(|c| depth.fit(c));
+            style.bg = style.bg.map(|c| depth.fit(c));
+        }
+    }
+
+    /// Style for a file status character
+    pub fn status(&self, status: char) -> Style {
+        match status {
+            'M' | 'T' => self.modified,
+            'A' => self.added,
+            'D' => self.deleted,
+            'R' | 'C' => self.renamed,
+            '?' => self.untracked,
+            'U' | '!' => self.unmerged,
+            '=' => self.dim,
+            _ => Style::default(),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+fn fg(color: Color) -> Style {
+    Style::default().fg(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_downgrades_colors() {
+        assert_eq!(
+            ColorDepth::Ansi16.fit(Color::Rgb(250, 10, 10)),
+            Color::LightRed
+        );
+        assert_eq!(
+            ColorDepth::Ansi16.fit(Color::Indexed(22)),
+            ColorDepth::Ansi16.fit(Color::Rgb(0, 95, 0))
+        );
+        assert_eq!(ColorDepth::Ansi16.fit(Color::Cyan), Color::Cyan);
+        assert_eq!(
+            ColorDepth::Ansi256.fit(Color::Rgb(255, 0, 0)),
+            Color::Indexed(196)
+        );
+        assert_eq!(
+            ColorDepth::TrueColor.fit(Color::Rgb(1, 2, 3)),
+            Color::Rgb(1, 2, 3)
+        );
+        assert_eq!(ColorDepth::NoColor.fit(Color::Red), Color::Reset);
+    }
+
+    #[test]
+    fn test_config_overrides_and_problems() {
+        let config = ThemeConfig {
+            preset: "light".to_string(),
+            diff_added_bg: Some("#002200".to_string()),
+            modified: Some("not-a-colour".to_string()),
+            ..ThemeConfig::default()
+        };
+        let (theme, problems) = Theme::from_config(&config, ColorDepth::TrueColor);
+        assert!(!theme.no_color);
+        assert_eq!(theme.diff_added.bg, Some(Color::Rgb(0, 0x22, 0)));
+        assert_eq!(theme.modified.fg, Theme::light().modified.fg);
+        assert_eq!(problems.len(), 1);
+
+        let (theme, problems) = Theme::from_config(&config, ColorDepth::NoColor);
+        assert!(theme.no_color);
+        assert_eq!(theme.diff_added.bg, None);
+        assert!(problems.is_empty());
+    }
+}
@@ -1,21 +1,43 @@
 use ansi_to_tui::IntoText;
 use ratatui::{
     layout::Rect,
-    style::{Color, Modifier, Style},
-    text::{Line, Span, Text},
+    style::{Modifier, Style},
+    text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
     Frame,
 };
 
-use crate::app::{App, DiffTool, Focus};
+use std::time::Duration;
+
+use crate::app::{App, Focus};
+use crate::git::diff::{FileChange, FileDiff};
+use crate::theme::Theme;
+
+/// The path with where it was renamed or copied from and any mode change
+fn file_label(path: &str, file: &FileDiff) -> String {
+    let mut label = path.to_string();
+    match file.change {
+        FileChange::Renamed { similarity } => {
+            label.push_str(&format!(" ← {} ({}%)", file.old_path, similarity));
+        }
+        FileChange::Copied { similarity } => {
+            label.push_str(&format!(" ← {} (copy, {}%)", file.old_path, similarity));
+        }
+        _ => {}
+    }
+    if let Some((old, new)) = file.mode_change() {
+        label.push_str(&format!(" mode {} → {}", old, new));
+    }
+    label
+}
 
 pub fn render(f: &mut Frame, app: &App, area: Rect) {
     let focused = matches!(app.focus, Focus::DiffView | Focus::InlineSelect);
 
     let border_style = if focused {
-        Style::default().fg(Color::Cyan)
+        app.theme.border_focused
     } else {
-        Style::default().fg(Color::DarkGray)
+        app.theme.border
     };
 
     let origin_label = match app.diff_origin {
@@ -23,8 +45,10 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
         None => String::new(),
     };
 
-    let title = match &app.current_file {
+    let loading = app.diff_loading_for();
+    let mut title = match &app.current_file {
         Some(path) => {
+            let path = &file_label(path, &app.file_diff);
             if app.file_diff.is_binary {
                 format!(" {} [{}][binary] ", path, origin_label)
             } else if !app.file_diff.hunks.is_empty() {
@@ -39,100 +63,235 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
                 format!(" {} [{}] ", path, origin_label)
             }
         }
+        None if app.has_sticky_header() => format!(
+            " All files [{}] ({} files, hunk {}/{}) ",
+            origin_label,
+            app.diff_files.len(),
+            app.hunk_cursor + 1,
+            app.file_diff.hunks.len()
+        ),
         None => " Diff ".to_string(),
     };
+    if let Some(elapsed) = loading {
+        title.push_str(&format!("{} loading… ", spinner(elapsed)));
+    }
 
     let inner = Block::default()
         .borders(Borders::ALL)
         .border_style(border_style)
         .title(title);
 
-    let inner_area = inner.inner(area);
+    let mut inner_area = inner.inner(area);
     f.render_widget(inner, area);
 
-    if app.current_file.is_none() {
-        let hint = Paragraph::new("Select a file and press 'l' to view its diff.")
-            .style(Style::default().fg(Color::DarkGray));
+    if !app.has_diff() && loading.is_none() {
+        let hint =
+            Paragraph::new("Select a file and press 'l' to view its diff.").style(app.theme.dim);
         f.render_widget(hint, inner_area);
         return;
     }
 
-    let (content, use_raw_renderer) = if app.focus == Focus::InlineSelect {
-        (&app.raw_diff, true)
-    } else {
-        match app.tool {
-            DiffTool::Raw => (&app.display_diff, true),
-            _ => (&app.display_diff, false),
-        }
-    };
-
-    let scroll = app.diff_scroll as u16;
+    if let Some(elapsed) = loading.filter(|_| app.display_text.is_empty()) {
+        let hint =
+            Paragraph::new(format!("{} Loading diff…", spinner(elapsed))).style(app.theme.dim);
+        f.render_widget(hint, inner_area);
+        return;
+    }
 
-    if use_raw_renderer {
-        let text = build_raw_diff_text(app, content);
-        let para = Paragraph::new(text).scroll((scroll, 0));
-        f.render_widget(para, inner_area);
-    } else {
-        let text = content
-            .as_bytes()
-            .into_text()
-            .unwrap_or_else(|_| build_raw_diff_text(app, content));
-        let para = Paragraph::new(text).scroll((scroll, 0));
-        f.render_widget(para, inner_area);
+    if app.has_sticky_header() {
+        render_sticky_header(f, app, inner_area);
+        inner_area.y += 1;
+        inner_area.height = inner_area.height.saturating_sub(1);
     }
-}
 
-fn build_raw_diff_text<'a>(app: &App, content: &'a str) -> Text<'a> {
+    // Only the visible window becomes widgets; styling was done at load time
+    let text = app.diff_text();
+    let start = app.diff_scroll.min(text.len());
+    let end = (start + inner_area.height as usize).min(text.len());
     let inline_select = app.focus == Focus::InlineSelect;
+    let (sel_from, sel_to) = app.selected_range();
 
-    let lines: Vec<Line<'a>> = content
-        .lines()
+    let lines: Vec<Line> = text.lines[start..end]
+        .iter()
         .enumerate()
-        .map(|(display_idx, line)| {
-            let base_style = diff_line_style(line);
-
-            if inline_select {
-                let is_cursor = display_idx == app.diff_cursor;
-                let is_selectable = app
-                    .line_infos
-                    .get(display_idx)
-                    .map(|info| info.is_selectable)
-                    .unwrap_or(false);
-
-                let style = if is_cursor {
-                    base_style.bg(Color::DarkGray).add_modifier(Modifier::BOLD)
-                } else if is_selectable {
-                    base_style
-                } else {
-                    base_style
-                };
-
-                Line::from(Span::styled(line.to_string(), style))
+        .map(|(offset, line)| {
+            let idx = start + offset;
+            let highlight = if !inline_select {
+                None
+            } else if idx == app.diff_cursor {
+                Some(app.theme.cursor.add_modifier(Modifier::BOLD))
+            } else if app.selection_anchor.is_some() && (sel_from..=sel_to).contains(&idx) {
+                Some(app.theme.selection)
             } else {
-                Line::from(Span::styled(line.to_string(), base_style))
+                None
+            };
+            let note = app.note_on(idx);
+            if highlight.is_none() && note.is_none() {
+                return line.clone();
             }
+            let mut spans: Vec<Span> = match highlight {
+                Some(style) => line
+                    .spans
+                    .iter()
+                    .map(|span| span.clone().patch_style(style))
+                    .collect(),
+                None => line.spans.clone(),
+            };
+            if let Some(note) = note {
+                spans.push(Span::styled(
+                    format!("  ◆ {}", note.text),
+                    app.theme.message.add_modifier(Modifier::ITALIC),
+                ));
+            }
+            Line::from(spans)
         })
         .collect();
 
-    Text::from(lines)
+    f.render_widget(Paragraph::new(lines), inner_area);
 }
 
-fn diff_line_style(line: &str) -> Style {
+/// The continuous view's first row: the file at the top of the pane
+fn render_sticky_header(f: &mut Frame, app: &App, area: Rect) {
+    let idx = app.file_index_at(app.diff_scroll);
+    let Some(file) = app.diff_files.get(idx) else {
+        return;
+    };
+    let header = Line::from(vec![
+        Span::styled(
+            format!("▸ {}", file.path),
+            app.theme.file_header.add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            format!("  file {}/{}", idx + 1, app.diff_files.len()),
+            app.theme.dim,
+        ),
+    ]);
+    f.render_widget(
+        Paragraph::new(header),
+        Rect {
+            height: 1.min(area.height),
+            ..area
+        },
+    );
+}
+
+/// A diff parsed and styled once per load, so each frame only clones the
+/// visible lines.
+#[derive(Debug, Clone, Default)]
+pub struct StyledDiff {
+    pub lines: Vec<Line<'static>>,
+    /// Line index of every `@@` hunk header
+    pub hunk_starts: Vec<usize>,
+}
+
+impl StyledDiff {
+    /// `ansi`: the content carries its own colours (delta, difftastic);
+    /// otherwise lines are coloured by their diff prefix.
+    pub fn new(content: &str, ansi: bool, theme: &Theme) -> Self {
+        let mut lines = if ansi {
+            content
+                .as_bytes()
+                .into_text()
+                .map(|text| text.lines)
+                .unwrap_or_else(|_| plain_lines(content, theme))
+        } else {
+            plain_lines(content, theme)
+        };
+        if theme.no_color {
+            strip_styles(&mut lines);
+        }
+
+        let hunk_starts = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                line.spans
+                    .first()
+                    .is_some_and(|span| span.content.starts_with("@@"))
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+
+        Self { lines, hunk_starts }
+    }
+
+    /// Add `other` after these lines, for the continuous view
+    pub fn append(&mut self, other: StyledDiff) {
+        let offset = self.lines.len();
+        self.hunk_starts
+            .extend(other.hunk_starts.into_iter().map(|start| start + offset));
+        self.lines.extend(other.lines);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+fn plain_lines(content: &str, theme: &Theme) -> Vec<Line<'static>> {
+    content
+        .lines()
+        .map(|line| Line::from(Span::styled(line.to_string(), diff_line_style(theme, line))))
+        .collect()
+}
+
+fn spinner(elapsed: Duration) -> char {
+    const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+    FRAMES[(elapsed.as_millis() / 80) as usize % FRAMES.len()]
+}
+
+/// Output of external tools carries its own colours; drop them for `NO_COLOR`
+fn strip_styles(lines: &mut [Line]) {
+    for line in lines {
+        line.style = Style::default();
+        for span in &mut line.spans {
+            span.style = Style::default();
+        }
+    }
+}
+
+fn diff_line_style(theme: &Theme, line: &str) -> Style {
     if line.starts_with('+') && !line.starts_with("+++") {
-        Style::default().fg(Color::Green)
+        theme.diff_added
     } else if line.starts_with('-') && !line.starts_with("---") {
-        Style::default().fg(Color::Red)
+        theme.diff_removed
     } else if line.starts_with("@@") {
-        Style::default()
-            .fg(Color::Cyan)
-            .add_modifier(Modifier::BOLD)
+        theme.hunk_header
     } else if line.starts_with("diff ")
         || line.starts_with("--- ")
         || line.starts_with("+++ ")
         || line.starts_with("index ")
     {
-        Style::default().fg(Color::Yellow)
+        theme.file_header
     } else {
         Style::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_styled_diff_indexes_hunks_once() {
+        let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n+b\n@@ -9 +9 @@\n c\n";
+        let text = StyledDiff::new(diff, false, &Theme::dark());
+        assert_eq!(text.len(), 8);
+        assert_eq!(text.hunk_starts, vec![3, 6]);
+        assert_eq!(text.lines[5].spans[0].style, Theme::dark().diff_added);
+    }
+
+    #[test]
+    fn test_styled_diff_drops_tool_colours_without_color() {
+        let text = StyledDiff::new("\x1b[32m+added\x1b[0m\n", true, &Theme::plain());
+        assert_eq!(text.len(), 1);
+        assert!(text.lines[0]
+            .spans
+            .iter()
+            .all(|span| span.style == Style::default()));
+    }
+}
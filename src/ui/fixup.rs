@@ -0,0 +1,62 @@
+use ratatui::{
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::app::App;
+use crate::git::fixup::{FixupPlan, StagedHunk};
+
+/// The fixup preview: each commit to be made and the hunks going into it
+pub fn render(f: &mut Frame, app: &App, area: Rect, plan: &FixupPlan) {
+    let hunk_line = |hunk: &StagedHunk| {
+        Line::from(vec![
+            Span::raw(format!("    {}", hunk.path)),
+            Span::styled(
+                format!(
+                    "  hunk {}  @@ -{},{} +{},{} @@",
+                    hunk.number,
+                    hunk.hunk.old_start,
+                    hunk.hunk.old_count,
+                    hunk.hunk.new_start,
+                    hunk.hunk.new_count
+                ),
+                app.theme.dim,
+            ),
+        ])
+    };
+
+    let mut lines = Vec::new();
+    for (commit, hunks) in &plan.fixups {
+        lines.push(Line::from(vec![
+            Span::styled(format!("{} ", commit.short_id()), app.theme.file_header),
+            Span::raw(format!("fixup! {}", commit.subject)),
+        ]));
+        lines.extend(hunks.iter().map(hunk_line));
+    }
+    if !plan.unassigned.is_empty() {
+        lines.push(Line::styled("not assigned, stays staged", app.theme.dim));
+        lines.extend(plan.unassigned.iter().map(hunk_line));
+    }
+
+    let width = (area.width * 4 / 5).max(20).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height * 4 / 5).max(3);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height.saturating_sub(height)) / 2,
+        width,
+        height,
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(app.theme.border_focused)
+        .title(format!(
+            " Fixups: {} commits for {} hunks ",
+            plan.fixups.len(),
+            plan.hunk_count()
+        ));
+
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
@@ -1,17 +1,34 @@
 pub mod diff;
+pub mod fixup;
+pub mod notes;
 pub mod statusbar;
 pub mod tree;
 
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     Frame,
 };
 
 use crate::app::{App, Focus, TreePane};
 
-pub fn render(f: &mut Frame, app: &App) {
-    let size = f.area();
+/// Screen areas of one frame, shared by rendering and mouse hit-testing.
+pub struct Areas {
+    pub unstaged: Option<Rect>,
+    pub staged: Option<Rect>,
+    pub diff: Rect,
+    pub status: Rect,
+}
+
+impl Areas {
+    pub fn tree(&self, pane: TreePane) -> Option<Rect> {
+        match pane {
+            TreePane::Unstaged => self.unstaged,
+            TreePane::Staged => self.staged,
+        }
+    }
+}
 
+pub fn layout(app: &App, size: Rect) -> Areas {
     // Split vertically: main area + status bar (1 line)
     let vert = Layout::default()
         .direction(Direction::Vertical)
@@ -22,42 +39,87 @@ pub fn render(f: &mut Frame, app: &App) {
     let status_area = vert[1];
 
     if matches!(app.focus, Focus::DiffView | Focus::InlineSelect) {
-        diff::render(f, app, main_area);
-        statusbar::render(f, app, status_area);
-        return;
+        return Areas {
+            unstaged: None,
+            staged: None,
+            diff: main_area,
+            status: status_area,
+        };
     }
 
-    // Split main area horizontally: tree pane (1/4) + diff pane (3/4)
+    // Split main area horizontally: tree pane + diff pane (1/4 : 3/4 by default)
+    let tree_percent = app.tree_width_percent;
     let horiz = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Ratio(1, 4), Constraint::Ratio(3, 4)])
+        .constraints([
+            Constraint::Percentage(tree_percent),
+            Constraint::Percentage(100 - tree_percent),
+        ])
         .split(main_area);
 
     let tree_area = horiz[0];
     let diff_area = horiz[1];
 
-    if app.is_commit_mode() {
-        tree::render(f, app, tree_area, TreePane::Unstaged);
-    } else {
-        // Split tree area vertically into unstaged (top) and staged (bottom)
-        let unstaged_items = app.unstaged.visible.len() as u32 + 2; // +2 for border
-        let staged_items = app.staged.visible.len() as u32 + 2;
-        let total = unstaged_items + staged_items;
-
-        let tree_split = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Ratio(unstaged_items.max(3), total.max(6)),
-                Constraint::Ratio(staged_items.max(3), total.max(6)),
-            ])
-            .split(tree_area);
-
-        let unstaged_area = tree_split[0];
-        let staged_area = tree_split[1];
-
-        tree::render(f, app, unstaged_area, TreePane::Unstaged);
-        tree::render(f, app, staged_area, TreePane::Staged);
+    if app.has_single_tree() {
+        return Areas {
+            unstaged: Some(tree_area),
+            staged: None,
+            diff: diff_area,
+            status: status_area,
+        };
+    }
+
+    // Split tree area vertically into unstaged (top) and staged (bottom)
+    let unstaged_items = app.unstaged.visible.len() as u32 + 2; // +2 for border
+    let staged_items = app.staged.visible.len() as u32 + 2;
+    let total = unstaged_items + staged_items;
+
+    let tree_split = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Ratio(unstaged_items.max(3), total.max(6)),
+            Constraint::Ratio(staged_items.max(3), total.max(6)),
+        ])
+        .split(tree_area);
+
+    Areas {
+        unstaged: Some(tree_split[0]),
+        staged: Some(tree_split[1]),
+        diff: diff_area,
+        status: status_area,
+    }
+}
+
+pub fn render(f: &mut Frame, app: &App) {
+    let areas = layout(app, f.area());
+
+    for pane in [TreePane::Unstaged, TreePane::Staged] {
+        if let Some(area) = areas.tree(pane) {
+            tree::render(f, app, area, pane);
+        }
+    }
+    diff::render(f, app, areas.diff);
+    statusbar::render(f, app, areas.status);
+    if let Some(cursor) = app.notes_list {
+        notes::render(
+            f,
+            app,
+            Rect {
+                height: areas.status.y,
+                ..f.area()
+            },
+            cursor,
+        );
+    }
+    if let Some(ref plan) = app.fixup_plan {
+        fixup::render(
+            f,
+            app,
+            Rect {
+                height: areas.status.y,
+                ..f.area()
+            },
+            plan,
+        );
     }
-    diff::render(f, app, diff_area);
-    statusbar::render(f, app, status_area);
 }
@@ -0,0 +1,51 @@
+use ratatui::{
+    layout::Rect,
+    style::Modifier,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::app::App;
+
+/// The notes popup, centred over both panes
+pub fn render(f: &mut Frame, app: &App, area: Rect, cursor: usize) {
+    let notes = &app.notes.notes;
+    let width = (area.width * 4 / 5).max(20).min(area.width);
+    let height = (notes.len() as u16 + 2).min(area.height * 3 / 5).max(3);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height.saturating_sub(height)) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(app.theme.border_focused)
+        .title(format!(" Notes ({}) ", notes.len()));
+    let rows = block.inner(popup).height as usize;
+    let start = (cursor + 1).saturating_sub(rows);
+
+    let lines: Vec<Line> = notes
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(rows)
+        .map(|(idx, note)| {
+            let line = Line::from(vec![
+                Span::styled(note.location(), app.theme.file_header),
+                Span::raw(format!("  {}", note.text)),
+                Span::styled(format!("  {}", note.content.trim()), app.theme.dim),
+            ]);
+            if idx == cursor {
+                line.patch_style(app.theme.cursor.add_modifier(Modifier::BOLD))
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
@@ -1,41 +1,83 @@
 use ratatui::{
     layout::Rect,
-    style::{Color, Modifier, Style},
+    style::Style,
     text::{Line, Span},
     widgets::Paragraph,
     Frame,
 };
 
 use crate::app::{App, DiffTool, Focus};
+use crate::keymap::Action;
 
 pub fn render(f: &mut Frame, app: &App, area: Rect) {
-    let spans = if let Some(ref err) = app.error_message {
-        vec![Span::styled(
-            format!(" ⚠ {}", err),
-            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
-        )]
-    } else if let Some(ref msg) = app.status_message {
-        vec![Span::styled(
-            format!(" {}", msg),
-            Style::default().fg(Color::Yellow),
-        )]
-    } else if app.focus == Focus::InlineSelect {
+    let spans = if let Some(ref input) = app.filter_input {
+        vec![
+            Span::styled(" / ", app.theme.prompt),
+            Span::raw(format!(" {}▏", input)),
+            Span::styled(
+                "  s:<status> ext:<ext> is:both <glob>  Enter:apply Esc:cancel",
+                Style::default(),
+            ),
+        ]
+    } else if let Some(ref input) = app.search_input {
+        vec![
+            Span::styled(" / ", app.theme.prompt),
+            Span::raw(format!(" {}▏", input)),
+            Span::styled(
+                "  search the diff (lowercase ignores case)  Enter:search Esc:cancel",
+                Style::default(),
+            ),
+        ]
+    } else if let Some(ref input) = app.note_input {
         vec![
+            Span::styled(" note ", app.theme.prompt),
+            Span::raw(format!(" {}▏", input)),
             Span::styled(
-                " [SELECT] ",
-                Style::default()
-                    .fg(Color::Black)
-                    .bg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
+                "  comment on the line (empty removes it)  Enter:save Esc:cancel",
+                Style::default(),
             ),
-            Span::raw(" j/k:move  Enter:apply  n/p:hunk  v:back  h:tree  r:refresh"),
+        ]
+    } else if let Some(ref pending) = app.pending_apply {
+        vec![
+            Span::styled(
+                if pending.reverse {
+                    " REVERT "
+                } else {
+                    " CHERRY-PICK "
+                },
+                app.theme.prompt,
+            ),
+            Span::raw(format!(
+                " {} {} the worktree?  y/Enter:apply n/Esc:cancel",
+                pending.what,
+                if pending.reverse { "in" } else { "into" }
+            )),
+        ]
+    } else if let Some(ref err) = app.error_message {
+        vec![Span::styled(format!(" ⚠ {}", err), app.theme.error)]
+    } else if let Some(ref msg) = app.status_message {
+        vec![Span::styled(format!(" {}", msg), app.theme.message)]
+    } else if app.fixup_plan.is_some() {
+        vec![
+            Span::styled(" FIXUP ", app.theme.prompt),
+            Span::raw(" Enter:commit fixups Esc:cancel"),
+        ]
+    } else if app.notes_list.is_some() {
+        vec![
+            Span::styled(" NOTES ", app.theme.prompt),
+            Span::raw(" j/k:move Enter:open d:delete y:copy markdown w:write notes.md Esc:close"),
+        ]
+    } else if app.focus == Focus::InlineSelect {
+        vec![
+            Span::styled(" [SELECT] ", app.theme.prompt),
+            Span::raw(build_hints(app)),
         ]
     } else {
         build_normal_statusbar(app)
     };
 
     let line = Line::from(spans);
-    let widget = Paragraph::new(line).style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    let widget = Paragraph::new(line).style(app.theme.statusbar);
     f.render_widget(widget, area);
 }
 
@@ -46,45 +88,172 @@ fn build_normal_statusbar(app: &App) -> Vec<Span<'static>> {
         DiffTool::Difftastic => " tool:difftastic ",
     };
 
-    let ops = if app.is_commit_mode() {
-        match app.focus {
-            Focus::Unstaged | Focus::Staged => {
-                " [l/Enter]open [h]back [c]copy [j/k]move [r]refresh [?]help [q]quit"
-            }
-            Focus::DiffView => " [j/k]scroll [h]back [n/p]hunk [r]refresh [q]quit",
-            Focus::InlineSelect => " [j/k]move [n/p]hunk [v]back [h]tree [r]refresh",
-        }
-    } else {
-        match app.focus {
-            Focus::Unstaged | Focus::Staged => {
-                " [l]open [h]back [Enter]stage/unstage [c]copy [j/k]move [r]refresh [?]help [q]quit"
-            }
-            Focus::DiffView => {
-                if app.tool.supports_line_ops() {
-                    " [j/k]scroll [h]back [v]select [n/p]hunk [r]refresh [q]quit"
-                } else {
-                    " [j/k]scroll [h]back [n/p]hunk [r]refresh [q]quit"
-                }
-            }
-            Focus::InlineSelect => " [j/k]move [Enter]apply [n/p]hunk [v]back [h]tree [r]refresh",
-        }
-    };
+    let ops = build_hints(app);
 
-    let status_legend = if app.is_commit_mode() {
+    let status_legend = if app.is_pager() || app.is_commit_mode() {
         "  M=modified A=added D=deleted R=renamed C=copied"
+    } else if app.patch.is_some() {
+        "  M/A/D/R=applies ==already applied !=does not apply"
     } else {
         "  M=modified A=added D=deleted ?=untracked"
     };
 
-    vec![
-        Span::styled(
-            tool_label.to_string(),
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Span::raw(ops.to_string()),
-        Span::styled(status_legend, Style::default().fg(Color::DarkGray)),
-    ]
+    let mut spans = vec![Span::styled(tool_label.to_string(), app.theme.badge)];
+    if !app.filter_query.is_empty() {
+        spans.push(Span::styled(
+            format!(" filter:{} ", app.filter_query),
+            app.theme.prompt,
+        ));
+    }
+    spans.push(Span::raw(ops.to_string()));
+    spans.push(Span::styled(status_legend, app.theme.dim));
+    spans
+}
+
+/// Key hints for the focused pane, generated from the active bindings
+fn build_hints(app: &App) -> String {
+    use Action::*;
+
+    let hunk: (&[Action], &str) = (&[NextHunk, PrevHunk], "hunk");
+    let moves: (&[Action], &str) = (&[Down, Up], "move");
+    let scroll: (&[Action], &str) = (&[Down, Up], "scroll");
+
+    let search: (&[Action], &str) = (&[Search], "search");
+    let matches: (&[Action], &str) = (&[SearchNext, SearchPrev], "match");
+
+    let items: Vec<(&[Action], &str)> = match app.focus {
+        Focus::Unstaged | Focus::Staged if app.is_pager() => vec![
+            (&[Open, ToggleStage], "open"),
+            (&[Back], "back"),
+            (&[CopyPath], "copy"),
+            (&[Filter], "filter"),
+            (&[CycleLayout], "layout"),
+            moves,
+            (&[Help], "help"),
+            (&[Quit], "quit"),
+        ],
+        Focus::Unstaged | Focus::Staged if app.patch.is_some() => vec![
+            (&[Open], "open"),
+            (&[ToggleStage], "apply"),
+            (&[ApplyToIndex], "apply to index"),
+            (&[Filter], "filter"),
+            moves,
+            (&[Refresh], "recheck"),
+            (&[Help], "help"),
+            (&[Quit], "quit"),
+        ],
+        Focus::Unstaged | Focus::Staged if app.is_commit_mode() => vec![
+            (&[Open, ToggleStage], "open"),
+            (&[Back], "back"),
+            (
+                &[CherryPick],
+                if app.revert { "revert" } else { "cherry-pick" },
+            ),
+            (&[ApplyToIndex], "to index"),
+            (&[ToggleRevert], "pick/revert"),
+            (&[CopyPath], "copy"),
+            (&[Filter], "filter"),
+            (&[CycleLayout], "layout"),
+            (&[ToggleViewed], "viewed"),
+            moves,
+            (&[Refresh], "refresh"),
+            (&[Help], "help"),
+            (&[Quit], "quit"),
+        ],
+        Focus::Unstaged | Focus::Staged => vec![
+            (&[Open], "open"),
+            (&[Back], "back"),
+            (&[ToggleStage], "stage/unstage"),
+            (&[CopyPath], "copy"),
+            (&[Filter], "filter"),
+            (&[CycleLayout], "layout"),
+            (&[ToggleViewed], "viewed"),
+            (&[Fixup], "fixup"),
+            moves,
+            (&[Refresh], "refresh"),
+            (&[Help], "help"),
+            (&[Quit], "quit"),
+        ],
+        Focus::DiffView if app.is_pager() => vec![
+            scroll,
+            (&[Back], "back"),
+            hunk,
+            search,
+            matches,
+            (&[CopyPatch], "copy patch"),
+            (&[Quit], "quit"),
+        ],
+        Focus::DiffView if app.patch.is_some() => vec![
+            scroll,
+            (&[Back], "back"),
+            (&[Apply], "apply hunk"),
+            (&[ApplyToIndex], "to index"),
+            (&[LineSelect], "select"),
+            hunk,
+            (&[Quit], "quit"),
+        ],
+        Focus::DiffView if app.is_commit_mode() && app.tool.supports_line_ops() => vec![
+            scroll,
+            (&[Back], "back"),
+            (
+                &[CherryPick],
+                if app.revert {
+                    "revert hunk"
+                } else {
+                    "cherry-pick hunk"
+                },
+            ),
+            (&[ApplyToIndex], "to index"),
+            (&[ToggleRevert], "pick/revert"),
+            (&[LineSelect], "select"),
+            hunk,
+            (&[Quit], "quit"),
+        ],
+        Focus::DiffView if !app.is_commit_mode() && app.tool.supports_line_ops() => vec![
+            scroll,
+            (&[Back], "back"),
+            (&[LineSelect], "select"),
+            hunk,
+            (&[NextFile, PrevFile], "file"),
+            (&[ToggleContinuous], "all files"),
+            (&[Annotate], "comment"),
+            (&[ListNotes], "notes"),
+            (&[Refresh], "refresh"),
+            (&[Quit], "quit"),
+        ],
+        Focus::DiffView => vec![
+            scroll,
+            (&[Back], "back"),
+            hunk,
+            (&[NextFile, PrevFile], "file"),
+            search,
+            (&[Annotate], "comment"),
+            (&[Refresh], "refresh"),
+            (&[Quit], "quit"),
+        ],
+        Focus::InlineSelect if app.is_commit_mode() => vec![
+            moves,
+            (&[RangeSelect], "range"),
+            (
+                &[CherryPick],
+                if app.revert { "revert" } else { "cherry-pick" },
+            ),
+            (&[ApplyToIndex], "to index"),
+            hunk,
+            (&[LineSelect], "back"),
+            (&[Back], "tree"),
+        ],
+        Focus::InlineSelect => vec![
+            moves,
+            (&[RangeSelect], "range"),
+            (&[Apply], "apply"),
+            (&[Annotate], "comment"),
+            hunk,
+            (&[LineSelect], "back"),
+            (&[Back], "tree"),
+            (&[Refresh], "refresh"),
+        ],
+    };
+
+    app.keymap.hints(&items)
 }
@@ -1,30 +1,54 @@
 use ratatui::{
     layout::Rect,
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, ListState},
     Frame,
 };
 
 use crate::app::{App, TreePane};
+use crate::git::diff::DiffStat;
+use crate::theme::Theme;
 
 pub fn render(f: &mut Frame, app: &App, area: Rect, pane: TreePane) {
     let focused = app.is_tree_focused(pane);
     let tree = app.tree(pane);
     let show_cursor = focused;
 
+    let theme = &app.theme;
     let border_style = if focused {
-        Style::default().fg(Color::Cyan)
+        theme.border_focused
     } else {
-        Style::default().fg(Color::DarkGray)
+        theme.border
     };
 
+    let show_stats = app.config.tree.diffstat;
+    let heavy_lines = app.config.tree.heavy_lines;
+
     let pane_label = app.tree_title(pane);
-    let title = if tree.visible.is_empty() {
+    let mut title = if tree.filter.is_active() {
+        format!(
+            " {} ({}/{}) ",
+            pane_label,
+            tree.matching_file_count(),
+            tree.file_count()
+        )
+    } else if tree.visible.is_empty() {
         format!(" {} (0) ", pane_label)
     } else {
         format!(" {} ({}) ", pane_label, tree.file_count())
     };
+    if tree.file_count() > 0 {
+        title.push_str(&format!(
+            "{}/{} viewed ",
+            tree.viewed_count(),
+            tree.file_count()
+        ));
+    }
+    if show_stats && !tree.visible.is_empty() {
+        let total = tree.total_stat();
+        title.push_str(&format!("+{} -{} ", total.added, total.removed));
+    }
 
     if tree.is_empty() {
         let block = Block::default()
@@ -67,46 +91,45 @@ pub fn render(f: &mut Frame, app: &App, area: Rect, pane: TreePane) {
                 format!(" {}", status_char)
             };
 
+            let viewed = !node.is_dir && tree.viewed.contains(&*node.path.to_string_lossy());
+
             let name_style = if node.is_dir {
-                Style::default()
-                    .fg(Color::Blue)
-                    .add_modifier(Modifier::BOLD)
+                theme.directory
+            } else if viewed {
+                theme.dim
             } else if node.is_untracked() {
-                Style::default().fg(Color::DarkGray)
+                theme.untracked
             } else if node.is_unmerged() {
-                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+                theme.unmerged
             } else {
-                match status_char {
-                    'M' => Style::default().fg(Color::Yellow),
-                    'A' => Style::default().fg(Color::Green),
-                    'D' => Style::default().fg(Color::Red),
-                    'R' | 'C' => Style::default().fg(Color::Cyan),
-                    '?' => Style::default().fg(Color::DarkGray),
-                    _ => Style::default(),
-                }
+                theme.status(status_char)
             };
 
-            let status_style = match status_char {
-                'M' => Style::default().fg(Color::Yellow),
-                'A' => Style::default().fg(Color::Green),
-                'D' => Style::default().fg(Color::Red),
-                'R' | 'C' => Style::default().fg(Color::Cyan),
-                '?' => Style::default().fg(Color::DarkGray),
-                'U' => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
-                _ => Style::default(),
+            let status_style = if viewed {
+                theme.dim
+            } else {
+                theme.status(status_char)
             };
 
             let row_style = if is_selected {
-                Style::default().bg(Color::DarkGray)
+                theme.cursor
             } else {
                 Style::default()
             };
 
-            let spans = vec![
+            let mut spans = vec![
                 Span::styled(format!("{}{}", indent, prefix), row_style),
                 Span::styled(node.name.clone(), name_style.patch(row_style)),
                 Span::styled(status_str, status_style.patch(row_style)),
             ];
+            if show_stats {
+                let heavy = !node.is_dir && node.stat.total() >= heavy_lines;
+                spans.extend(
+                    stat_spans(theme, &node.stat, heavy)
+                        .into_iter()
+                        .map(|span| span.patch_style(row_style)),
+                );
+            }
 
             ListItem::new(Line::from(spans))
         })
@@ -119,12 +142,53 @@ pub fn render(f: &mut Frame, app: &App, area: Rect, pane: TreePane) {
                 .border_style(border_style)
                 .title(title),
         )
-        .highlight_style(Style::default().bg(Color::DarkGray));
+        .highlight_style(theme.cursor);
 
-    let mut list_state = ListState::default();
+    let mut list_state =
+        ListState::default().with_offset(scroll_offset(app, pane, area.height as usize));
     if show_cursor && !tree.is_empty() {
         list_state.select(Some(tree.cursor));
     }
 
     f.render_stateful_widget(list, area, &mut list_state);
 }
+
+/// First visible row of a tree pane: keeps the cursor on screen while the
+/// pane is focused. Used for rendering and for mapping mouse clicks to rows.
+pub fn scroll_offset(app: &App, pane: TreePane, area_height: usize) -> usize {
+    if !app.is_tree_focused(pane) {
+        return 0;
+    }
+    let rows = area_height.saturating_sub(2).max(1); // -2 for border
+    app.tree(pane).cursor.saturating_sub(rows - 1)
+}
+
+/// `+N -M` counts for a tree row. Heavy files get a marker and bold counts.
+fn stat_spans(theme: &Theme, stat: &DiffStat, heavy: bool) -> Vec<Span<'static>> {
+    if stat.binary && stat.total() == 0 {
+        return vec![Span::styled(" bin", theme.dim)];
+    }
+    if stat.total() == 0 {
+        return Vec::new();
+    }
+
+    let emphasis = if heavy {
+        Modifier::BOLD
+    } else {
+        Modifier::empty()
+    };
+    let mut spans = vec![
+        Span::styled(
+            format!(" +{}", stat.added),
+            theme.added.add_modifier(emphasis),
+        ),
+        Span::styled(
+            format!(" -{}", stat.removed),
+            theme.deleted.add_modifier(emphasis),
+        ),
+    ];
+    if heavy {
+        spans.push(Span::styled(" ●", theme.heavy));
+    }
+    spans
+}
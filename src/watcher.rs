@@ -0,0 +1,383 @@
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use crate::cache::worktree_fingerprint;
+
+/// Files inside the git directory whose changes affect what diffview shows
+const GIT_DIR_FILES: &[&str] = &["index", "HEAD"];
+
+/// Longest time a steady stream of changes can postpone a refresh
+const MAX_DELAY_FACTOR: u32 = 5;
+
+/// Watches the worktree and the git index and reports when a refresh is due.
+///
+/// Every directory is watched on its own, except ignored ones, so build
+/// output like `target/` or `node_modules/` costs no inotify watches and
+/// does not keep the view refreshing. Changes are debounced: a refresh fires
+/// once no relevant event arrived for the debounce period.
+pub struct RepoWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    repo_root: PathBuf,
+    git_dir: PathBuf,
+    /// Directories known to be ignored; events below them skip `git check-ignore`
+    ignored_dirs: HashSet<PathBuf>,
+    /// The index as diffview last loaded it. An index event that leaves it
+    /// like this comes from diffview's own writes.
+    seen_index: Option<String>,
+    debouncer: Debouncer,
+}
+
+impl RepoWatcher {
+    pub fn new(repo_root: &Path, git_dir: &Path, debounce: Duration) -> Result<Self> {
+        let (tx, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(tx).context("Failed to start file watcher")?;
+        let ignored_dirs = crate::git::ignored_dirs(repo_root)
+            .unwrap_or_default()
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+
+        let mut this = Self {
+            watcher,
+            events,
+            repo_root: repo_root.to_path_buf(),
+            git_dir: git_dir.to_path_buf(),
+            ignored_dirs,
+            seen_index: None,
+            debouncer: Debouncer::new(debounce),
+        };
+        for dir in watched_dirs(repo_root, repo_root, &this.ignored_dirs) {
+            this.watcher
+                .watch(&dir, RecursiveMode::NonRecursive)
+                .with_context(|| format!("Failed to watch {}", dir.display()))?;
+        }
+        this.watcher
+            .watch(git_dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", git_dir.display()))?;
+        this.index_seen();
+        Ok(this)
+    }
+
+    /// Remember the index as it is now, before diffview loads the status.
+    /// Index events that leave it unchanged then trigger no refresh.
+    pub fn index_seen(&mut self) {
+        self.seen_index = worktree_fingerprint(&self.git_dir.join("index"));
+    }
+
+    /// Drain pending events. Returns true when a refresh is due.
+    pub fn poll(&mut self) -> bool {
+        let mut candidates = Vec::new();
+        let mut created_dirs = Vec::new();
+        let mut head_changed = false;
+        let mut index_touched = false;
+
+        loop {
+            match self.events.try_recv() {
+                Ok(Ok(event)) => {
+                    if !is_change(&event.kind) {
+                        continue;
+                    }
+                    let created = matches!(event.kind, EventKind::Create(_));
+                    for path in event.paths {
+                        if path.starts_with(&self.git_dir) {
+                            match git_state_file(&self.git_dir, &path) {
+                                Some("index") => index_touched = true,
+                                Some(_) => head_changed = true,
+                                None => {}
+                            }
+                        } else if let Ok(relative) = path.strip_prefix(&self.repo_root) {
+                            if !self.in_ignored_dir(relative) {
+                                if created && path.is_dir() {
+                                    created_dirs.push(relative.to_path_buf());
+                                }
+                                candidates.push(relative.to_path_buf());
+                            }
+                        }
+                    }
+                }
+                // Watch errors (e.g. a removed directory) are not actionable
+                Ok(Err(_)) => {}
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+            }
+        }
+
+        let mut index_changed = head_changed;
+        if index_touched {
+            let index = worktree_fingerprint(&self.git_dir.join("index"));
+            if index != self.seen_index {
+                self.seen_index = index;
+                index_changed = true;
+            }
+        }
+
+        if index_changed || self.has_unignored(candidates, &created_dirs) {
+            self.debouncer.record(Instant::now());
+        }
+        for dir in created_dirs {
+            self.watch_new_dir(&dir);
+        }
+
+        self.debouncer.take_due(Instant::now())
+    }
+
+    fn in_ignored_dir(&self, relative: &Path) -> bool {
+        relative
+            .ancestors()
+            .any(|dir| self.ignored_dirs.contains(dir))
+    }
+
+    /// Watch a directory created since the start, and the directories below
+    /// it that are not ignored
+    fn watch_new_dir(&mut self, relative: &Path) {
+        if self.in_ignored_dir(relative) {
+            return;
+        }
+        let dirs = watched_dirs(
+            &self.repo_root.join(relative),
+            &self.repo_root,
+            &self.ignored_dirs,
+        );
+        let queries: Vec<String> = dirs
+            .iter()
+            .filter_map(|dir| dir.strip_prefix(&self.repo_root).ok())
+            .map(|dir| format!("{}/", dir.to_string_lossy()))
+            .collect();
+        if let Ok(ignored) = crate::git::check_ignore(&queries, &self.repo_root) {
+            self.ignored_dirs.extend(
+                ignored
+                    .iter()
+                    .map(|dir| PathBuf::from(dir.trim_end_matches('/'))),
+            );
+        }
+        for dir in dirs {
+            let ignored = dir
+                .strip_prefix(&self.repo_root)
+                .is_ok_and(|relative| self.in_ignored_dir(relative));
+            if !ignored {
+                // The directory may be gone again already
+                let _ = self.watcher.watch(&dir, RecursiveMode::NonRecursive);
+            }
+        }
+    }
+
+    /// Ask git which of the changed paths are ignored. Parent directories are
+    /// checked too and remembered, since nothing below an ignored directory
+    /// can be re-included.
+    fn has_unignored(&mut self, candidates: Vec<PathBuf>, dirs: &[PathBuf]) -> bool {
+        if candidates.is_empty() {
+            return false;
+        }
+
+        let mut queries: Vec<String> = Vec::new();
+        for path in &candidates {
+            if dirs.contains(path) {
+                queries.push(format!("{}/", path.to_string_lossy()));
+            } else {
+                queries.push(path.to_string_lossy().to_string());
+            }
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                queries.push(format!("{}/", parent.to_string_lossy()));
+            }
+        }
+        queries.sort();
+        queries.dedup();
+
+        let Ok(ignored) = crate::git::check_ignore(&queries, &self.repo_root) else {
+            // Without git's answer, assume the change matters
+            return true;
+        };
+
+        for entry in &ignored {
+            if let Some(dir) = entry.strip_suffix('/') {
+                self.ignored_dirs.insert(PathBuf::from(dir));
+            }
+        }
+
+        candidates.iter().any(|path| {
+            !ignored.contains(&path.to_string_lossy().to_string()) && !self.in_ignored_dir(path)
+        })
+    }
+}
+
+/// `start` and the directories below it, leaving out git directories and
+/// the ignored ones (repo-relative in `ignored`) with everything below them
+fn watched_dirs(start: &Path, repo_root: &Path, ignored: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    let mut stack = vec![start.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        if let Ok(entries) = std::fs::read_dir(&dir) {
+            for entry in entries.flatten() {
+                // Symlinks are not followed, as git does not
+                let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+                if !is_dir || entry.file_name() == ".git" {
+                    continue;
+                }
+                let path = entry.path();
+                let relative = path.strip_prefix(repo_root).unwrap_or(&path);
+                if !ignored.contains(relative) {
+                    stack.push(path);
+                }
+            }
+        }
+        dirs.push(dir);
+    }
+    dirs
+}
+
+fn is_change(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Any | EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    )
+}
+
+/// The name of a file inside the git directory that affects what diffview shows
+fn git_state_file<'a>(git_dir: &Path, path: &'a Path) -> Option<&'a str> {
+    path.strip_prefix(git_dir)
+        .ok()
+        .and_then(|p| p.to_str())
+        .filter(|p| GIT_DIR_FILES.contains(p))
+}
+
+/// Collapses a burst of changes into one refresh
+#[derive(Debug)]
+struct Debouncer {
+    delay: Duration,
+    first_change: Option<Instant>,
+    last_change: Option<Instant>,
+}
+
+impl Debouncer {
+    fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            first_change: None,
+            last_change: None,
+        }
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.first_change.get_or_insert(now);
+        self.last_change = Some(now);
+    }
+
+    /// Due once changes have been quiet for `delay`, or a steady stream has
+    /// gone on for `MAX_DELAY_FACTOR` times that long
+    fn take_due(&mut self, now: Instant) -> bool {
+        let (Some(first), Some(last)) = (self.first_change, self.last_change) else {
+            return false;
+        };
+        let quiet = now.duration_since(last) >= self.delay;
+        let overdue = now.duration_since(first) >= self.delay * MAX_DELAY_FACTOR;
+        if quiet || overdue {
+            self.first_change = None;
+            self.last_change = None;
+        }
+        quiet || overdue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git_state_files() {
+        let git_dir = Path::new("/repo/.git");
+        let state_file = |path: &'static str| git_state_file(git_dir, Path::new(path));
+        assert_eq!(state_file("/repo/.git/index"), Some("index"));
+        assert_eq!(state_file("/repo/.git/HEAD"), Some("HEAD"));
+        assert_eq!(state_file("/repo/.git/index.lock"), None);
+        assert_eq!(state_file("/repo/.git/objects/ab/cdef"), None);
+    }
+
+    #[test]
+    fn test_ignored_dirs_are_not_watched() {
+        let root = std::env::temp_dir().join(format!("diffview-watch-{}", std::process::id()));
+        for dir in [
+            "src/git",
+            "target/debug",
+            ".git/objects",
+            "web/node_modules/x",
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let ignored = ["target", "web/node_modules"].map(PathBuf::from).into();
+        let mut dirs: Vec<PathBuf> = watched_dirs(&root, &root, &ignored)
+            .into_iter()
+            .map(|dir| dir.strip_prefix(&root).unwrap().to_path_buf())
+            .collect();
+        dirs.sort();
+        let expected: Vec<PathBuf> = ["", "src", "src/git", "web"].map(PathBuf::from).into();
+        assert_eq!(dirs, expected);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    /// Poll until a refresh is due or `ms` have passed
+    fn due_within(watcher: &mut RepoWatcher, ms: u64) -> bool {
+        let end = Instant::now() + Duration::from_millis(ms);
+        while Instant::now() < end {
+            if watcher.poll() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn test_refreshes_skip_ignored_dirs_and_own_index_writes() {
+        let repo = crate::testing::TestRepo::new("watcher");
+        repo.write(".gitignore", "target/\n");
+        repo.write("a.txt", "a\n");
+        repo.write("target/out", "1\n");
+        repo.commit_all("init");
+        let git_dir = crate::git::get_git_dir(&repo.root).unwrap();
+        let mut watcher =
+            RepoWatcher::new(&repo.root, &git_dir, Duration::from_millis(30)).unwrap();
+
+        repo.write("target/out", "2\n");
+        assert!(!due_within(&mut watcher, 300));
+
+        repo.write("a.txt", "b\n");
+        assert!(due_within(&mut watcher, 3000));
+
+        // A directory created later is watched too
+        std::fs::create_dir(repo.root.join("new")).unwrap();
+        assert!(due_within(&mut watcher, 3000));
+        repo.write("new/x.txt", "x\n");
+        assert!(due_within(&mut watcher, 3000));
+
+        // Staging the way diffview does, followed by its own status load
+        repo.git(&["add", "a.txt"]);
+        watcher.index_seen();
+        assert!(!due_within(&mut watcher, 300));
+        repo.git(&["reset", "-q"]);
+        assert!(due_within(&mut watcher, 3000));
+    }
+
+    #[test]
+    fn test_debounce_waits_for_quiet_period() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(ms(100));
+        assert!(!debouncer.take_due(start));
+
+        debouncer.record(start);
+        debouncer.record(start + ms(80));
+        assert!(!debouncer.take_due(start + ms(150)));
+        assert!(debouncer.take_due(start + ms(180)));
+        assert!(!debouncer.take_due(start + ms(400)));
+
+        // A steady stream still refreshes after the maximum delay
+        for t in (0..500).step_by(50) {
+            debouncer.record(start + ms(t));
+        }
+        assert!(debouncer.take_due(start + ms(500)));
+    }
+}